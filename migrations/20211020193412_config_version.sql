@@ -0,0 +1,36 @@
+-- Add migration script here
+ALTER TABLE guild_config ADD COLUMN version int4 NOT NULL DEFAULT 0;
+
+CREATE TABLE guild_config_backup
+(
+	uid bigserial NOT NULL,
+	guildid int8 NOT NULL,
+	key varchar(64) NOT NULL,
+	version int4 NOT NULL,
+	data jsonb NOT NULL,
+	reason text NOT NULL,
+	created_at timestamptz NOT NULL DEFAULT now(),
+    CONSTRAINT guild_config_backup_pk PRIMARY KEY (uid)
+);
+
+DROP FUNCTION get_config(int8, varchar);
+DROP FUNCTION set_config(int8, varchar, jsonb);
+
+CREATE OR REPLACE FUNCTION get_config (guildid_in int8, key_in varchar) RETURNS TABLE (data jsonb, version int4) AS $$
+    #variable_conflict use_column
+    BEGIN
+        PERFORM pg_advisory_lock(uid) FROM guild_config WHERE guildid = guildid_in AND key = key_in;
+        RETURN QUERY SELECT data, version FROM guild_config WHERE guildid = guildid_in AND key = key_in;
+    END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION set_config (guildid_in int8, key_in varchar, data_in jsonb, version_in int4) RETURNS void AS $$
+    BEGIN
+        IF EXISTS (SELECT * FROM guild_config WHERE guildid = guildid_in AND key = key_in) THEN
+            UPDATE guild_config SET data = data_in, version = version_in WHERE guildid = guildid_in AND key = key_in;
+        ELSE
+            INSERT INTO guild_config (guildid, data, key, version) VALUES (guildid_in, data_in, key_in, version_in);
+        END IF;
+        PERFORM pg_advisory_unlock(uid) FROM guild_config WHERE guildid = guildid_in AND key = key_in;
+    END;
+$$ LANGUAGE plpgsql;
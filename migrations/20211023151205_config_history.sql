@@ -0,0 +1,29 @@
+-- Add migration script here
+CREATE TABLE guild_config_history
+(
+	uid bigserial NOT NULL,
+	guildid int8 NOT NULL,
+	key varchar(64) NOT NULL,
+	authorid int8,
+	old_data jsonb,
+	new_data jsonb NOT NULL,
+	version int4 NOT NULL,
+	created_at timestamptz NOT NULL DEFAULT now(),
+    CONSTRAINT guild_config_history_pk PRIMARY KEY (uid)
+);
+
+DROP FUNCTION set_config(int8, varchar, jsonb, int4);
+
+CREATE OR REPLACE FUNCTION set_config (guildid_in int8, key_in varchar, data_in jsonb, version_in int4, authorid_in int8) RETURNS void AS $$
+    DECLARE previous JSONB;
+    BEGIN
+        SELECT data INTO previous FROM guild_config WHERE guildid = guildid_in AND key = key_in;
+        IF FOUND THEN
+            UPDATE guild_config SET data = data_in, version = version_in WHERE guildid = guildid_in AND key = key_in;
+        ELSE
+            INSERT INTO guild_config (guildid, data, key, version) VALUES (guildid_in, data_in, key_in, version_in);
+        END IF;
+        INSERT INTO guild_config_history (guildid, key, authorid, old_data, new_data, version) VALUES (guildid_in, key_in, authorid_in, previous, data_in, version_in);
+        PERFORM pg_advisory_unlock(uid) FROM guild_config WHERE guildid = guildid_in AND key = key_in;
+    END;
+$$ LANGUAGE plpgsql;
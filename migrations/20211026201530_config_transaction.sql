@@ -0,0 +1,22 @@
+-- Add migration script here
+DROP FUNCTION get_config(int8, varchar);
+
+WITH duplicates AS (
+    DELETE FROM guild_config a USING guild_config b
+    WHERE a.guildid = b.guildid AND a.key = b.key AND a.uid < b.uid
+    RETURNING a.*
+)
+INSERT INTO guild_config_backup (guildid, key, version, data, reason)
+SELECT guildid, key, version, data, 'Duplicated row removed when adding the (guildid, key) constraint' FROM duplicates;
+
+ALTER TABLE guild_config ADD CONSTRAINT guild_config_un UNIQUE (guildid, key);
+
+CREATE OR REPLACE FUNCTION set_config (guildid_in int8, key_in varchar, data_in jsonb, version_in int4, authorid_in int8) RETURNS void AS $$
+    DECLARE previous JSONB;
+    BEGIN
+        SELECT data INTO previous FROM guild_config WHERE guildid = guildid_in AND key = key_in;
+        INSERT INTO guild_config (guildid, data, key, version) VALUES (guildid_in, data_in, key_in, version_in)
+            ON CONFLICT (guildid, key) DO UPDATE SET data = EXCLUDED.data, version = EXCLUDED.version;
+        INSERT INTO guild_config_history (guildid, key, authorid, old_data, new_data, version) VALUES (guildid_in, key_in, authorid_in, previous, data_in, version_in);
+    END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,46 @@
+-- Add migration script here
+ALTER TABLE guild_config ADD COLUMN scope varchar(16) NOT NULL DEFAULT 'guild';
+ALTER TABLE guild_config ADD COLUMN scopeid int8;
+UPDATE guild_config SET scopeid = guildid;
+ALTER TABLE guild_config ALTER COLUMN scopeid SET NOT NULL;
+ALTER TABLE guild_config ALTER COLUMN scope DROP DEFAULT;
+ALTER TABLE guild_config DROP CONSTRAINT guild_config_un;
+ALTER TABLE guild_config ADD CONSTRAINT guild_config_un UNIQUE (guildid, key, scope, scopeid);
+
+ALTER TABLE guild_config_history ADD COLUMN scope varchar(16) NOT NULL DEFAULT 'guild';
+ALTER TABLE guild_config_history ADD COLUMN scopeid int8;
+UPDATE guild_config_history SET scopeid = guildid;
+ALTER TABLE guild_config_history ALTER COLUMN scopeid SET NOT NULL;
+ALTER TABLE guild_config_history ALTER COLUMN scope DROP DEFAULT;
+ALTER TABLE guild_config_history ALTER COLUMN new_data DROP NOT NULL;
+
+ALTER TABLE guild_config_backup ADD COLUMN scope varchar(16) NOT NULL DEFAULT 'guild';
+ALTER TABLE guild_config_backup ADD COLUMN scopeid int8;
+UPDATE guild_config_backup SET scopeid = guildid;
+ALTER TABLE guild_config_backup ALTER COLUMN scopeid SET NOT NULL;
+ALTER TABLE guild_config_backup ALTER COLUMN scope DROP DEFAULT;
+
+DROP FUNCTION set_config(int8, varchar, jsonb, int4, int8);
+
+CREATE OR REPLACE FUNCTION set_config (guildid_in int8, scope_in varchar, scopeid_in int8, key_in varchar, data_in jsonb, version_in int4, authorid_in int8) RETURNS void AS $$
+    DECLARE previous JSONB;
+    BEGIN
+        SELECT data INTO previous FROM guild_config WHERE guildid = guildid_in AND scope = scope_in AND scopeid = scopeid_in AND key = key_in;
+        INSERT INTO guild_config (guildid, scope, scopeid, data, key, version) VALUES (guildid_in, scope_in, scopeid_in, data_in, key_in, version_in)
+            ON CONFLICT (guildid, key, scope, scopeid) DO UPDATE SET data = EXCLUDED.data, version = EXCLUDED.version;
+        INSERT INTO guild_config_history (guildid, scope, scopeid, key, authorid, old_data, new_data, version) VALUES (guildid_in, scope_in, scopeid_in, key_in, authorid_in, previous, data_in, version_in);
+    END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION remove_config (guildid_in int8, scope_in varchar, scopeid_in int8, key_in varchar, authorid_in int8) RETURNS bool AS $$
+    DECLARE previous JSONB;
+    DECLARE previous_version int4;
+    BEGIN
+        DELETE FROM guild_config WHERE guildid = guildid_in AND scope = scope_in AND scopeid = scopeid_in AND key = key_in RETURNING data, version INTO previous, previous_version;
+        IF NOT FOUND THEN
+            RETURN false;
+        END IF;
+        INSERT INTO guild_config_history (guildid, scope, scopeid, key, authorid, old_data, new_data, version) VALUES (guildid_in, scope_in, scopeid_in, key_in, authorid_in, previous, NULL, previous_version);
+        RETURN true;
+    END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE user_permission ADD denied text[] NOT NULL DEFAULT '{}';
+ALTER TABLE role_permission ADD denied text[] NOT NULL DEFAULT '{}';
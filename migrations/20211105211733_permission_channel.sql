@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- 0 means the permissions apply in the whole guild
+ALTER TABLE user_permission ADD channelid int8 NOT NULL DEFAULT 0;
+ALTER TABLE role_permission ADD channelid int8 NOT NULL DEFAULT 0;
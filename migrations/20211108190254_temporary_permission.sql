@@ -0,0 +1,17 @@
+-- Add migration script here
+
+-- Grants that expire, only one of userid and roleid is set
+CREATE TABLE temporary_permission (
+	uid bigserial NOT NULL,
+	guildid int8 NOT NULL,
+	userid int8 NULL,
+	roleid int8 NULL,
+	channelid int8 NOT NULL DEFAULT 0,
+	permission text NOT NULL,
+	granterid int8 NOT NULL,
+	expires_at timestamptz NOT NULL,
+	CONSTRAINT temporary_permission_pk PRIMARY KEY (uid),
+	CONSTRAINT temporary_permission_target CHECK ((userid IS NULL) <> (roleid IS NULL))
+);
+
+CREATE INDEX temporary_permission_expires_at ON temporary_permission (expires_at);
@@ -0,0 +1,28 @@
+-- Add migration script here
+
+-- Tell every process caching permissions which entries changed.
+-- The payload is `user <guildid> <userid>` or `role <guildid>`
+CREATE OR REPLACE FUNCTION notify_permission_change() RETURNS trigger AS $$
+DECLARE
+	entry jsonb;
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		entry := to_jsonb(OLD);
+	ELSE
+		entry := to_jsonb(NEW);
+	END IF;
+	IF entry->>'userid' IS NOT NULL THEN
+		PERFORM pg_notify('permission_change', 'user ' || (entry->>'guildid') || ' ' || (entry->>'userid'));
+	ELSE
+		PERFORM pg_notify('permission_change', 'role ' || (entry->>'guildid'));
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_permission_notify AFTER INSERT OR UPDATE OR DELETE ON user_permission
+	FOR EACH ROW EXECUTE FUNCTION notify_permission_change();
+CREATE TRIGGER role_permission_notify AFTER INSERT OR UPDATE OR DELETE ON role_permission
+	FOR EACH ROW EXECUTE FUNCTION notify_permission_change();
+CREATE TRIGGER temporary_permission_notify AFTER INSERT OR UPDATE OR DELETE ON temporary_permission
+	FOR EACH ROW EXECUTE FUNCTION notify_permission_change();
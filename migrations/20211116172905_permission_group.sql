@@ -0,0 +1,35 @@
+-- Add migration script here
+
+-- Named bundles of permissions, granted as `group:<name>`
+CREATE TABLE permission_group (
+	uid bigserial NOT NULL,
+	guildid int8 NOT NULL,
+	name text NOT NULL,
+	permissions text[] NOT NULL DEFAULT '{}',
+	CONSTRAINT permission_group_pk PRIMARY KEY (uid),
+	CONSTRAINT permission_group_name UNIQUE (guildid, name)
+);
+
+-- A group change affects every user and role of the guild, the payload is `guild <guildid>`
+CREATE OR REPLACE FUNCTION notify_permission_change() RETURNS trigger AS $$
+DECLARE
+	entry jsonb;
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		entry := to_jsonb(OLD);
+	ELSE
+		entry := to_jsonb(NEW);
+	END IF;
+	IF TG_TABLE_NAME = 'permission_group' THEN
+		PERFORM pg_notify('permission_change', 'guild ' || (entry->>'guildid'));
+	ELSIF entry->>'userid' IS NOT NULL THEN
+		PERFORM pg_notify('permission_change', 'user ' || (entry->>'guildid') || ' ' || (entry->>'userid'));
+	ELSE
+		PERFORM pg_notify('permission_change', 'role ' || (entry->>'guildid'));
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER permission_group_notify AFTER INSERT OR UPDATE OR DELETE ON permission_group
+	FOR EACH ROW EXECUTE FUNCTION notify_permission_change();
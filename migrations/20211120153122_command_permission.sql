@@ -0,0 +1,38 @@
+-- Add migration script here
+
+-- The permission required to use a command in a guild, replacing the one it has by default.
+-- `command` is the full path of the command like `playlist delete`, a NULL permission opens it to everyone
+CREATE TABLE command_permission (
+	uid bigserial NOT NULL,
+	guildid int8 NOT NULL,
+	command text NOT NULL,
+	permission text NULL,
+	CONSTRAINT command_permission_pk PRIMARY KEY (uid),
+	CONSTRAINT command_permission_command UNIQUE (guildid, command)
+);
+
+-- The payload is `command <guildid>` for the command bindings
+CREATE OR REPLACE FUNCTION notify_permission_change() RETURNS trigger AS $$
+DECLARE
+	entry jsonb;
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		entry := to_jsonb(OLD);
+	ELSE
+		entry := to_jsonb(NEW);
+	END IF;
+	IF TG_TABLE_NAME = 'command_permission' THEN
+		PERFORM pg_notify('permission_change', 'command ' || (entry->>'guildid'));
+	ELSIF TG_TABLE_NAME = 'permission_group' THEN
+		PERFORM pg_notify('permission_change', 'guild ' || (entry->>'guildid'));
+	ELSIF entry->>'userid' IS NOT NULL THEN
+		PERFORM pg_notify('permission_change', 'user ' || (entry->>'guildid') || ' ' || (entry->>'userid'));
+	ELSE
+		PERFORM pg_notify('permission_change', 'role ' || (entry->>'guildid'));
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER command_permission_notify AFTER INSERT OR UPDATE OR DELETE ON command_permission
+	FOR EACH ROW EXECUTE FUNCTION notify_permission_change();
@@ -0,0 +1,10 @@
+-- Add migration script here
+
+-- The queue of every guild the bot is playing in, to resume it after a restart
+CREATE TABLE music_queue (
+	guildid int8 NOT NULL,
+	channelid int8 NOT NULL,
+	queue jsonb NOT NULL,
+	saved_at timestamptz NOT NULL DEFAULT now(),
+	CONSTRAINT music_queue_pk PRIMARY KEY (guildid)
+);
@@ -0,0 +1,15 @@
+-- Add migration script here
+
+-- Every song played in a guild
+CREATE TABLE music_history (
+	uid bigserial NOT NULL,
+	guildid int8 NOT NULL,
+	url text NOT NULL,
+	title text NULL,
+	duration_ms int8 NULL,
+	added_by int8 NOT NULL,
+	played_at timestamptz NOT NULL DEFAULT now(),
+	CONSTRAINT music_history_pk PRIMARY KEY (uid)
+);
+
+CREATE INDEX music_history_guildid_idx ON music_history (guildid, played_at DESC);
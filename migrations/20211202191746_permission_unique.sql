@@ -0,0 +1,19 @@
+-- Add migration script here
+
+-- Rows created twice by concurrent grants are merged into the oldest one before the targets become unique
+UPDATE user_permission AS keep SET
+	ids = ARRAY(SELECT DISTINCT p FROM user_permission AS dup, unnest(dup.ids) AS p WHERE dup.guildid = keep.guildid AND dup.userid = keep.userid AND dup.channelid = keep.channelid),
+	denied = ARRAY(SELECT DISTINCT p FROM user_permission AS dup, unnest(dup.denied) AS p WHERE dup.guildid = keep.guildid AND dup.userid = keep.userid AND dup.channelid = keep.channelid)
+WHERE keep.uid = (SELECT min(uid) FROM user_permission AS dup WHERE dup.guildid = keep.guildid AND dup.userid = keep.userid AND dup.channelid = keep.channelid);
+DELETE FROM user_permission AS dup USING user_permission AS keep
+WHERE dup.guildid = keep.guildid AND dup.userid = keep.userid AND dup.channelid = keep.channelid AND dup.uid > keep.uid;
+
+UPDATE role_permission AS keep SET
+	ids = ARRAY(SELECT DISTINCT p FROM role_permission AS dup, unnest(dup.ids) AS p WHERE dup.guildid = keep.guildid AND dup.roleid = keep.roleid AND dup.channelid = keep.channelid),
+	denied = ARRAY(SELECT DISTINCT p FROM role_permission AS dup, unnest(dup.denied) AS p WHERE dup.guildid = keep.guildid AND dup.roleid = keep.roleid AND dup.channelid = keep.channelid)
+WHERE keep.uid = (SELECT min(uid) FROM role_permission AS dup WHERE dup.guildid = keep.guildid AND dup.roleid = keep.roleid AND dup.channelid = keep.channelid);
+DELETE FROM role_permission AS dup USING role_permission AS keep
+WHERE dup.guildid = keep.guildid AND dup.roleid = keep.roleid AND dup.channelid = keep.channelid AND dup.uid > keep.uid;
+
+ALTER TABLE user_permission ADD CONSTRAINT user_permission_target UNIQUE (guildid, userid, channelid);
+ALTER TABLE role_permission ADD CONSTRAINT role_permission_target UNIQUE (guildid, roleid, channelid);
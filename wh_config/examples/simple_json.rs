@@ -25,26 +25,27 @@ async fn main() {
         .await
         .unwrap();
 
-    let mut guard = wh_config::shared::get_config_or_default::<Conf>(&pool, 1)
-        .await
-        .unwrap();
-
-    dbg!(&guard);
-
-    guard.list.push(1);
-    guard.list.push(2);
-    guard.list.push(3);
-    guard.list.push(4);
-
     let date = {
         let process = std::process::Command::new("date").output().unwrap();
 
         String::from_utf8(process.stdout).unwrap()
     };
 
-    guard.s = date;
+    let conf = wh_config::shared::update_config::<Conf, _, _>(&pool, 1, None, |conf| {
+        dbg!(&conf);
+
+        conf.list.push(1);
+        conf.list.push(2);
+        conf.list.push(3);
+        conf.list.push(4);
+
+        conf.s = date.clone();
 
-    guard.num = 7;
+        conf.num = 7;
+        Ok(conf.clone())
+    })
+    .await
+    .unwrap();
 
-    wh_config::shared::set_config(guard).await.unwrap();
+    dbg!(conf);
 }
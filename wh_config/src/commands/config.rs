@@ -0,0 +1,283 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, CommandResult};
+use serenity::model::channel::Message;
+
+use config_cmd::*;
+
+#[command]
+#[only_in(guilds)]
+#[sub_commands(history, rollback, export, apply)]
+/// The top level command used to inspect and restore the guild's configuration
+pub async fn config(ctx: &Context, msg: &Message) -> CommandResult {
+    reply_message!(ctx, msg, fluent!(CONFIG_subcommands));
+    Ok(())
+}
+
+/// Send the page `page_num` of the changes of `owner` to `key`
+pub(crate) async fn send_history(
+    ctx: &Context,
+    msg: &Message,
+    owner: crate::shared::HistoryOwner,
+    key: &str,
+    page_num: u16,
+) -> CommandResult {
+    use serenity::builder::{CreateEmbed, CreateMessage};
+    use serenity::prelude::Mentionable;
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+
+    let total = crate::shared::count_config_history(db, owner, key).await?;
+    if total == 0 {
+        message_err!(format!(fluent!(CONFIG_no_history), key));
+    }
+    let len =
+        ((total + crate::shared::HISTORY_PAGE_SIZE - 1) / crate::shared::HISTORY_PAGE_SIZE) as u16;
+    let page_num = page_num.min(len - 1);
+    let entries = crate::shared::get_config_history(db, owner, key, page_num).await?;
+
+    let bot = ctx.cache.current_user_id().await;
+    let mut content = String::new();
+    for entry in entries {
+        use std::fmt::Write;
+        let data = match &entry.new_data {
+            Some(data) => {
+                let mut data = serde_json::to_string(data)?;
+                if data.len() > 150 {
+                    data = data.chars().take(150).collect::<String>() + "...";
+                }
+                format!("```json\n{}\n```", data)
+            }
+            None => String::from("*(removed)*"),
+        };
+        writeln!(
+            content,
+            "`#{uid}` <t:{time}:f> by {author} in {scope} *(v{version})*\n{data}",
+            uid = entry.uid,
+            time = entry.created_at.timestamp(),
+            author = entry
+                .authorid
+                .map(|a| serenity::model::id::UserId(a.0))
+                .unwrap_or(bot)
+                .mention(),
+            scope = entry.scope,
+            version = entry.version,
+            data = data,
+        )?;
+    }
+
+    let mut message = CreateMessage::default();
+    let mut embed = CreateEmbed::default();
+    embed.author(|f| f.name(format!("Config history - {}", key)));
+    embed.description(content);
+    embed.footer(|f| f.text(format!("Page {}/{}", page_num + 1, len)));
+    message.set_embed(embed);
+    msg.channel_id
+        .send_message(&ctx.http, |c| {
+            *c = message;
+            c
+        })
+        .await?;
+    Ok(())
+}
+
+mod config_cmd {
+    use serenity::client::Context;
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+
+    #[command]
+    #[only_in(guilds)]
+    #[min_args(1)]
+    #[max_args(2)]
+    #[usage("[key] [?page]")]
+    #[example("points.event.join 2")]
+    /// Show who changed the given config key and when, newest changes first
+    /// The personal preferences are in the history of each user, like `language history`
+    pub async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let key = args.single::<String>();
+        if key.is_err() {
+            message_err!(fluent!(CONFIG_ARG_key_missing));
+        }
+        let key = key.unwrap();
+        let page_num = args.single::<u16>().unwrap_or(1).max(1) - 1;
+
+        super::send_history(
+            ctx,
+            msg,
+            crate::shared::HistoryOwner::Guild(msg.guild_id.unwrap().0),
+            &key,
+            page_num,
+        )
+        .await
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[num_args(1)]
+    #[usage("[id]")]
+    #[example("12")]
+    /// Restore a config key to the value it had right after the given change (the id is shown by `config history`)
+    pub async fn rollback(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let uid = args.single::<i64>();
+        if uid.is_err() {
+            message_err!(fluent!(CONFIG_ARG_invalid_id));
+        }
+        let uid = uid.unwrap();
+
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+
+        let entry = crate::shared::rollback_config(
+            db,
+            crate::shared::HistoryOwner::Guild(msg.guild_id.unwrap().0),
+            uid,
+            msg.author.id.0,
+        )
+        .await?;
+        match entry {
+            Some(entry) => {
+                reply_message!(
+                    ctx,
+                    msg,
+                    format!(fluent!(CONFIG_rollback_done), entry.key, entry.uid)
+                );
+            }
+            None => message_err!(fluent!(CONFIG_history_not_found)),
+        }
+        Ok(())
+    }
+
+    /// Map the guild's role ids to their names
+    async fn guild_roles(
+        ctx: &Context,
+        msg: &Message,
+    ) -> serenity::Result<std::collections::HashMap<u64, String>> {
+        Ok(msg
+            .guild_id
+            .unwrap()
+            .roles(ctx)
+            .await?
+            .into_iter()
+            .map(|(id, role)| (id.0, role.name))
+            .collect())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[max_args(1)]
+    #[usage("[?toml|yaml]")]
+    #[example("yaml")]
+    /// Export the guild's configs, role points and role permissions and denies as a template file
+    pub async fn export(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let format = match args.single::<String>() {
+            Ok(name) => match crate::shared::TemplateFormat::from_name(&name) {
+                Some(format) => format,
+                None => message_err!(fluent!(CONFIG_ARG_invalid_format)),
+            },
+            Err(_) => crate::shared::TemplateFormat::Toml,
+        };
+        let roles = guild_roles(ctx, msg).await?;
+
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let template = crate::shared::export_template(db, msg.guild_id.unwrap().0, &roles).await?;
+        let data = format.serialize(&template)?;
+        let filename = format!("template.{}", format.extension());
+
+        msg.channel_id
+            .send_files(&ctx.http, vec![(data.as_bytes(), filename.as_str())], |m| {
+                m.content(fluent!(CONFIG_template_exported))
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[max_args(1)]
+    #[usage("[?dry]")]
+    #[example("dry")]
+    /// Apply the template file attached to the message to the guild, roles are matched by name.
+    /// With `dry` the changes are only shown.
+    /// The permissions are checked like with `permission add`, changing one that implies `permission.manage` needs the ADMINISTRATOR discord permission
+    pub async fn apply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        use serenity::builder::{CreateEmbed, CreateMessage};
+        let dry_run = match args.single::<String>() {
+            Ok(arg) if arg == "dry" => true,
+            Ok(_) => message_err!(fluent!(CONFIG_ARG_invalid_apply)),
+            Err(_) => false,
+        };
+        let attachment = match msg.attachments.first() {
+            Some(a) => a,
+            None => message_err!(fluent!(CONFIG_ARG_template_missing)),
+        };
+        let format = match attachment
+            .filename
+            .rsplit_once('.')
+            .and_then(|(_, ext)| crate::shared::TemplateFormat::from_name(ext))
+        {
+            Some(format) => format,
+            None => message_err!(fluent!(CONFIG_ARG_invalid_format)),
+        };
+        let data = String::from_utf8(attachment.download().await?);
+        let template = match data
+            .map_err(Into::into)
+            .and_then(|d| format.deserialize(&d))
+        {
+            Ok(t) => t,
+            Err(e) => message_err!(format!(fluent!(CONFIG_template_invalid), e)),
+        };
+        let roles = guild_roles(ctx, msg).await?;
+        let administrator = msg
+            .guild(&ctx.cache)
+            .await
+            .unwrap()
+            .member_permissions(ctx, msg.author.id)
+            .await?
+            .administrator();
+
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let changes = crate::shared::apply_template(
+            db,
+            msg.guild_id.unwrap().0,
+            &template,
+            &roles,
+            Some(msg.author.id.0),
+            administrator,
+            dry_run,
+        )
+        .await?;
+
+        let mut content = String::new();
+        for change in &changes {
+            use std::fmt::Write;
+            writeln!(content, "{}", change)?;
+        }
+        if changes.is_empty() {
+            content.push_str(fluent!(CONFIG_template_no_change));
+        }
+        if content.len() > 4000 {
+            content = content.chars().take(4000).collect::<String>() + "...";
+        }
+
+        let mut message = CreateMessage::default();
+        let mut embed = CreateEmbed::default();
+        embed.author(|f| {
+            f.name(if dry_run {
+                "Template changes (dry run)"
+            } else {
+                "Template applied"
+            })
+        });
+        embed.description(content);
+        message.set_embed(embed);
+        msg.channel_id
+            .send_message(&ctx.http, |c| {
+                *c = message;
+                c
+            })
+            .await?;
+        Ok(())
+    }
+}
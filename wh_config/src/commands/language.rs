@@ -0,0 +1,103 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+use language_cmd::*;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(1)]
+#[usage("[?code]")]
+#[example("fr")]
+#[sub_commands(history, rollback)]
+/// Show or set the language you want the bot to use with you
+pub async fn language(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    use crate::shared::{Language, Scope};
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    let userid = msg.author.id.0;
+
+    let code = match args.single::<String>() {
+        Ok(code) => code.to_lowercase(),
+        Err(_) => {
+            let scopes = Scope::chain(msg.guild_id.unwrap().0, None, Some(userid));
+            let language =
+                crate::shared::resolve_config_or_default::<Language>(db, &scopes).await?;
+            reply_message!(
+                ctx,
+                msg,
+                format!(fluent!(CONFIG_language_current), language.code)
+            );
+            return Ok(());
+        }
+    };
+    if !(2..=8).contains(&code.len())
+        || !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        message_err!(fluent!(CONFIG_ARG_invalid_language));
+    }
+    crate::shared::update_scoped_config::<Language, _, _>(
+        db,
+        Scope::User(userid),
+        Some(userid),
+        |l| {
+            l.code = code.clone();
+            Ok(())
+        },
+    )
+    .await?;
+    reply_message!(ctx, msg, format!(fluent!(CONFIG_language_set), code));
+    Ok(())
+}
+
+mod language_cmd {
+    use crate::shared::{HistoryOwner, Language};
+    use serenity::client::Context;
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+
+    #[command]
+    #[only_in(guilds)]
+    #[max_args(1)]
+    #[usage("[?page]")]
+    #[example("2")]
+    /// Show the changes of your language, it is shared by every guild so they are not in `config history`
+    pub async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let page_num = args.single::<u16>().unwrap_or(1).max(1) - 1;
+        crate::commands::config::send_history(
+            ctx,
+            msg,
+            HistoryOwner::User(msg.author.id.0),
+            <Language as crate::shared::Config>::KEY,
+            page_num,
+        )
+        .await
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[num_args(1)]
+    #[usage("[id]")]
+    #[example("12")]
+    /// Put your language back to the value it had right after the given change (the id is shown by `language history`)
+    pub async fn rollback(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let uid = match args.single::<i64>() {
+            Ok(uid) => uid,
+            Err(_) => message_err!(fluent!(CONFIG_ARG_invalid_id)),
+        };
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let userid = msg.author.id.0;
+        match crate::shared::rollback_config(db, HistoryOwner::User(userid), uid, userid).await? {
+            Some(entry) => {
+                reply_message!(
+                    ctx,
+                    msg,
+                    format!(fluent!(CONFIG_rollback_done), entry.key, entry.uid)
+                );
+            }
+            None => message_err!(fluent!(CONFIG_personal_history_not_found)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+add_commands!(Config, (config), (config_manage));
+
+check_permission!(
+    CONFIG_MANAGE_CHECK,
+    "config.manage",
+    fluent!(CONFIG_PERMISSION_manage),
+    wh_permission::shared::registry::DefaultPolicy::Discord(
+        serenity::model::permissions::Permissions::MANAGE_GUILD
+    )
+);
+
+add_commands!(ConfigUser, (language), ());
@@ -3,11 +3,18 @@ extern crate sqlx;
 extern crate serde;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate wh_core;
+#[macro_use]
+extern crate wh_permission;
+#[macro_use]
+extern crate fluent_const;
 
+extern crate chrono;
 extern crate serde_json;
 extern crate serenity;
-extern crate wh_core;
 extern crate wh_database;
 
+mod commands;
 pub mod module;
 pub mod shared;
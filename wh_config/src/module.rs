@@ -1,6 +1,9 @@
 pub static MODULE_DECLARATION: wh_core::ModuleDeclaration = wh_core::ModuleDeclaration {
     module_name: "Config",
-    command_groups: &[],
+    command_groups: &[
+        &crate::commands::CONFIG_GROUP,
+        &crate::commands::CONFIGUSER_GROUP,
+    ],
     register_typemap: |t| Box::pin(register_typemap(t)),
     register_event_handler: |e| Box::pin(register_event_handler(e)),
     register_builder,
@@ -21,7 +24,8 @@ fn register_builder(
 fn register_intent(
     intent: serenity::client::bridge::gateway::GatewayIntents,
 ) -> serenity::client::bridge::gateway::GatewayIntents {
-    intent
+    use serenity::client::bridge::gateway::GatewayIntents as I;
+    intent | I::GUILD_MESSAGES
 }
 
 fn register_init() {}
@@ -4,13 +4,124 @@ use std::fmt::Display;
 
 pub trait Config: Serialize + DeserializeOwned {
     const KEY: &'static str;
+    /// Version of the JSON layout, bump it when the struct changes and handle the old layout in [`Config::migrate`]
+    const VERSION: u32 = 0;
+
+    /// Upgrade `data` stored with the layout of `version` to the layout of `version + 1`.
+    /// It is called as many times as needed to get to [`Config::VERSION`] when a config is read
+    fn migrate(version: u32, _data: serde_json::Value) -> AllResult<serde_json::Value> {
+        Err(format!(
+            "No migration from version {} for config `{}`",
+            version,
+            Self::KEY
+        )
+        .into())
+    }
 }
 
-pub struct ConfigGuard<T: Config> {
-    db: sqlx::pool::PoolConnection<sqlx::Postgres>,
-    guildid: u64,
-    data: T,
+/// A config type the bot reads, registered with [`crate::register_config!`] so the values coming from a template can be checked
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigInfo {
+    pub key: &'static str,
+    pub version: u32,
+    /// Migrate a value stored with a version to [`ConfigInfo::version`], it fails if the result isn't a valid config
+    pub check: fn(i32, serde_json::Value) -> AllResult<serde_json::Value>,
+}
+
+pub use inventory;
+inventory::collect!(ConfigInfo);
+
+/// Register a [`Config`] at link time, templates can only contain the registered keys
+#[macro_export]
+macro_rules! register_config {
+    ($config:ty) => {
+        $crate::shared::inventory::submit! {
+            $crate::shared::ConfigInfo {
+                key: <$config as $crate::shared::Config>::KEY,
+                version: <$config as $crate::shared::Config>::VERSION,
+                check: $crate::shared::check_config::<$config>,
+            }
+        }
+    };
+}
+
+/// The registered config with this key
+pub fn config_info(key: &str) -> Option<&'static ConfigInfo> {
+    inventory::iter::<ConfigInfo>
+        .into_iter()
+        .find(|info| info.key == key)
 }
+
+/// See [`ConfigInfo::check`]
+pub fn check_config<T: Config>(
+    version: i32,
+    data: serde_json::Value,
+) -> AllResult<serde_json::Value> {
+    let (config, _) = upgrade::<T>(version, data)?;
+    Ok(serde_json::value::to_value(config)?)
+}
+
+/// Where a config value applies.
+/// When resolving a config, the narrowest scope with a value wins (see [`Scope::chain`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// The whole bot, used when no guild has a value
+    Global,
+    Guild(u64),
+    Channel {
+        guildid: u64,
+        channelid: u64,
+    },
+    /// A user's personal preference, shared across every guild
+    User(u64),
+}
+
+impl Scope {
+    /// The `(guildid, scope, scopeid)` columns that identify this scope in the database
+    fn columns(self) -> (u64, &'static str, u64) {
+        match self {
+            Scope::Global => (0, "global", 0),
+            Scope::Guild(guildid) => (guildid, "guild", guildid),
+            Scope::Channel { guildid, channelid } => (guildid, "channel", channelid),
+            Scope::User(userid) => (0, "user", userid),
+        }
+    }
+
+    fn from_columns(guildid: u64, scope: &str, scopeid: u64) -> Option<Self> {
+        Some(match scope {
+            "global" => Scope::Global,
+            "guild" => Scope::Guild(guildid),
+            "channel" => Scope::Channel {
+                guildid,
+                channelid: scopeid,
+            },
+            "user" => Scope::User(scopeid),
+            _ => return None,
+        })
+    }
+
+    /// The scopes to look into, narrowest first: user, channel, guild and then global
+    pub fn chain(guildid: u64, channelid: Option<u64>, userid: Option<u64>) -> Vec<Scope> {
+        let mut out = Vec::with_capacity(4);
+        out.extend(userid.map(Scope::User));
+        out.extend(channelid.map(|channelid| Scope::Channel { guildid, channelid }));
+        out.push(Scope::Guild(guildid));
+        out.push(Scope::Global);
+        out
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Global => write!(f, "global"),
+            Scope::Guild(_) => write!(f, "guild"),
+            Scope::Channel { channelid, .. } => write!(f, "channel <#{}>", channelid),
+            Scope::User(userid) => write!(f, "user <@{}>", userid),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub struct ReadConfig<T: Config> {
     inner: T,
@@ -36,157 +147,1120 @@ impl<T: Config> std::ops::Deref for ReadConfig<T> {
     }
 }
 
-impl<T: Config + Debug + ?Sized> Debug for ConfigGuard<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.data.fmt(f)
+pub type AllResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Run the migration chain on `data` stored with `version` and deserialize it.
+/// The returned boolean is `true` if at least one migration was applied
+fn upgrade<T: Config>(version: i32, mut data: serde_json::Value) -> AllResult<(T, bool)> {
+    let mut version = u32::try_from(version)?;
+    if version > T::VERSION {
+        return Err(format!(
+            "Stored version {} is newer than the supported version {}",
+            version,
+            T::VERSION
+        )
+        .into());
     }
+    let migrated = version < T::VERSION;
+    while version < T::VERSION {
+        data = T::migrate(version, data)?;
+        version += 1;
+    }
+    Ok((serde_json::value::from_value(data)?, migrated))
 }
 
-impl<T: Config + Display + ?Sized> Display for ConfigGuard<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.data.fmt(f)
+/// Turn a stored row into `T`.
+/// A migrated row is written back with the new version, a row that can't be migrated or deserialized is copied into `guild_config_backup` and treated as missing
+async fn load_config<'e, T, E>(
+    executor: E,
+    scope: Scope,
+    version: i32,
+    data: serde_json::Value,
+) -> AllResult<Option<T>>
+where
+    T: Config,
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let (guildid, scope_name, scopeid) = scope.columns();
+    match upgrade::<T>(version, data.clone()) {
+        Ok((val, migrated)) => {
+            if migrated {
+                query!(
+                    "
+                    WITH updated AS (
+                        UPDATE guild_config SET data = $5::jsonb, version = $6::int4
+                        WHERE guildid = $1::int8 AND scope = $2::varchar AND scopeid = $3::int8 AND key = $4::varchar AND version = $7::int4
+                        RETURNING guildid, scope, scopeid, key
+                    )
+                    INSERT INTO guild_config_history (guildid, scope, scopeid, key, authorid, old_data, new_data, version)
+                    SELECT guildid, scope, scopeid, key, NULL, $8::jsonb, $5::jsonb, $6::int4 FROM updated
+                    ",
+                    wh_database::shared::Id(guildid) as _,
+                    scope_name,
+                    wh_database::shared::Id(scopeid) as _,
+                    <T as Config>::KEY,
+                    serde_json::value::to_value(&val)? as _,
+                    <T as Config>::VERSION as i32,
+                    version,
+                    data as _,
+                )
+                .execute(executor)
+                .await?;
+            }
+            Ok(Some(val))
+        }
+        Err(e) => {
+            error!(
+                "Error when loading config `{}` (version {}, {}), a backup has been made: {}",
+                <T as Config>::KEY,
+                version,
+                scope,
+                e
+            );
+            query!(
+                "
+                INSERT INTO guild_config_backup (guildid, scope, scopeid, key, version, data, reason)
+                SELECT $1::int8, $2::varchar, $3::int8, $4::varchar, $5::int4, $6::jsonb, $7::text
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM guild_config_backup
+                    WHERE guildid = $1::int8 AND scope = $2::varchar AND scopeid = $3::int8 AND key = $4::varchar AND version = $5::int4 AND data = $6::jsonb
+                )
+                ",
+                wh_database::shared::Id(guildid) as _,
+                scope_name,
+                wh_database::shared::Id(scopeid) as _,
+                <T as Config>::KEY,
+                version,
+                data as _,
+                e.to_string(),
+            )
+            .execute(executor)
+            .await?;
+            Ok(None)
+        }
     }
 }
 
-impl<T: Config> std::ops::Deref for ConfigGuard<T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.data
-    }
+/// Write `data` for `key` and record the change in the config history
+async fn write_config<'e, E>(
+    executor: E,
+    scope: Scope,
+    key: &str,
+    data: serde_json::Value,
+    version: i32,
+    author: Option<u64>,
+) -> AllResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let (guildid, scope_name, scopeid) = scope.columns();
+    query!(
+        "SELECT * FROM set_config($1::int8, $2::varchar, $3::int8, $4::varchar, $5::jsonb, $6::int4, $7::int8)",
+        wh_database::shared::Id(guildid) as _,
+        scope_name,
+        wh_database::shared::Id(scopeid) as _,
+        key,
+        data as _,
+        version,
+        author.map(wh_database::shared::Id) as _,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
 }
 
-impl<T: Config> std::ops::DerefMut for ConfigGuard<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.data
+/// Remove the value of `key` at `scope` and record the change in the config history.
+/// Returns `false` if there was no value to remove
+async fn delete_config<'e, E>(
+    executor: E,
+    scope: Scope,
+    key: &str,
+    author: Option<u64>,
+) -> AllResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let (guildid, scope_name, scopeid) = scope.columns();
+    Ok(query!(
+        "SELECT * FROM remove_config($1::int8, $2::varchar, $3::int8, $4::varchar, $5::int8)",
+        wh_database::shared::Id(guildid) as _,
+        scope_name,
+        wh_database::shared::Id(scopeid) as _,
+        key,
+        author.map(wh_database::shared::Id) as _,
+    )
+    .fetch_one(executor)
+    .await?
+    .remove_config
+    .unwrap_or(false))
+}
+
+const WRITE_RETRIES: usize = 5;
+
+/// Returns true if the error comes from a transaction that conflicted with another one and can be retried
+fn is_conflict(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    match error.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(e)) => matches!(
+            e.code().as_deref(),
+            Some("40001" /* serialization_failure */)
+                | Some("40P01" /* deadlock_detected */)
+                | Some("23505" /* unique_violation */)
+        ),
+        _ => false,
     }
 }
 
-impl<T: Config> Drop for ConfigGuard<T> {
-    fn drop(&mut self) {
-        panic!("This struct must be discareded with the function \"set_config\" and not dropped")
+/// Lock the value of `key` at `scope` until the end of the transaction and read it as `(version, data)`.
+/// A row lock covers nothing while no value is stored, so an advisory lock on the guild, scope and key is taken first and also serializes the first insert
+async fn lock_config(
+    tx: &mut sqlx::Transaction<'static, sqlx::Postgres>,
+    scope: Scope,
+    key: &str,
+) -> AllResult<Option<(i32, serde_json::Value)>> {
+    let (guildid, scope_name, scopeid) = scope.columns();
+    query!(
+        "SELECT pg_advisory_xact_lock(hashtextextended(format('%s/%s/%s/%s', $1::int8, $2::varchar, $3::int8, $4::varchar), 0))",
+        wh_database::shared::Id(guildid) as _,
+        scope_name,
+        wh_database::shared::Id(scopeid) as _,
+        key,
+    )
+    .execute(&mut *tx)
+    .await?;
+    Ok(query!(
+        "SELECT data, version FROM guild_config WHERE guildid = $1::int8 AND scope = $2::varchar AND scopeid = $3::int8 AND key = $4::varchar FOR UPDATE",
+        wh_database::shared::Id(guildid) as _,
+        scope_name,
+        wh_database::shared::Id(scopeid) as _,
+        key,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .map(|r| (r.version, r.data)))
+}
+
+/// Run `attempt` in a transaction and commit it, every config write goes through here.
+/// `attempt` must take the values it changes with [`lock_config`], it is run again when the transaction conflicts with another one.
+/// What `attempt` borrows is passed in `state`, so every try can borrow it again
+async fn locked_write<S, R, F>(
+    database: &sqlx::PgPool,
+    what: &str,
+    state: &mut S,
+    mut attempt: F,
+) -> AllResult<R>
+where
+    F: for<'a> FnMut(
+        &'a mut sqlx::Transaction<'static, sqlx::Postgres>,
+        &'a mut S,
+    ) -> serenity::futures::future::BoxFuture<'a, AllResult<R>>,
+{
+    let mut retries = 0;
+    loop {
+        let mut tx = database.begin().await?;
+        let res = match attempt(&mut tx, state).await {
+            Ok(out) => tx.commit().await.map(|_| out).map_err(Into::into),
+            Err(e) => Err(e),
+        };
+        match res {
+            Err(e) if retries < WRITE_RETRIES && is_conflict(&*e) => {
+                retries += 1;
+                debug!(
+                    "Conflict when writing {}, retrying ({}/{}): {}",
+                    what, retries, WRITE_RETRIES, e
+                );
+            }
+            res => return res,
+        }
     }
 }
 
-type AllResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+/// Load the guild's config (or its default), let `f` modify it and write it back.
+/// This is [`update_scoped_config`] with [`Scope::Guild`]
+/// ```ignore
+/// wh_config::shared::update_config::<JoinEvent, _, _>(db, guildid, Some(userid), |join| {
+///     join.roles.push(roleid);
+///     Ok(())
+/// })
+/// .await?;
+/// ```
+pub async fn update_config<T, F, R>(
+    database: &sqlx::PgPool,
+    guildid: u64,
+    author: Option<u64>,
+    f: F,
+) -> AllResult<R>
+where
+    T: Config + Default + Send,
+    F: FnMut(&mut T) -> AllResult<R> + Send,
+    R: Send,
+{
+    update_scoped_config(database, Scope::Guild(guildid), author, f).await
+}
+
+/// Load the config stored at `scope` (or its default), let `f` modify it and write it back.
+///
+/// Everything happens inside a transaction holding the value (see [`lock_config`]), so concurrent updates are serialized.
+/// If `f` returns an error the transaction is rolled back and the stored config is left untouched.
+/// The update is retried when it conflicts with another transaction, meaning `f` can be called more than once.
+/// `author` is the user responsible for the change and is kept in the config history, `None` if the bot made the change by itself
+pub async fn update_scoped_config<T, F, R>(
+    database: &sqlx::PgPool,
+    scope: Scope,
+    author: Option<u64>,
+    mut f: F,
+) -> AllResult<R>
+where
+    T: Config + Default + Send,
+    F: FnMut(&mut T) -> AllResult<R> + Send,
+    R: Send,
+{
+    locked_write(database, <T as Config>::KEY, &mut f, |tx, f| {
+        Box::pin(async move {
+            let mut data = match lock_config(tx, scope, <T as Config>::KEY).await? {
+                Some((version, data)) => {
+                    load_config::<T, _>(&mut *tx, scope, version, data).await?
+                }
+                None => None,
+            }
+            .unwrap_or_default();
+
+            let out = f(&mut data)?;
+
+            write_config(
+                &mut *tx,
+                scope,
+                <T as Config>::KEY,
+                serde_json::value::to_value(&data)?,
+                <T as Config>::VERSION as i32,
+                author,
+            )
+            .await?;
+            Ok(out)
+        })
+    })
+    .await
+}
+
+/// Remove the value stored at `scope`, so the config falls back to the next wider scope.
+/// Returns `false` if there was nothing to remove
+pub async fn remove_scoped_config<T: Config>(
+    database: &sqlx::PgPool,
+    scope: Scope,
+    author: Option<u64>,
+) -> AllResult<bool> {
+    locked_write(database, <T as Config>::KEY, &mut (), |tx, _| {
+        Box::pin(async move {
+            lock_config(tx, scope, <T as Config>::KEY).await?;
+            delete_config(&mut *tx, scope, <T as Config>::KEY, author).await
+        })
+    })
+    .await
+}
 
-async fn _get_config<T: Config>(
+pub async fn read_config<T: Config>(
     database: &sqlx::PgPool,
     guildid: u64,
-) -> AllResult<(Option<T>, sqlx::pool::PoolConnection<sqlx::Postgres>)> {
-    let mut conn = database.acquire().await?;
+) -> AllResult<Option<ReadConfig<T>>> {
+    read_scoped_config(database, Scope::Guild(guildid)).await
+}
+
+pub async fn read_config_or_default<T: Config + Default>(
+    database: &sqlx::PgPool,
+    guildid: u64,
+) -> AllResult<ReadConfig<T>> {
+    Ok(read_config::<T>(database, guildid)
+        .await?
+        .unwrap_or_default())
+}
+
+/// Read the value stored exactly at `scope`, without falling back to a wider scope
+pub async fn read_scoped_config<T: Config>(
+    database: &sqlx::PgPool,
+    scope: Scope,
+) -> AllResult<Option<ReadConfig<T>>> {
+    let (guildid, scope_name, scopeid) = scope.columns();
     let res = query!(
-        "SELECT * FROM get_config($1::int8, $2::varchar)",
+        "SELECT data, version FROM guild_config WHERE guildid = $1 AND scope = $2 AND scopeid = $3 AND key = $4",
         wh_database::shared::Id(guildid) as _,
-        <T as Config>::KEY,
+        scope_name,
+        wh_database::shared::Id(scopeid) as _,
+        <T as Config>::KEY
     )
-    .fetch_optional(&mut conn)
+    .fetch_optional(database)
     .await?;
     Ok(match res {
-        Some(e) => match e.get_config {
-            Some(e) => {
-                let val: Result<T, _> = serde_json::value::from_value(e);
-                if let Err(e) = &val {
-                    error!(
-                        "Error when deserializing config `{}`: {}",
-                        <T as Config>::KEY,
-                        e
-                    );
-                    (None, conn)
-                } else {
-                    (Some(val.unwrap()), conn)
-                }
-            }
-            None => (None, conn),
-        },
-        None => (None, conn),
-    })
+        Some(r) => load_config::<T, _>(database, scope, r.version, r.data).await?,
+        None => None,
+    }
+    .map(|d| ReadConfig { inner: d }))
 }
 
-pub async fn get_config_or_default<T: Config + Default>(
+/// Read the value of the first scope in `scopes` that has one, `scopes` should be ordered from the narrowest to the widest
+/// ```ignore
+/// let scopes = Scope::chain(guildid, Some(channelid), None);
+/// let rate = resolve_config_or_default::<PointsRate>(db, &scopes).await?;
+/// ```
+pub async fn resolve_config<T: Config>(
     database: &sqlx::PgPool,
-    guildid: u64,
-) -> AllResult<ConfigGuard<T>> {
-    let (data, conn) = _get_config::<T>(database, guildid).await?;
-
-    Ok(ConfigGuard {
-        guildid,
-        db: conn,
-        data: data.unwrap_or_default(),
-    })
+    scopes: &[Scope],
+) -> AllResult<Option<ReadConfig<T>>> {
+    let columns = scopes
+        .iter()
+        .map(|scope| scope.columns())
+        .collect::<Vec<_>>();
+    // The ids are stored as int8, `as` keeps their bits like `Id` does
+    let guildids = columns.iter().map(|c| c.0 as i64).collect::<Vec<_>>();
+    let names = columns.iter().map(|c| c.1.to_string()).collect::<Vec<_>>();
+    let scopeids = columns.iter().map(|c| c.2 as i64).collect::<Vec<_>>();
+    let rows = query!(
+        "
+        SELECT c.data, c.version, s.rank AS \"rank!\"
+        FROM guild_config c
+        JOIN UNNEST($1::int8[], $2::varchar[], $3::int8[]) WITH ORDINALITY AS s(guildid, scope, scopeid, rank)
+            ON c.guildid = s.guildid AND c.scope = s.scope AND c.scopeid = s.scopeid
+        WHERE c.key = $4::varchar
+        ORDER BY s.rank
+        ",
+        &guildids,
+        &names,
+        &scopeids,
+        <T as Config>::KEY
+    )
+    .fetch_all(database)
+    .await?;
+    // A value that can't be loaded is backed up and the next scope is used
+    for row in rows {
+        let scope = scopes[row.rank as usize - 1];
+        if let Some(config) = load_config::<T, _>(database, scope, row.version, row.data).await? {
+            return Ok(Some(ReadConfig { inner: config }));
+        }
+    }
+    Ok(None)
 }
 
-pub async fn get_config<T: Config>(
+pub async fn resolve_config_or_default<T: Config + Default>(
     database: &sqlx::PgPool,
-    guildid: u64,
-) -> AllResult<Option<ConfigGuard<T>>> {
-    let (data, conn) = _get_config::<T>(database, guildid).await?;
+    scopes: &[Scope],
+) -> AllResult<ReadConfig<T>> {
+    Ok(resolve_config::<T>(database, scopes)
+        .await?
+        .unwrap_or_default())
+}
+
+/*
+  __  _   _ _     _                     __
+ / / | | | (_)___| |_ ___  _ __ _   _  \ \
+/ /  | |_| | / __| __/ _ \| '__| | | |  \ \
+\ \  |  _  | \__ \ || (_) | |  | |_| |  / /
+ \_\ |_| |_|_|___/\__\___/|_|   \__, | /_/
+                                |___/
+*/
+
+pub const HISTORY_PAGE_SIZE: i64 = 10;
 
-    Ok(data.map(|c| ConfigGuard {
-        guildid,
-        db: conn,
-        data: c,
-    }))
+/// Whose changes a history holds.
+/// The user scope is shared by every guild, so its changes are in the history of the user and not of a guild
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryOwner {
+    /// Every scope inside the guild
+    Guild(u64),
+    /// The personal preferences of the user
+    User(u64),
 }
 
-pub async fn set_config<T: Config>(guard: ConfigGuard<T>) -> AllResult<()> {
-    let mut guard = std::mem::ManuallyDrop::new(guard);
-    query!(
-        "SELECT * FROM set_config($1::int8, $2::varchar, $3::jsonb)",
-        wh_database::shared::Id(guard.guildid) as _,
-        <T as Config>::KEY,
-        serde_json::value::to_value(&guard.data)? as _,
+impl HistoryOwner {
+    /// The `guildid` of the rows, and the user if only their user scope is kept
+    fn columns(self) -> (u64, Option<u64>) {
+        match self {
+            HistoryOwner::Guild(guildid) => (guildid, None),
+            HistoryOwner::User(userid) => (Scope::User(userid).columns().0, Some(userid)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConfigHistoryRaw {
+    uid: i64,
+    guildid: i64,
+    scope: String,
+    scopeid: i64,
+    key: String,
+    authorid: Option<i64>,
+    old_data: Option<serde_json::Value>,
+    new_data: Option<serde_json::Value>,
+    version: i32,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigHistory {
+    pub uid: i64,
+    pub guildid: wh_database::shared::Id,
+    pub scope: Scope,
+    pub key: String,
+    pub authorid: Option<wh_database::shared::Id>,
+    pub old_data: Option<serde_json::Value>,
+    /// `None` if the value has been removed
+    pub new_data: Option<serde_json::Value>,
+    pub version: i32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ConfigHistoryRaw {
+    fn into_processed(self) -> AllResult<ConfigHistory> {
+        let guildid: wh_database::shared::Id = self.guildid.into();
+        let scopeid: wh_database::shared::Id = self.scopeid.into();
+        let scope = Scope::from_columns(guildid.0, &self.scope, scopeid.0)
+            .ok_or_else(|| format!("Unknown config scope `{}`", self.scope))?;
+        Ok(ConfigHistory {
+            uid: self.uid,
+            guildid,
+            scope,
+            key: self.key,
+            authorid: self.authorid.map(Into::into),
+            old_data: self.old_data,
+            new_data: self.new_data,
+            version: self.version,
+            created_at: self.created_at,
+        })
+    }
+}
+
+/// Get the changes of `owner` made to `key`, newest first, [`HISTORY_PAGE_SIZE`] at a time
+pub async fn get_config_history(
+    database: &sqlx::PgPool,
+    owner: HistoryOwner,
+    key: &str,
+    page: u16,
+) -> AllResult<Vec<ConfigHistory>> {
+    let (guildid, userid) = owner.columns();
+    let res = query_as!(
+        ConfigHistoryRaw,
+        "SELECT uid, guildid, scope, scopeid, key, authorid, old_data, new_data, version, created_at FROM guild_config_history WHERE guildid = $1::int8 AND ($5::int8 IS NULL OR (scope = 'user' AND scopeid = $5::int8)) AND key = $2::varchar ORDER BY uid DESC LIMIT $3 OFFSET $4",
+        wh_database::shared::Id(guildid) as _,
+        key,
+        HISTORY_PAGE_SIZE,
+        page as i64 * HISTORY_PAGE_SIZE,
+        userid.map(wh_database::shared::Id) as _,
     )
-    .execute(&mut guard.db)
+    .fetch_all(database)
     .await?;
-    // SAFETY: this is safe because we forget the `guard` juste after this so nobody can use dropped memory
-    unsafe {
-        std::ptr::drop_in_place(&mut guard.data as *mut _);
-        std::ptr::drop_in_place(&mut guard.db as *mut _);
-        std::ptr::drop_in_place(&mut guard.guildid as *mut _);
+    res.into_iter().map(|r| r.into_processed()).collect()
+}
+
+pub async fn count_config_history(
+    database: &sqlx::PgPool,
+    owner: HistoryOwner,
+    key: &str,
+) -> AllResult<i64> {
+    let (guildid, userid) = owner.columns();
+    Ok(query!(
+        "SELECT COUNT(*) FROM guild_config_history WHERE guildid = $1::int8 AND ($3::int8 IS NULL OR (scope = 'user' AND scopeid = $3::int8)) AND key = $2::varchar",
+        wh_database::shared::Id(guildid) as _,
+        key,
+        userid.map(wh_database::shared::Id) as _,
+    )
+    .fetch_one(database)
+    .await?
+    .count
+    .unwrap_or(0))
+}
+
+pub async fn get_config_history_entry(
+    database: &sqlx::PgPool,
+    owner: HistoryOwner,
+    uid: i64,
+) -> AllResult<Option<ConfigHistory>> {
+    let (guildid, userid) = owner.columns();
+    query_as!(
+        ConfigHistoryRaw,
+        "SELECT uid, guildid, scope, scopeid, key, authorid, old_data, new_data, version, created_at FROM guild_config_history WHERE guildid = $1::int8 AND ($3::int8 IS NULL OR (scope = 'user' AND scopeid = $3::int8)) AND uid = $2::int8",
+        wh_database::shared::Id(guildid) as _,
+        uid,
+        userid.map(wh_database::shared::Id) as _,
+    )
+    .fetch_optional(database)
+    .await?
+    .map(|r| r.into_processed())
+    .transpose()
+}
+
+/// Put back the config as it was right after the history entry `uid` of `owner` was made.
+/// The rollback is itself recorded as a new change made by `author`
+pub async fn rollback_config(
+    database: &sqlx::PgPool,
+    owner: HistoryOwner,
+    uid: i64,
+    author: u64,
+) -> AllResult<Option<ConfigHistory>> {
+    let entry = match get_config_history_entry(database, owner, uid).await? {
+        Some(e) => e,
+        None => return Ok(None),
     };
-    std::mem::forget(guard);
+    locked_write(database, &entry.key, &mut &entry, |tx, entry| {
+        Box::pin(async move {
+            lock_config(tx, entry.scope, &entry.key).await?;
+            match &entry.new_data {
+                Some(data) => {
+                    write_config(
+                        &mut *tx,
+                        entry.scope,
+                        &entry.key,
+                        data.clone(),
+                        entry.version,
+                        Some(author),
+                    )
+                    .await
+                }
+                None => delete_config(&mut *tx, entry.scope, &entry.key, Some(author))
+                    .await
+                    .map(|_| ()),
+            }
+        })
+    })
+    .await?;
+    Ok(Some(entry))
+}
+
+/*
+  __  _____                    _       _             __
+ / / |_   _|__ _ __ ___  _ __ | | __ _| |_ ___  ___  \ \
+/ /    | |/ _ \ '_ ` _ \| '_ \| |/ _` | __/ _ \/ __|  \ \
+\ \    | |  __/ | | | | | |_) | | (_| | ||  __/\__ \  / /
+ \_\   |_|\___|_| |_| |_| .__/|_|\__,_|\__\___||___/ /_/
+                        |_|
+*/
+
+/// A guild setup that can be exported from a guild and applied to another one.
+/// Roles are referenced by name since their ids are different in every guild
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Template {
+    /// The guild wide value of every config key
+    #[serde(default)]
+    pub configs: std::collections::BTreeMap<String, TemplateConfig>,
+    #[serde(default)]
+    pub roles: Vec<TemplateRole>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateConfig {
+    pub version: i32,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TemplateRole {
+    pub name: String,
+    /// Points needed to get the role
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub points: Option<i64>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub denied: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateFormat {
+    Toml,
+    Yaml,
+}
+
+impl TemplateFormat {
+    /// Get the format from its name or from a file extension
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    pub fn serialize(self, template: &Template) -> AllResult<String> {
+        Ok(match self {
+            // Going through `toml::Value` puts the plain values before the tables, which the serializer requires
+            Self::Toml => {
+                let mut value = serde_json::to_value(template)?;
+                remove_nulls(&mut value)?;
+                toml::to_string_pretty(&toml::Value::try_from(value)?)?
+            }
+            Self::Yaml => serde_yaml::to_string(template)?,
+        })
+    }
+
+    pub fn deserialize(self, data: &str) -> AllResult<Template> {
+        Ok(match self {
+            Self::Toml => toml::from_str(data)?,
+            Self::Yaml => serde_yaml::from_str(data)?,
+        })
+    }
+}
+
+/// TOML has no null, the fields set to null are left out and read back as missing.
+/// A null can't be left out of a list without shifting the other items, so it is an error
+fn remove_nulls(value: &mut serde_json::Value) -> AllResult<()> {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            for v in map.values_mut() {
+                remove_nulls(v)?;
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for v in items {
+                if v.is_null() {
+                    return Err(
+                        "TOML can't represent a null inside a list, use YAML instead".into(),
+                    );
+                }
+                remove_nulls(v)?;
+            }
+        }
+        _ => (),
+    }
     Ok(())
 }
 
-pub async fn read_config<T: Config>(
+/// A difference between a template and the guild it is applied to
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateChange {
+    ConfigAdded(String),
+    ConfigChanged(String),
+    RolePoints {
+        role: u64,
+        old: Option<i64>,
+        new: Option<i64>,
+    },
+    RolePermissions {
+        role: u64,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    RoleDenied {
+        role: u64,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// No role with this name exists in the guild, it is skipped
+    RoleMissing(String),
+    /// Multiple roles have this name in the guild, it is skipped
+    RoleAmbiguous(String),
+}
+
+impl Display for TemplateChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ConfigAdded(key) => write!(f, "+ config `{}`", key),
+            Self::ConfigChanged(key) => write!(f, "~ config `{}`", key),
+            Self::RolePoints { role, old, new } => {
+                let points =
+                    |p: &Option<i64>| p.map_or_else(|| "none".to_string(), |p| p.to_string());
+                write!(
+                    f,
+                    "~ <@&{}> points: {} -> {}",
+                    role,
+                    points(old),
+                    points(new)
+                )
+            }
+            Self::RolePermissions {
+                role,
+                added,
+                removed,
+            } => {
+                write!(f, "~ <@&{}> permissions:", role)?;
+                for p in added {
+                    write!(f, " +`{}`", p)?;
+                }
+                for p in removed {
+                    write!(f, " -`{}`", p)?;
+                }
+                Ok(())
+            }
+            Self::RoleDenied {
+                role,
+                added,
+                removed,
+            } => {
+                write!(f, "~ <@&{}> denied:", role)?;
+                for p in added {
+                    write!(f, " +`{}`", p)?;
+                }
+                for p in removed {
+                    write!(f, " -`{}`", p)?;
+                }
+                Ok(())
+            }
+            Self::RoleMissing(name) => write!(f, "! no role named `{}`, skipped", name),
+            Self::RoleAmbiguous(name) => {
+                write!(f, "! multiple roles named `{}`, skipped", name)
+            }
+        }
+    }
+}
+
+/// Export the guild wide configs, the role points and the guild wide role permissions and denies of a guild.
+/// `roles` maps the guild's role ids to their names, roles missing from it are left out
+pub async fn export_template(
     database: &sqlx::PgPool,
     guildid: u64,
-) -> AllResult<Option<ReadConfig<T>>> {
-    let res = query!(
-        "SELECT data FROM guild_config WHERE guildid = $1 AND key = $2",
+    roles: &std::collections::HashMap<u64, String>,
+) -> AllResult<Template> {
+    let mut template = Template::default();
+    let configs = query!(
+        "SELECT key, data, version FROM guild_config WHERE guildid = $1::int8 AND scope = 'guild'",
         wh_database::shared::Id(guildid) as _,
-        <T as Config>::KEY
     )
-    .fetch_optional(database)
+    .fetch_all(database)
     .await?;
-    Ok(match res.map(|r| r.data) {
-        Some(d) => {
-            let val: Result<T, _> = serde_json::value::from_value(d);
-            if let Err(e) = &val {
-                error!(
-                    "Error when deserializing config `{}`: {}",
-                    <T as Config>::KEY,
-                    e
-                );
-                None
-            } else {
-                Some(val.unwrap())
+    for row in configs {
+        template.configs.insert(
+            row.key,
+            TemplateConfig {
+                version: row.version,
+                data: row.data,
+            },
+        );
+    }
+
+    let mut template_roles = std::collections::BTreeMap::<u64, TemplateRole>::new();
+    let points = query!(
+        "SELECT roleid, points FROM role_points WHERE guildid = $1::int8",
+        wh_database::shared::Id(guildid) as _,
+    )
+    .fetch_all(database)
+    .await?;
+    for row in points {
+        let roleid = wh_database::shared::Id::from(row.roleid).0;
+        if let Some(name) = roles.get(&roleid) {
+            let role = template_roles.entry(roleid).or_default();
+            role.name = name.clone();
+            role.points = Some(row.points);
+        }
+    }
+    let permissions = query!(
+        "SELECT roleid, ids, denied FROM role_permission WHERE guildid = $1::int8 AND channelid = 0",
+        wh_database::shared::Id(guildid) as _,
+    )
+    .fetch_all(database)
+    .await?;
+    for row in permissions {
+        let roleid = wh_database::shared::Id::from(row.roleid).0;
+        if let Some(name) = roles.get(&roleid) {
+            let role = template_roles.entry(roleid).or_default();
+            role.name = name.clone();
+            role.permissions.extend(row.ids);
+            role.permissions.sort();
+            role.permissions.dedup();
+            role.denied.extend(row.denied);
+            role.denied.sort();
+            role.denied.dedup();
+        }
+    }
+    template.roles = template_roles.into_values().collect();
+    Ok(template)
+}
+
+/// The sorted list wanted by the template, with what it adds to and removes from `current`
+fn list_changes(
+    current: &[String],
+    template: &[String],
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut wanted = template.to_vec();
+    wanted.sort();
+    wanted.dedup();
+    let added = wanted
+        .iter()
+        .filter(|p| !current.contains(p))
+        .cloned()
+        .collect();
+    let removed = current
+        .iter()
+        .filter(|p| !wanted.contains(p))
+        .cloned()
+        .collect();
+    (wanted, added, removed)
+}
+
+/// The template with every config migrated to the current version of its registered type.
+/// Unknown keys and values that aren't valid configs are rejected, nothing would be able to read them
+fn check_template_configs(template: &Template) -> AllResult<Template> {
+    let mut checked = template.clone();
+    for (key, config) in checked.configs.iter_mut() {
+        let info = match config_info(key) {
+            Some(info) => info,
+            None => message_err!(format!("The template has an unknown config `{}`", key)),
+        };
+        match (info.check)(config.version, config.data.clone()) {
+            Ok(data) => {
+                *config = TemplateConfig {
+                    version: info.version as i32,
+                    data,
+                }
             }
+            Err(e) => message_err!(format!(
+                "The config `{}` of the template is invalid: {}",
+                key, e
+            )),
         }
-        None => None,
     }
-    .map(|d| ReadConfig { inner: d }))
+    Ok(checked)
 }
 
-pub async fn read_config_or_default<T: Config + Default>(
+/// The permissions `permission` stands for: itself if it is registered, or the permissions of the group of the guild if it is one.
+/// `None` if it is neither
+async fn expand_permission(
+    tx: &mut sqlx::Transaction<'static, sqlx::Postgres>,
+    guildid: u64,
+    permission: &str,
+) -> AllResult<Option<Vec<String>>> {
+    match wh_permission::shared::permission_group::group_name(permission) {
+        Some(name) => Ok(query!(
+            "SELECT permissions FROM permission_group WHERE guildid = $1::int8 AND name = $2::text",
+            wh_database::shared::Id(guildid) as _,
+            name,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|r| r.permissions)),
+        None if wh_permission::shared::is_valid_permission(permission) => {
+            Ok(Some(vec![permission.to_string()]))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Make sure the template only gives valid permissions and existing groups, and that the author can manage every permission it changes.
+/// Like with `permission add`, only Discord administrators can change a permission that implies `permission.manage`
+async fn check_template_permissions(
+    tx: &mut sqlx::Transaction<'static, sqlx::Postgres>,
+    guildid: u64,
+    given: &[String],
+    changed: &[&String],
+    administrator: bool,
+) -> AllResult<()> {
+    for permission in given {
+        if expand_permission(tx, guildid, permission).await?.is_none() {
+            message_err!(format!(
+                "The permission or group `{}` of the template doesn't exist",
+                permission
+            ));
+        }
+    }
+    if administrator {
+        return Ok(());
+    }
+    for permission in changed {
+        // A permission removed by the template may not be valid anymore, it is then checked as it is
+        let permissions = expand_permission(tx, guildid, permission)
+            .await?
+            .unwrap_or_else(|| vec![permission.to_string()]);
+        if permissions
+            .iter()
+            .any(|p| wh_permission::shared::permission_implies(p, "permission.manage"))
+        {
+            message_err!(format!(
+                "The template changes `{}`, which can only be managed by having the ADMINISTRATOR discord permission",
+                permission
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Compute the changes needed to make the guild match `template` and apply them unless `dry_run` is set.
+/// `roles` maps the guild's role ids to their names, template roles are matched by name.
+/// `administrator` tells if the author has the ADMINISTRATOR Discord permission, the template can't change the permissions implying `permission.manage` otherwise.
+/// Config keys and roles that are not in the template are left untouched
+pub async fn apply_template(
     database: &sqlx::PgPool,
     guildid: u64,
-) -> AllResult<ReadConfig<T>> {
-    Ok(read_config::<T>(database, guildid)
+    template: &Template,
+    roles: &std::collections::HashMap<u64, String>,
+    author: Option<u64>,
+    administrator: bool,
+    dry_run: bool,
+) -> AllResult<Vec<TemplateChange>> {
+    let template = check_template_configs(template)?;
+    let changes = locked_write(
+        database,
+        "a template",
+        &mut (&template, roles),
+        |tx, (template, roles)| {
+            Box::pin(template_changes(
+                tx,
+                guildid,
+                template,
+                roles,
+                author,
+                administrator,
+                dry_run,
+            ))
+        },
+    )
+    .await?;
+    if !dry_run {
+        wh_permission::shared::role_permission::invalidate_role_cache(guildid);
+    }
+    Ok(changes)
+}
+
+/// Compute the changes of [`apply_template`] in `tx` and make them unless `dry_run` is set
+async fn template_changes(
+    tx: &mut sqlx::Transaction<'static, sqlx::Postgres>,
+    guildid: u64,
+    template: &Template,
+    roles: &std::collections::HashMap<u64, String>,
+    author: Option<u64>,
+    administrator: bool,
+    dry_run: bool,
+) -> AllResult<Vec<TemplateChange>> {
+    let mut changes = Vec::new();
+
+    // The configs are sorted, so two templates applied at the same time lock them in the same order
+    for (key, config) in &template.configs {
+        let current = lock_config(tx, Scope::Guild(guildid), key).await?;
+        let change = match current {
+            None => TemplateChange::ConfigAdded(key.clone()),
+            Some((version, data)) if data != config.data || version != config.version => {
+                TemplateChange::ConfigChanged(key.clone())
+            }
+            Some(_) => continue,
+        };
+        changes.push(change);
+        if !dry_run {
+            write_config(
+                &mut *tx,
+                Scope::Guild(guildid),
+                key,
+                config.data.clone(),
+                config.version,
+                author,
+            )
+            .await?;
+        }
+    }
+
+    for role in &template.roles {
+        let mut matching = roles
+            .iter()
+            .filter(|(_, name)| **name == role.name)
+            .map(|(id, _)| *id);
+        let roleid = match (matching.next(), matching.next()) {
+            (Some(id), None) => id,
+            (None, _) => {
+                changes.push(TemplateChange::RoleMissing(role.name.clone()));
+                continue;
+            }
+            (Some(_), Some(_)) => {
+                changes.push(TemplateChange::RoleAmbiguous(role.name.clone()));
+                continue;
+            }
+        };
+
+        let points = query!(
+            "SELECT points FROM role_points WHERE guildid = $1::int8 AND roleid = $2::int8",
+            wh_database::shared::Id(guildid) as _,
+            wh_database::shared::Id(roleid) as _,
+        )
+        .fetch_optional(&mut *tx)
         .await?
-        .unwrap_or_default())
+        .map(|r| r.points);
+        if points != role.points {
+            changes.push(TemplateChange::RolePoints {
+                role: roleid,
+                old: points,
+                new: role.points,
+            });
+            if !dry_run {
+                query!(
+                    "DELETE FROM role_points WHERE guildid = $1::int8 AND roleid = $2::int8",
+                    wh_database::shared::Id(guildid) as _,
+                    wh_database::shared::Id(roleid) as _,
+                )
+                .execute(&mut *tx)
+                .await?;
+                if let Some(points) = role.points {
+                    query!(
+                        "INSERT INTO role_points (roleid, guildid, points) VALUES ($1::int8, $2::int8, $3::int8)",
+                        wh_database::shared::Id(roleid) as _,
+                        wh_database::shared::Id(guildid) as _,
+                        points,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        let current = query!(
+            "SELECT ids, denied FROM role_permission WHERE guildid = $1::int8 AND roleid = $2::int8 AND channelid = 0",
+            wh_database::shared::Id(guildid) as _,
+            wh_database::shared::Id(roleid) as _,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let (permissions, denied) = current.map(|r| (r.ids, r.denied)).unwrap_or_default();
+        let (wanted, added, removed) = list_changes(&permissions, &role.permissions);
+        let (wanted_denied, denied_added, denied_removed) = list_changes(&denied, &role.denied);
+        let given = wanted
+            .iter()
+            .chain(&wanted_denied)
+            .cloned()
+            .collect::<Vec<_>>();
+        let changed = added
+            .iter()
+            .chain(&removed)
+            .chain(&denied_added)
+            .chain(&denied_removed)
+            .collect::<Vec<_>>();
+        check_template_permissions(tx, guildid, &given, &changed, administrator).await?;
+        if added.is_empty()
+            && removed.is_empty()
+            && denied_added.is_empty()
+            && denied_removed.is_empty()
+        {
+            continue;
+        }
+        if !added.is_empty() || !removed.is_empty() {
+            changes.push(TemplateChange::RolePermissions {
+                role: roleid,
+                added,
+                removed,
+            });
+        }
+        if !denied_added.is_empty() || !denied_removed.is_empty() {
+            changes.push(TemplateChange::RoleDenied {
+                role: roleid,
+                added: denied_added,
+                removed: denied_removed,
+            });
+        }
+        if !dry_run {
+            query!(
+                "INSERT INTO role_permission (roleid, guildid, ids, denied) VALUES ($1::int8, $2::int8, $3::text[], $4::text[]) ON CONFLICT (guildid, roleid, channelid) DO UPDATE SET ids = EXCLUDED.ids, denied = EXCLUDED.denied",
+                wh_database::shared::Id(roleid) as _,
+                wh_database::shared::Id(guildid) as _,
+                &wanted[..],
+                &wanted_denied[..],
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    Ok(changes)
 }
 
+// --------------------------------------------
+
 #[derive(Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AllowCustomImage {
     pub default: bool,
@@ -197,3 +1271,376 @@ pub struct AllowCustomImage {
 impl Config for AllowCustomImage {
     const KEY: &'static str = "image.custom.rule";
 }
+crate::register_config!(AllowCustomImage);
+
+/// Language a user wants the bot to talk to them in, set in [`Scope::User`] and falling back to the guild and global values
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Language {
+    pub code: String,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self {
+            code: String::from("en"),
+        }
+    }
+}
+
+impl Config for Language {
+    const KEY: &'static str = "user.language";
+}
+crate::register_config!(Language);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stored as `{"vol": n}` in version 0, `{"volume": n}` in version 1 and `{"volume": n, "muted": false}` in version 2
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Volume {
+        volume: u8,
+        muted: bool,
+    }
+
+    impl Config for Volume {
+        const KEY: &'static str = "test.volume";
+        const VERSION: u32 = 2;
+
+        fn migrate(version: u32, mut data: serde_json::Value) -> AllResult<serde_json::Value> {
+            let object = data.as_object_mut().ok_or("not an object")?;
+            match version {
+                0 => {
+                    let vol = object.remove("vol").ok_or("no `vol`")?;
+                    object.insert("volume".into(), vol);
+                }
+                1 => {
+                    object.insert("muted".into(), false.into());
+                }
+                _ => unreachable!(),
+            }
+            Ok(data)
+        }
+    }
+
+    /// Was bumped without a migration
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Unmigrated {
+        volume: u8,
+    }
+
+    impl Config for Unmigrated {
+        const KEY: &'static str = "test.unmigrated";
+        const VERSION: u32 = 1;
+    }
+
+    fn template(data: serde_json::Value) -> Template {
+        let mut template = Template::default();
+        template.configs.insert(
+            "music.now_playing".into(),
+            TemplateConfig { version: 0, data },
+        );
+        template.roles.push(TemplateRole {
+            name: "DJ".into(),
+            points: None,
+            permissions: vec!["music.*".into()],
+            denied: vec!["music.record".into()],
+        });
+        template
+    }
+
+    #[test]
+    fn toml_leaves_out_nulls() {
+        let exported = template(serde_json::json!({"channel": null, "buttons": true}));
+        let toml = TemplateFormat::Toml.serialize(&exported).unwrap();
+        let imported = TemplateFormat::Toml.deserialize(&toml).unwrap();
+        assert_eq!(
+            imported.configs["music.now_playing"].data,
+            serde_json::json!({"buttons": true})
+        );
+        assert_eq!(imported.roles, exported.roles);
+    }
+
+    #[test]
+    fn toml_rejects_nulls_in_lists() {
+        let exported = template(serde_json::json!({"roles": [1, null]}));
+        assert!(TemplateFormat::Toml.serialize(&exported).is_err());
+        let yaml = TemplateFormat::Yaml.serialize(&exported).unwrap();
+        assert_eq!(TemplateFormat::Yaml.deserialize(&yaml).unwrap(), exported);
+    }
+
+    #[test]
+    fn upgrade_runs_the_whole_chain() {
+        let expected = Volume {
+            volume: 50,
+            muted: false,
+        };
+        assert_eq!(
+            upgrade::<Volume>(0, serde_json::json!({"vol": 50})).unwrap(),
+            (expected, true)
+        );
+        let (from_middle, migrated) =
+            upgrade::<Volume>(1, serde_json::json!({"volume": 50})).unwrap();
+        assert_eq!(from_middle.volume, 50);
+        assert!(migrated);
+        let (current, migrated) =
+            upgrade::<Volume>(2, serde_json::json!({"volume": 50, "muted": true})).unwrap();
+        assert!(current.muted);
+        assert!(!migrated);
+    }
+
+    #[test]
+    fn upgrade_failures() {
+        // A migration step fails
+        assert!(upgrade::<Volume>(0, serde_json::json!({"volume": 50})).is_err());
+        // The migrated data doesn't match the struct
+        assert!(upgrade::<Volume>(0, serde_json::json!({"vol": "loud"})).is_err());
+        // Newer than the code or not a version at all
+        assert!(upgrade::<Volume>(3, serde_json::json!({"volume": 50, "muted": true})).is_err());
+        assert!(upgrade::<Volume>(-1, serde_json::json!({"vol": 50})).is_err());
+        // No migration was written for the bump
+        assert!(upgrade::<Unmigrated>(0, serde_json::json!({"volume": 50})).is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_load_is_backed_up_once() {
+        dotenv::dotenv().ok();
+        // Needs the database, like the `query!` checks of the build
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        let mut tx = db.begin().await.unwrap();
+        let scope = Scope::Guild(u64::MAX);
+        let data = serde_json::json!({"volume": 50});
+        for _ in 0..2 {
+            let loaded = load_config::<Volume, _>(&mut tx, scope, 0, data.clone())
+                .await
+                .unwrap();
+            assert!(loaded.is_none());
+        }
+        let backups = query!(
+            "SELECT COUNT(*) FROM guild_config_backup WHERE guildid = $1::int8 AND key = $2::varchar AND version = 0 AND data = $3::jsonb",
+            wh_database::shared::Id(u64::MAX) as _,
+            <Volume as Config>::KEY,
+            data as _,
+        )
+        .fetch_one(&mut tx)
+        .await
+        .unwrap()
+        .count;
+        assert_eq!(backups, Some(1));
+        tx.rollback().await.unwrap();
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Counter {
+        count: u32,
+    }
+
+    impl Config for Counter {
+        const KEY: &'static str = "test.counter";
+    }
+
+    #[tokio::test]
+    async fn concurrent_updates_are_not_lost() {
+        dotenv::dotenv().ok();
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        let guildid = u64::MAX - 1;
+        let scope = Scope::Guild(guildid);
+        // Starts without a stored value, so the first writes race too
+        let updates = (0..10)
+            .map(|_| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    update_scoped_config::<Counter, _, _>(&db, scope, None, |counter| {
+                        counter.count += 1;
+                        Ok(())
+                    })
+                    .await
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut results = Vec::new();
+        for update in updates {
+            results.push(update.await.unwrap());
+        }
+        let count = read_scoped_config::<Counter>(&db, scope).await.unwrap();
+        for table in ["guild_config", "guild_config_history"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE guildid = $1::int8 AND key = $2::varchar",
+                table
+            ))
+            .bind(wh_database::shared::Id(guildid))
+            .bind(<Counter as Config>::KEY)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(count.map(|c| c.count), Some(10));
+    }
+
+    #[tokio::test]
+    async fn resolve_config_takes_the_narrowest_scope() {
+        dotenv::dotenv().ok();
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        let guildid = u64::MAX - 3;
+        let channelid = 1;
+        for (scope, count) in [
+            (Scope::Guild(guildid), 1),
+            (Scope::Channel { guildid, channelid }, 2),
+        ] {
+            update_scoped_config::<Counter, _, _>(&db, scope, None, |counter| {
+                counter.count = count;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        }
+        let in_channel =
+            resolve_config::<Counter>(&db, &Scope::chain(guildid, Some(channelid), None)).await;
+        let elsewhere = resolve_config::<Counter>(&db, &Scope::chain(guildid, Some(2), None)).await;
+        for table in ["guild_config", "guild_config_history"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE guildid = $1::int8 AND key = $2::varchar",
+                table
+            ))
+            .bind(wh_database::shared::Id(guildid))
+            .bind(<Counter as Config>::KEY)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        assert_eq!(in_channel.unwrap().map(|c| c.count), Some(2));
+        assert_eq!(elsewhere.unwrap().map(|c| c.count), Some(1));
+    }
+
+    #[tokio::test]
+    async fn user_changes_are_in_the_user_history() {
+        dotenv::dotenv().ok();
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        let userid = u64::MAX - 3;
+        let owner = HistoryOwner::User(userid);
+        for count in [1, 2] {
+            update_scoped_config::<Counter, _, _>(&db, Scope::User(userid), None, |counter| {
+                counter.count = count;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        }
+        let history = get_config_history(&db, owner, <Counter as Config>::KEY, 0).await;
+        let in_other_user = count_config_history(
+            &db,
+            HistoryOwner::User(userid - 1),
+            <Counter as Config>::KEY,
+        )
+        .await;
+        let first = history.as_ref().unwrap().last().unwrap().uid;
+        let rolled_back = rollback_config(&db, owner, first, userid).await;
+        let count = read_scoped_config::<Counter>(&db, Scope::User(userid)).await;
+        sqlx::query(
+            "DELETE FROM guild_config WHERE scope = 'user' AND scopeid = $1::int8 AND key = $2::varchar",
+        )
+        .bind(wh_database::shared::Id(userid))
+        .bind(<Counter as Config>::KEY)
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "DELETE FROM guild_config_history WHERE scope = 'user' AND scopeid = $1::int8 AND key = $2::varchar",
+        )
+        .bind(wh_database::shared::Id(userid))
+        .bind(<Counter as Config>::KEY)
+        .execute(&db)
+        .await
+        .unwrap();
+        assert_eq!(history.unwrap().len(), 2);
+        assert_eq!(in_other_user.unwrap(), 0);
+        assert!(rolled_back.unwrap().is_some());
+        assert_eq!(count.unwrap().map(|c| c.count), Some(1));
+    }
+
+    #[test]
+    fn template_configs_are_checked() {
+        let mut template = Template::default();
+        template.configs.insert(
+            "user.language".into(),
+            TemplateConfig {
+                version: 0,
+                data: serde_json::json!({"code": "fr"}),
+            },
+        );
+        assert_eq!(check_template_configs(&template).unwrap(), template);
+
+        let mut invalid = template.clone();
+        invalid.configs.get_mut("user.language").unwrap().data = serde_json::json!(5);
+        assert!(check_template_configs(&invalid).is_err());
+
+        let mut newer = template.clone();
+        newer.configs.get_mut("user.language").unwrap().version = 1;
+        assert!(check_template_configs(&newer).is_err());
+
+        let mut unknown = template;
+        unknown.configs.insert(
+            "test.unknown".into(),
+            TemplateConfig {
+                version: 0,
+                data: serde_json::json!({}),
+            },
+        );
+        assert!(check_template_configs(&unknown).is_err());
+    }
+
+    #[tokio::test]
+    async fn template_permissions_are_checked() {
+        dotenv::dotenv().ok();
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let db = sqlx::PgPool::connect(&url).await.unwrap();
+        let guildid = u64::MAX - 2;
+        let roles = std::iter::once((1, "Mods".to_string())).collect();
+        let apply = |permissions: &[&str], administrator: bool| {
+            let mut template = Template::default();
+            template.roles.push(TemplateRole {
+                name: "Mods".into(),
+                points: None,
+                permissions: permissions.iter().map(|p| p.to_string()).collect(),
+                denied: Vec::new(),
+            });
+            let db = db.clone();
+            let roles = &roles;
+            async move {
+                apply_template(&db, guildid, &template, roles, None, administrator, true).await
+            }
+        };
+
+        assert!(apply(&["*"], false).await.is_err());
+        assert_eq!(
+            apply(&["*"], true).await.unwrap(),
+            [TemplateChange::RolePermissions {
+                role: 1,
+                added: vec!["*".into()],
+                removed: Vec::new(),
+            }]
+        );
+        assert!(apply(&["test.missing"], true).await.is_err());
+        assert!(apply(&["group:missing"], true).await.is_err());
+    }
+}
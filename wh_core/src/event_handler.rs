@@ -308,6 +308,18 @@ impl EventHandler for WhEventHandlerManager {
         }
     }
 
+    async fn interaction_create(
+        &self,
+        _ctx: Context,
+        _interaction: serenity::model::interactions::Interaction,
+    ) {
+        for handler in &self.inners {
+            handler
+                .interaction_create(_ctx.clone(), _interaction.clone())
+                .await;
+        }
+    }
+
     async fn invite_create(&self, _ctx: Context, _data: serenity::model::event::InviteCreateEvent) {
         for handler in &self.inners {
             handler.invite_create(_ctx.clone(), _data.clone()).await;
@@ -5,6 +5,7 @@ extern crate wh_core;
 extern crate wh_database;
 extern crate wh_music;
 extern crate wh_points;
+extern crate wh_config;
 
 extern crate serenity;
 #[macro_use]
@@ -32,13 +33,6 @@ impl EventHandler for WhEventHandler {
                 "s"
             }
         );
-        for guild in _data_about_bot.guilds {
-            guild
-                .id()
-                .disconnect_member(&_ctx.http, &_ctx.cache.current_user_id().await)
-                .await
-                .expect("Error when disconnecting voice");
-        }
     }
 }
 
@@ -87,6 +81,21 @@ fn logger_setup() -> Result<(), Box<dyn std::error::Error>> {
         .apply()?)
 }
 
+/// Wait for Ctrl-C, or for the SIGTERM sent by `docker stop` and systemd
+async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
@@ -124,12 +133,12 @@ async fn bot_launch() -> Result<(), Box<dyn std::error::Error>> {
 
     #[serenity::framework::standard::macros::hook]
     async fn before_hook(
-        _: &serenity::client::Context,
-        _: &serenity::model::channel::Message,
+        ctx: &serenity::client::Context,
+        msg: &serenity::model::channel::Message,
         cmd_name: &str,
     ) -> bool {
         // debug!("cmd_name: {}", cmd_name);
-        true
+        wh_permission::shared::command_binding::enforce_command_requirement(ctx, msg).await
     }
 
     #[serenity::framework::standard::macros::hook]
@@ -176,14 +185,15 @@ async fn bot_launch() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    modules!(modules, wh_database, wh_music, wh_points, wh_permission);
+    const PREFIX: &str = "wh?";
+    modules!(modules, wh_database, wh_music, wh_points, wh_permission, wh_config);
     let mut framework = serenity::framework::StandardFramework::new()
         .help(&wh_core::HELP_COMMAND)
         .after(after_hook)
         .on_dispatch_error(error_hook)
         .before(before_hook)
         .configure(|c| {
-            c.prefix("wh?");
+            c.prefix(PREFIX);
             c.allow_dm(false);
             c.case_insensitivity(true)
         });
@@ -202,6 +212,11 @@ async fn bot_launch() -> Result<(), Box<dyn std::error::Error>> {
         (module.register_typemap)(&mut type_map).await;
         (module.register_init)();
     }
+    let groups = modules
+        .iter()
+        .flat_map(|m| m.command_groups.iter().copied())
+        .collect::<Vec<_>>();
+    wh_permission::shared::command_binding::register_commands(PREFIX, &groups);
 
     let mut client = serenity::client::Client::builder(std::env::var("WH_DISCORD_BOT_TOKEN").expect(
         "Please use `WH_DISCORD_BOT_TOKEN` environement variable(or .env) with your bot's TOKEN",
@@ -222,6 +237,23 @@ async fn bot_launch() -> Result<(), Box<dyn std::error::Error>> {
 
     debug!("Start");
     let mut client = client.unwrap();
+
+    let data = client.data.clone();
+    let cache = client.cache_and_http.cache.clone();
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        if let Err(e) = shutdown_signal().await {
+            error!("Error when waiting for the shutdown signal: {}", e);
+            return;
+        }
+        info!("Shutting down");
+        if let Err(e) =
+            wh_music::shared::saved_queue::save_queues(&data, &cache.guilds().await).await
+        {
+            error!("Couldn't save the music queues: {}", e);
+        }
+        shard_manager.lock().await.shutdown_all().await;
+    });
     match client.start().await {
         Err(e) => error!("Error when starting client: {}", e),
         Ok(_) => {
@@ -0,0 +1,98 @@
+//! Every permission the code checks must be registered with `check_permission!` or `register_permission!`,
+//! otherwise it can't be granted and `has_permission` only logs an error at runtime
+
+use std::path::{Path, PathBuf};
+
+/// The calls whose string arguments are permissions
+const PERMISSION_CALLS: &[&str] = &[
+    "check_permission!(",
+    "register_permission!(",
+    "check_permission(",
+    "has_permission(",
+    "evaluate_permission(",
+    "explain_permission(",
+    "ensure_can_manage(",
+    "permission_implies(",
+];
+
+fn rust_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            rust_files(&path, out);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            out.push(path);
+        }
+    }
+}
+
+fn is_permission(literal: &str) -> bool {
+    literal.contains('.')
+        && literal
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+}
+
+/// The permission literals given to the permission calls in `source`
+fn referenced_permissions(source: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for call in PERMISSION_CALLS {
+        for (start, _) in source.match_indices(call) {
+            let rest = &source[start + call.len()..];
+            let args = &rest[..rest.find(';').unwrap_or(rest.len())];
+            out.extend(
+                args.split('"')
+                    .skip(1)
+                    .step_by(2)
+                    .filter(|literal| is_permission(literal))
+                    .map(String::from),
+            );
+        }
+    }
+    out
+}
+
+#[test]
+fn every_referenced_permission_is_registered() {
+    // Make sure every module is linked with its registrations
+    let modules = [
+        &wh_database::module::MODULE_DECLARATION,
+        &wh_music::module::MODULE_DECLARATION,
+        &wh_points::module::MODULE_DECLARATION,
+        &wh_permission::module::MODULE_DECLARATION,
+        &wh_config::module::MODULE_DECLARATION,
+    ];
+    assert!(!modules.is_empty());
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(workspace).unwrap() {
+        let src = entry.unwrap().path().join("src");
+        if src.is_dir() {
+            rust_files(&src, &mut files);
+        }
+    }
+
+    let mut referenced = Vec::new();
+    for file in &files {
+        let source = std::fs::read_to_string(file).unwrap();
+        for permission in referenced_permissions(&source) {
+            referenced.push((permission, file.strip_prefix(workspace).unwrap().to_owned()));
+        }
+    }
+    assert!(
+        referenced.iter().any(|(p, _)| p == "permission.manage"),
+        "No permission was found in the sources"
+    );
+
+    let missing = referenced
+        .iter()
+        .filter(|(permission, _)| !wh_permission::shared::registry::is_registered(permission))
+        .map(|(permission, file)| format!("`{}` in {}", permission, file.display()))
+        .collect::<Vec<_>>();
+    assert!(
+        missing.is_empty(),
+        "These permissions aren't registered: {}",
+        missing.join(", ")
+    );
+}
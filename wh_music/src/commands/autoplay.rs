@@ -0,0 +1,72 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(2)]
+#[usage("[?off|related|playlist] [?playlist name]")]
+#[example("playlist \"meme music\"")]
+/// Choose what is played once the queue is empty, without arguments it shows the current mode
+/// `related` plays songs related to the last one and `playlist` random songs of a saved playlist, the recent songs aren't played again
+pub async fn autoplay(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    use crate::shared::autoplay::{Autoplay, AutoplayMode};
+    let guildid = msg.guild_id.unwrap().0;
+    let mode = if args.is_empty() {
+        None
+    } else {
+        match args.single::<String>().unwrap_or_default().as_str() {
+            "off" => Some(AutoplayMode::Off),
+            "related" => Some(AutoplayMode::Related),
+            "playlist" => {
+                let name = match args.single_quoted::<String>() {
+                    Ok(name) => name,
+                    Err(_) => message_err!(fluent!(MUSIC_ARG_playlist_name)),
+                };
+                let playlist = match crate::shared::get_playlist(ctx, guildid, &name).await? {
+                    Some(playlist) => playlist,
+                    None => message_err!(fluent!(MUSIC_playlist_not_exist)),
+                };
+                Some(AutoplayMode::Playlist(playlist.name))
+            }
+            _ => message_err!(fluent!(MUSIC_ARG_autoplay)),
+        }
+    };
+
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    let mode = match mode {
+        Some(mode) => {
+            wh_config::shared::update_config::<Autoplay, _, _>(
+                db,
+                guildid,
+                Some(msg.author.id.0),
+                |c| {
+                    c.mode = mode.clone();
+                    Ok(())
+                },
+            )
+            .await?;
+            mode
+        }
+        None => {
+            let scopes = wh_config::shared::Scope::chain(guildid, None, None);
+            wh_config::shared::resolve_config_or_default::<Autoplay>(db, &scopes)
+                .await?
+                .mode
+                .clone()
+        }
+    };
+    match mode {
+        AutoplayMode::Off => {
+            reply_message!(ctx, msg, fluent!(MUSIC_autoplay_off));
+        }
+        AutoplayMode::Related => {
+            reply_message!(ctx, msg, fluent!(MUSIC_autoplay_related));
+        }
+        AutoplayMode::Playlist(name) => {
+            reply_message!(ctx, msg, format!(fluent!(MUSIC_autoplay_playlist), name));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[min_args(1)]
+#[max_args(2)]
+#[usage("[enable|disable|reset] [?#channel]")]
+#[example("disable #general")]
+/// Allow or forbid the music commands in a channel, without a channel it changes the guild's default
+async fn channel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    use wh_config::shared::Scope;
+    let action = args.single::<String>().unwrap_or_default();
+    let guildid = msg.guild_id.unwrap().0;
+    let scope = match args.single::<String>() {
+        Ok(arg) => match serenity::utils::parse_channel(&arg) {
+            Some(channelid) => Scope::Channel { guildid, channelid },
+            None => message_err!(fluent!(MUSIC_ARG_channel_mention)),
+        },
+        Err(_) => Scope::Guild(guildid),
+    };
+
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    let author = Some(msg.author.id.0);
+
+    match action.as_str() {
+        "enable" | "disable" => {
+            let enabled = action == "enable";
+            wh_config::shared::update_scoped_config::<crate::shared::MusicChannel, _, _>(
+                db,
+                scope,
+                author,
+                |c| {
+                    c.enabled = enabled;
+                    Ok(())
+                },
+            )
+            .await?;
+            reply_message!(
+                ctx,
+                msg,
+                format!(
+                    fluent!(MUSIC_channel_set),
+                    if enabled { "enabled" } else { "disabled" },
+                    scope
+                )
+            );
+        }
+        "reset" => {
+            wh_config::shared::remove_scoped_config::<crate::shared::MusicChannel>(
+                db, scope, author,
+            )
+            .await?;
+            reply_message!(ctx, msg, format!(fluent!(MUSIC_channel_reset), scope));
+        }
+        _ => message_err!(fluent!(MUSIC_ARG_channel_action)),
+    }
+    Ok(())
+}
@@ -19,7 +19,13 @@ pub async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
     match call_opt {
         Some(call) => {
             if channel_id.map(|c| c.0) == call.lock().await.current_channel().map(|c| c.0) {
-                call.lock().await.queue().stop();
+                // The stopped songs would be added back otherwise
+                crate::shared::queue_control::set_queue_loop(guild.id.0, false);
+                let queue = call.lock().await.queue().clone();
+                if !queue.is_empty() {
+                    crate::shared::autoplay::halt(guild.id.0);
+                }
+                queue.stop();
             } else {
                 message_err!(fluent!(MUSIC_not_same_channel));
             }
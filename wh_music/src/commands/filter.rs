@@ -0,0 +1,60 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(1)]
+#[usage("[?filter]")]
+#[example("bassboost")]
+/// Change the audio filter of the guild, without a filter it shows the current one and the available ones
+pub async fn filter(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    use crate::shared::audio::FilterPreset;
+    let guildid = msg.guild_id.unwrap().0;
+    let available = FilterPreset::ALL
+        .iter()
+        .map(|f| format!("`{}`", f))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    if args.is_empty() {
+        let settings = crate::shared::audio::audio_settings(db, guildid).await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!(fluent!(MUSIC_filter_current), settings.filter, available)
+        );
+        return Ok(());
+    }
+    let filter = match FilterPreset::from_name(args.rest().trim()) {
+        Some(filter) => filter,
+        None => message_err!(format!(fluent!(MUSIC_ARG_filter), available)),
+    };
+    wh_config::shared::update_config::<crate::shared::audio::AudioSettings, _, _>(
+        db,
+        guildid,
+        Some(msg.author.id.0),
+        |c| {
+            c.filter = filter;
+            Ok(())
+        },
+    )
+    .await?;
+    let previous = crate::shared::audio::active_filter(guildid);
+    crate::shared::audio::set_active_filter(guildid, filter);
+
+    // The song playing is restarted where it is to hear the new filter, the others pick it when they start
+    if let Some(call) = songbird::get(ctx).await.unwrap().get(guildid) {
+        let current = call.lock().await.queue().current();
+        if let Some(track) = current {
+            if track.is_seekable() {
+                // The same place in the song, played at the speed of the new filter
+                let position = previous.song_time(track.get_info().await?.position);
+                track.seek_time(filter.played_time(position))?;
+            }
+        }
+    }
+    reply_message!(ctx, msg, format!(fluent!(MUSIC_filter_set), filter));
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use history_cmd::*;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(1)]
+#[usage("[page?]")]
+#[example("2")]
+#[sub_commands(save)]
+/// Show the songs played in the guild, the most recent first
+/// The numbers are the ones to give to `replay`
+async fn history(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    use crate::shared::history::HISTORY_PAGE_SIZE;
+    let guildid = msg.guild_id.unwrap().0;
+    let page = args.parse::<i64>().unwrap_or(1).max(1);
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+
+    let len = crate::shared::history::history_len(db, guildid).await?;
+    if len == 0 {
+        message_err!(fluent!(MUSIC_history_empty));
+    }
+    let pages = (len + HISTORY_PAGE_SIZE - 1) / HISTORY_PAGE_SIZE;
+    let page = page.min(pages);
+    let offset = (page - 1) * HISTORY_PAGE_SIZE;
+    let entries =
+        crate::shared::history::get_history(db, guildid, offset, HISTORY_PAGE_SIZE).await?;
+    let list = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| format!("`{}` {}", offset + i as i64 + 1, entry))
+        .collect::<Vec<_>>()
+        .join("\n");
+    reply_message!(
+        ctx,
+        msg,
+        format!("{}\n{}", list, format!(fluent!(MUSIC_history_page), page, pages))
+    );
+    Ok(())
+}
+
+mod history_cmd {
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+    use serenity::prelude::Context;
+
+    #[command]
+    #[only_in(guilds)]
+    #[num_args(2)]
+    #[usage("[count] [playlist name]")]
+    #[example("10 \"last night\"")]
+    /// Add the last songs played to a playlist, which is created if it doesn't exist
+    async fn save(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let count = match args.single::<i64>() {
+            Ok(count) if count > 0 => count.min(crate::shared::MAX_QUEUED_ITEM as i64),
+            _ => message_err!(fluent!(MUSIC_ARG_invalid_number)),
+        };
+        let name = match args.single_quoted::<String>() {
+            Ok(name) => name,
+            Err(_) => message_err!(fluent!(MUSIC_ARG_playlist_name)),
+        };
+        if name.len() > 32 {
+            message_err!(fluent!(MUSIC_ARG_playlist_name_too_long));
+        }
+        let guildid = msg.guild_id.unwrap().0;
+        let urls = {
+            let lock = ctx.data.read().await;
+            let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+            let mut urls = Vec::new();
+            for entry in crate::shared::history::get_history(db, guildid, 0, count).await? {
+                if !urls.contains(&entry.url) {
+                    urls.push(entry.url);
+                }
+            }
+            urls
+        };
+        if urls.is_empty() {
+            message_err!(fluent!(MUSIC_history_empty));
+        }
+
+        crate::shared::create_playlist_if_not_exist(ctx, &name, msg.author.id.0, guildid).await?;
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        query!("UPDATE user_playlist SET items = array_distinct(array_cat(items, $3::text[])) WHERE name = UPPER($1::varchar(32)) AND guildid = $2::int8;",
+            name, wh_database::shared::Id(guildid) as _, &urls).execute(db).await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!(
+                fluent!(MUSIC_songs_added_playlist),
+                urls.len(),
+                if urls.len() > 1 { "s" } else { "" },
+                name
+            )
+        );
+        Ok(())
+    }
+}
@@ -27,21 +27,6 @@ pub async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 
     let (handler, res) = manager.join(guild_id, connect_to).await;
     res?;
-    let meh = crate::shared::MusicEventHandler {
-        call: handler.clone(),
-    };
-    handler.lock().await.add_global_event(
-        songbird::events::Event::Track(songbird::events::TrackEvent::End),
-        meh,
-    );
-    let voice_recorder = crate::shared::VoiceRecorder {
-        typemap: ctx.data.clone(),
-        guild_id: msg.guild_id.unwrap(),
-    };
-
-    handler.lock().await.add_global_event(
-        songbird::events::Event::Core(songbird::events::CoreEvent::VoicePacket),
-        voice_recorder,
-    );
+    crate::shared::register_call_events(ctx, guild_id, &handler).await;
     Ok(())
 }
@@ -15,13 +15,31 @@ async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
     let has_handler = manager.get(guild_id).is_some();
 
     if has_handler {
+        crate::shared::queue_control::set_queue_loop(guild_id.0, false);
+        if let Some(call) = manager.get(guild_id) {
+            if !call.lock().await.queue().is_empty() {
+                crate::shared::autoplay::halt(guild_id.0);
+            }
+        }
+        crate::shared::now_playing::remove_announcement(&ctx.http, guild_id).await;
         if let Err(e) = manager.remove(guild_id).await {
             both_err!(
                 fluent!(MUSIC_error_leaving_channel),
                 format!(fluent!(MUSIC_LOG_err_leaving_channel), e)
             );
         }
+        // Nothing can be heard once the bot is gone
+        let recording = ctx
+            .data
+            .read()
+            .await
+            .get::<crate::shared::recording::RecordingsKey>()
+            .unwrap()
+            .stop(&guild_id);
         reply_message!(ctx, msg, fluent!(MUSIC_left_voice_channel));
+        if let Some(recording) = recording {
+            crate::shared::recording::finish_in_text_channel(ctx, guild_id, recording).await;
+        }
     } else {
         reply_message!(ctx, msg, fluent!(MUSIC_voice_not_connected));
     }
@@ -0,0 +1,53 @@
+use library_cmd::*;
+use serenity::framework::standard::{macros::*, CommandResult};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+#[command]
+#[only_in(guilds)]
+#[sub_commands(search, scan)]
+/// This command does nothing on its own, must use the subcommands search and scan
+async fn library(_: &Context, _: &Message) -> CommandResult {
+    Ok(())
+}
+
+mod library_cmd {
+    use crate::commands::MUSIC_MANAGE_CHECK;
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+    use serenity::prelude::Context;
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[query]")]
+    #[example("daft punk")]
+    #[min_args(1)]
+    /// Search the title, artist and album of the songs of the library and pick which ones to add to the queue
+    async fn search(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        args.trimmed().unquoted();
+        let query = args.rest().trim().to_string();
+        super::super::pick_and_play(
+            ctx,
+            msg,
+            args,
+            &crate::shared::library::LibrarySearch,
+            &query,
+        )
+        .await
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[num_args(0)]
+    #[checks(music_manage)]
+    /// Read the songs of the library again, after files were added or changed
+    async fn scan(ctx: &Context, msg: &Message) -> CommandResult {
+        let tracks = crate::shared::library::library_index(true).await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!(fluent!(MUSIC_library_scanned), tracks.len())
+        );
+        Ok(())
+    }
+}
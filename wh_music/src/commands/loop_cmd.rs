@@ -4,8 +4,10 @@ use serenity::model::channel::Message;
 
 #[command("loop")]
 #[only_in(guilds)]
-#[usage("loop <?num>")]
+#[usage("loop <?num|queue>")]
 #[example("loop 7")]
+/// Loop the current song, forever or the given number of times, or call it again to stop.
+/// `loop queue` adds every finished song back at the end of the queue instead
 pub async fn loop_cmd(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let manager = songbird::get(&ctx).await.unwrap();
 
@@ -16,6 +18,18 @@ pub async fn loop_cmd(ctx: &Context, msg: &Message, mut args: Args) -> CommandRe
     }
     let call = call.unwrap();
 
+    if args.current() == Some("queue") {
+        let guildid = msg.guild_id.unwrap().0;
+        let enabled = !crate::shared::queue_control::is_queue_looping(guildid);
+        crate::shared::queue_control::set_queue_loop(guildid, enabled);
+        if enabled {
+            reply_message!(ctx, msg, fluent!(MUSIC_loop_queue_enable));
+        } else {
+            reply_message!(ctx, msg, fluent!(MUSIC_loop_queue_disable));
+        }
+        return Ok(());
+    }
+
     let loop_state = {
         let track = call.lock().await.queue().current();
         if track.is_none() {
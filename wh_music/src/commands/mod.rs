@@ -1,9 +1,62 @@
 add_commands!(
     Music,
-    (clear, join, pause, play, queue, resume, skip, playlist, loop_cmd),
-    ()
+    (
+        clear, join, pause, play, queue, resume, skip, playlist, loop_cmd, search, seek, forward,
+        rewind, history, replay, library
+    ),
+    (music_channel)
 );
 
-add_commands!(MusicPriv, (move_cmd, remove, leave), (music_manage));
+add_commands!(
+    MusicPriv,
+    (move_cmd, remove, leave, channel, shuffle, volume, filter, voteskip, nowplaying, autoplay),
+    (music_manage)
+);
+
+add_commands!(MusicRecord, (record), (music_record));
+
+check_permission!(
+    MUSIC_MANAGE_CHECK,
+    "music.manage",
+    fluent!(MUSIC_PERMISSION_manage),
+    wh_permission::shared::registry::DefaultPolicy::Discord(
+        serenity::model::permissions::Permissions::MANAGE_CHANNELS
+    )
+);
+
+check_permission!(
+    MUSIC_RECORD_CHECK,
+    "music.record",
+    fluent!(MUSIC_PERMISSION_record),
+    wh_permission::shared::registry::DefaultPolicy::Discord(
+        serenity::model::permissions::Permissions::MANAGE_GUILD
+    )
+);
+
+use serenity::framework::standard::{Args, Check, CommandOptions, Reason};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+const MUSIC_CHANNEL_CHECK: Check = Check {
+    function: check_music_channel,
+    name: "music.channel",
+    check_in_help: false,
+    display_in_help: false,
+};
 
-check_permission!(MUSIC_MANAGE_CHECK, "music.manage");
+#[hook]
+async fn check_music_channel(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> Result<(), Reason> {
+    let enabled =
+        crate::shared::music_channel_enabled(ctx, msg.guild_id.unwrap().0, msg.channel_id.0)
+            .await
+            .map_err(|e| Reason::Log(format!("Database error: {}", e)))?;
+    if !enabled {
+        return Err(Reason::User(fluent!(MUSIC_channel_disabled_here).into()));
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(3)]
+#[usage("[?#channel|off] [?image|embed] [?buttons]")]
+#[example("#music embed buttons")]
+/// Announce the songs in a channel when they start, without arguments it shows the current settings
+/// The image is sent again for every song while the embed is edited, `buttons` adds buttons to pause and skip
+pub async fn nowplaying(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    use crate::shared::now_playing::{AnnounceStyle, NowPlayingAnnounce};
+    let guildid = msg.guild_id.unwrap().0;
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    if args.is_empty() {
+        let scopes = wh_config::shared::Scope::chain(guildid, None, None);
+        let config =
+            wh_config::shared::resolve_config_or_default::<NowPlayingAnnounce>(db, &scopes).await?;
+        match config.channel {
+            Some(channel) => {
+                reply_message!(
+                    ctx,
+                    msg,
+                    format!(
+                        fluent!(MUSIC_now_playing_current),
+                        channel,
+                        config.style.name(),
+                        if config.buttons { "with" } else { "without" }
+                    )
+                );
+            }
+            None => {
+                reply_message!(ctx, msg, fluent!(MUSIC_now_playing_disabled));
+            }
+        }
+        return Ok(());
+    }
+
+    let first = args.single::<String>().unwrap_or_default();
+    let channel = if first == "off" {
+        None
+    } else {
+        match serenity::utils::parse_channel(&first) {
+            Some(channel) => Some(channel),
+            None => message_err!(fluent!(MUSIC_ARG_channel_mention)),
+        }
+    };
+    let mut style = AnnounceStyle::default();
+    let mut buttons = false;
+    for arg in args.iter::<String>() {
+        match arg.unwrap_or_default().as_str() {
+            "image" => style = AnnounceStyle::Image,
+            "embed" => style = AnnounceStyle::Embed,
+            "buttons" => buttons = true,
+            _ => message_err!(fluent!(MUSIC_ARG_now_playing)),
+        }
+    }
+    let config = NowPlayingAnnounce {
+        channel,
+        style,
+        buttons,
+    };
+    wh_config::shared::update_config::<NowPlayingAnnounce, _, _>(
+        db,
+        guildid,
+        Some(msg.author.id.0),
+        |c| {
+            *c = config;
+            Ok(())
+        },
+    )
+    .await?;
+    match channel {
+        Some(channel) => {
+            reply_message!(
+                ctx,
+                msg,
+                format!(
+                    fluent!(MUSIC_now_playing_set),
+                    channel,
+                    style.name(),
+                    if buttons { "with" } else { "without" }
+                )
+            );
+        }
+        None => {
+            crate::shared::now_playing::remove_announcement(&ctx.http, msg.guild_id.unwrap())
+                .await;
+            reply_message!(ctx, msg, fluent!(MUSIC_now_playing_disabled));
+        }
+    }
+    Ok(())
+}
@@ -6,20 +6,33 @@ use serenity::{client::Context, framework::standard::Args};
 #[aliases("p")]
 #[usage("[query or url]")]
 #[example("https://www.youtube.com/watch?v=dQw4w9WgXcQ")]
-#[min_args(1)]
 /// Make the bot play the music
 /// the query can be a youtube video, a youtube playlist, a simple query or a spotify song/playlist url
+/// It can also be the url of an audio file, a song of the library like `library:artist/song.mp3`, or audio files attached to the message
 async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let guild = msg.guild(&ctx.cache).await.unwrap();
     args.trimmed().unquoted();
-    let url = args.parse::<url::Url>();
-    let song_url = match url {
-        Ok(url) => crate::shared::SongUrl::from_url(url),
-        Err(_) => crate::shared::SongUrl::Query(args.remains().unwrap_or("").to_string()),
+    let song_query = if args.is_empty() {
+        let mut attachments = Vec::new();
+        for attachment in &msg.attachments {
+            if crate::shared::library::is_audio_attachment(attachment) {
+                attachments.push(url::Url::parse(&attachment.url)?);
+            }
+        }
+        match attachments.len() {
+            0 => message_err!(fluent!(MUSIC_ARG_query_or_url)),
+            1 => crate::shared::SongType::SingleUrl(attachments.remove(0)),
+            _ => crate::shared::SongType::MultipleUrl(attachments),
+        }
+    } else {
+        let url = args.parse::<url::Url>();
+        let song_url = match url {
+            Ok(url) => crate::shared::SongUrl::from_url(url),
+            Err(_) => crate::shared::SongUrl::Query(args.remains().unwrap_or("").to_string()),
+        };
+        song_url.into_query().await?
     };
 
-    let song_query = song_url.into_query().await?;
-
     let vc = guild.voice_states.get(&ctx.cache.current_user_id().await);
     if vc.is_none() {
         super::join(ctx, msg, args).await?;
@@ -352,13 +352,7 @@ SELECT count(*) FROM deleted", wh_database::shared::Id(msg.author.id.0)as _, wh_
 
             let (handler, res) = manager.join(guild_id, connect_to).await;
             res?;
-            let meh = crate::shared::MusicEventHandler {
-                call: handler.clone(),
-            };
-            handler.lock().await.add_global_event(
-                songbird::events::Event::Track(songbird::events::TrackEvent::End),
-                meh,
-            );
+            crate::shared::register_call_events(ctx, guild_id, &handler).await;
         }
 
         let manager = songbird::get(ctx).await.unwrap();
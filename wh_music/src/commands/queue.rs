@@ -30,120 +30,23 @@ pub async fn queue(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
             let len = (valid_queue.len() as f32 / 10f32).ceil() as u8;
             let page_num = page_num.clamp(0, len as u16);
 
-            let now_playing = match queue.get(0).map(|e| async move {
-                let now_playing_info = e.get_info().await;
-                if let Err(e) = now_playing_info {
-                    return Err(e);
-                }
-                let now_playing_info = now_playing_info.unwrap();
-                let now_playing_typemap = e.typemap().read().await;
-                let now_playing_metadata = now_playing_typemap
-                    .get::<crate::shared::TrackMetadataKey>()
-                    .unwrap();
-
-                Ok(crate::shared::NowPlaying {
-                    time_in: now_playing_info.position,
-                    song: crate::shared::Song {
-                        duration: now_playing_metadata
-                            .duration
-                            .unwrap_or(std::time::Duration::ZERO),
-                        title: {
-                            let mut title =
-                                now_playing_metadata.title.clone().unwrap_or_else(|| {
-                                    now_playing_metadata
-                                        .url
-                                        .clone()
-                                        .unwrap_or_else(|| String::from("Unknown"))
-                                });
-                            if title.len() > 47 {
-                                title = title.chars().take(57).collect::<String>() + "...";
-                            }
-                            title
-                        },
-                        added_by: now_playing_metadata.added_by.0,
-                        loop_num: match now_playing_info.loops {
-                            songbird::tracks::LoopState::Infinite => {
-                                crate::shared::LoopState::Infinite
-                            }
-                            songbird::tracks::LoopState::Finite(1 | 0) => {
-                                crate::shared::LoopState::None
-                            }
-                            songbird::tracks::LoopState::Finite(n) => {
-                                crate::shared::LoopState::Finite(n.min(100) as u8)
-                            }
-                        },
-                    },
-                })
-            }) {
-                Some(f) => Some(f.await?),
+            let now_playing = match queue.get(0) {
+                Some(track) => Some(
+                    crate::shared::now_playing::now_playing(msg.guild_id.unwrap().0, track).await?,
+                ),
                 None => None,
             };
-            let queue =
-                match valid_queue
-                    .chunks(10)
-                    .nth(page_num as usize)
-                    .map(|songs| async move {
-                        let mut out = arrayvec::ArrayVec::<_, 10>::new();
-                        for song in songs.iter() {
-                            let song_info = song.get_info().await;
-                            if let Err(e) = song_info {
-                                return Err(e);
-                            }
-                            let song_info = song_info.unwrap();
-
-                            let song_typemap = song.typemap().read().await;
-                            let song_metadata = song_typemap
-                                .get::<crate::shared::TrackMetadataKey>()
-                                .unwrap();
-                            out.push(crate::shared::Song {
-                                duration: song_metadata
-                                    .duration
-                                    .unwrap_or(std::time::Duration::ZERO),
-                                title: {
-                                    let mut title =
-                                        song_metadata.title.clone().unwrap_or_else(|| {
-                                            song_metadata
-                                                .url
-                                                .clone()
-                                                .unwrap_or_else(|| String::from("Unknown"))
-                                        });
-                                    if title.len() > 47 {
-                                        title = title.chars().take(57).collect::<String>() + "...";
-                                    }
-                                    title
-                                },
-                                added_by: song_metadata.added_by.0,
-                                loop_num: match song_info.loops {
-                                    songbird::tracks::LoopState::Infinite => {
-                                        crate::shared::LoopState::Infinite
-                                    }
-                                    songbird::tracks::LoopState::Finite(1 | 0) => {
-                                        crate::shared::LoopState::None
-                                    }
-                                    songbird::tracks::LoopState::Finite(n) => {
-                                        crate::shared::LoopState::Finite(n.min(100) as u8)
-                                    }
-                                },
-                            })
-                        }
-                        Ok(out)
-                    }) {
-                    Some(f) => Some(f.await?),
-                    None => None,
+            let mut queue = arrayvec::ArrayVec::<_, 10>::new();
+            if let Some(songs) = valid_queue.chunks(10).nth(page_num as usize) {
+                for song in songs {
+                    queue.push(crate::shared::now_playing::song(song).await?);
                 }
-                .unwrap_or_default();
+            }
             if let Some(now_playing) = now_playing {
                 let typing = msg.channel_id.start_typing(&ctx.http)?;
                 let client = reqwest::Client::new();
-                let request_builder = client.post(format!(
-                    "{base}/api/queue/now_playing",
-                    base = *crate::shared::BASE_URL
-                ));
-                let request_builder = request_builder.json(&now_playing);
-
-                let request = request_builder.send().await?;
-
-                let now_playling_img = request.bytes().await?;
+                let now_playling_img =
+                    crate::shared::now_playing::render_now_playing(&now_playing).await?;
                 let queue_list_img;
                 typing.stop();
                 let mut queue_list_requested = false;
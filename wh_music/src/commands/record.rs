@@ -0,0 +1,156 @@
+use record_cmd::*;
+use serenity::framework::standard::{macros::*, CommandResult};
+use serenity::model::channel::Message;
+use serenity::prelude::Context;
+
+#[command]
+#[only_in(guilds)]
+#[num_args(0)]
+#[sub_commands(start, stop)]
+/// Record the voice channel of the bot, only the people who agree with the button of the announcement are recorded
+/// Without subcommand it shows the current recording
+async fn record(ctx: &Context, msg: &Message) -> CommandResult {
+    let recordings = ctx
+        .data
+        .read()
+        .await
+        .get::<crate::shared::recording::RecordingsKey>()
+        .unwrap()
+        .clone();
+    let status = recordings.with_guild(&msg.guild_id.unwrap(), |recording| {
+        format!(
+            fluent!(MUSIC_record_status),
+            recording.channel,
+            crate::shared::queue_control::format_position(recording.elapsed()),
+            recording.consented().len()
+        )
+    });
+    let status = match status {
+        Some(status) => status,
+        None => message_err!(fluent!(MUSIC_record_not_running)),
+    };
+    reply_message!(ctx, msg, status);
+    Ok(())
+}
+
+mod record_cmd {
+    use crate::shared::recording::{GuildRecording, RecordingFormat, RecordingsKey};
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+    use serenity::prelude::Context;
+
+    #[command]
+    #[only_in(guilds)]
+    #[num_args(0)]
+    /// Start recording your voice channel, the bot joins it if needed
+    async fn start(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+        let guild = msg.guild(&ctx.cache).await.unwrap();
+        let channel_id = match guild
+            .voice_states
+            .get(&msg.author.id)
+            .and_then(|x| x.channel_id)
+        {
+            Some(channel_id) => channel_id,
+            None => message_err!(fluent!(MUSIC_need_voice_channel)),
+        };
+        if !guild
+            .voice_states
+            .contains_key(&ctx.cache.current_user_id().await)
+        {
+            crate::commands::join(ctx, msg, args).await?;
+        }
+        let call = match songbird::get(ctx).await.unwrap().get(guild.id) {
+            Some(call) => call,
+            None => message_err!(fluent!(MUSIC_voice_not_connected)),
+        };
+        if call.lock().await.current_channel().map(|c| c.0) != Some(channel_id.0) {
+            message_err!(fluent!(MUSIC_not_same_channel));
+        }
+
+        let recordings = ctx
+            .data
+            .read()
+            .await
+            .get::<RecordingsKey>()
+            .unwrap()
+            .clone();
+        let recording = GuildRecording::new(channel_id, msg.channel_id, msg.author.id);
+        let started = recording.started_at();
+        let full = recording.full();
+        if !recordings.start(guild.id, recording) {
+            message_err!(fluent!(MUSIC_record_already));
+        }
+        crate::shared::recording::set_decoding(&call, true).await;
+        let announcement = msg
+            .channel_id
+            .send_message(&ctx.http, |m| {
+                m.content(format!(
+                    fluent!(MUSIC_record_announcement),
+                    msg.author.id,
+                    channel_id,
+                    crate::shared::recording::MAX_RECORDING_DURATION.as_secs() / 60
+                ))
+                .components(|c| crate::shared::recording::fill_components(c, true))
+            })
+            .await;
+        match announcement {
+            Ok(announcement) => {
+                recordings.with_guild(&guild.id, |recording| {
+                    // It may have been stopped and started again while the announcement was sent
+                    if recording.started_at() == started {
+                        recording.announcement = Some(announcement.id);
+                    }
+                });
+                crate::shared::recording::stop_at_limit(ctx.clone(), guild.id, started, full);
+                Ok(())
+            }
+            Err(e) => {
+                // Nobody could agree without the announcement
+                if recordings.stop_started_at(&guild.id, started).is_some() {
+                    crate::shared::recording::set_decoding(&call, false).await;
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[max_args(1)]
+    #[usage("[?ogg|wav]")]
+    #[example("wav")]
+    /// Stop the recording and send the mixed audio, the audio of each speaker and when they spoke
+    /// The files are stored on the bot if they are too big to be sent
+    async fn stop(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+        let format = match args.current() {
+            None | Some("ogg") => RecordingFormat::Ogg,
+            Some("wav") => RecordingFormat::Wav,
+            Some(_) => message_err!(fluent!(MUSIC_ARG_record_format)),
+        };
+        let guild_id = msg.guild_id.unwrap();
+        let recording = {
+            let recordings = ctx
+                .data
+                .read()
+                .await
+                .get::<RecordingsKey>()
+                .unwrap()
+                .clone();
+            match recordings.stop(&guild_id) {
+                Some(recording) => recording,
+                None => message_err!(fluent!(MUSIC_record_not_running)),
+            }
+        };
+        if let Some(dir) =
+            crate::shared::recording::finish(ctx, msg.channel_id, guild_id, recording, format)
+                .await?
+        {
+            reply_message!(
+                ctx,
+                msg,
+                format!(fluent!(MUSIC_record_stored), dir.display())
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use serenity::framework::standard::{macros::*, CommandResult};
+use serenity::model::channel::Message;
+use serenity::{client::Context, framework::standard::Args};
+
+#[command]
+#[only_in(guilds)]
+#[num_args(1)]
+#[usage("[number]")]
+#[example("1")]
+/// Add a song of the history back to the queue, 1 being the last song played
+async fn replay(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let index = match args.parse::<i64>() {
+        Ok(index) if index > 0 => index,
+        _ => message_err!(fluent!(MUSIC_ARG_invalid_number)),
+    };
+    let entry = {
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        crate::shared::history::get_history(db, msg.guild_id.unwrap().0, index - 1, 1)
+            .await?
+            .pop()
+    };
+    let entry = match entry {
+        Some(entry) => entry,
+        None => message_err!(fluent!(MUSIC_ARG_index_oob)),
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let vc = guild.voice_states.get(&ctx.cache.current_user_id().await);
+    if vc.is_none() {
+        super::join(ctx, msg, args).await?;
+    }
+
+    let manager = songbird::get(ctx).await.unwrap();
+    let call = manager.get(guild.id).unwrap();
+    crate::shared::play_yt_url(call, entry.url, ctx, msg, true).await
+}
@@ -0,0 +1,49 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[aliases("rw")]
+#[num_args(1)]
+#[usage("[duration]")]
+#[example("10")]
+/// Go back in the current song
+pub async fn rewind(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let offset = match crate::shared::queue_control::parse_timestamp(args.rest()) {
+        Some(offset) => offset,
+        None => message_err!(fluent!(MUSIC_ARG_timestamp)),
+    };
+    let call = songbird::get(ctx).await.unwrap().get(msg.guild_id.unwrap());
+    let call = match call {
+        Some(call) => call,
+        None => message_err!(fluent!(MUSIC_voice_not_connected)),
+    };
+    let channel_id = msg
+        .guild(&ctx.cache)
+        .await
+        .unwrap()
+        .voice_states
+        .get(&msg.author.id)
+        .and_then(|x| x.channel_id);
+    if channel_id.map(|c| c.0) != call.lock().await.current_channel().map(|c| c.0) {
+        message_err!(fluent!(MUSIC_not_same_channel));
+    }
+    let position = crate::shared::queue_control::seek_current(
+        msg.guild_id.unwrap().0,
+        &call,
+        |position, duration| {
+            crate::shared::queue_control::offset_position(position, offset, false, duration)
+        },
+    )
+    .await?;
+    reply_message!(
+        ctx,
+        msg,
+        format!(
+            fluent!(MUSIC_seeked),
+            crate::shared::queue_control::format_position(position)
+        )
+    );
+    Ok(())
+}
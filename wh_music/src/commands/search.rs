@@ -0,0 +1,109 @@
+use serenity::framework::standard::{macros::*, CommandResult};
+use serenity::model::channel::Message;
+use serenity::model::interactions::InteractionResponseType;
+use serenity::{client::Context, framework::standard::Args};
+
+#[command]
+#[only_in(guilds)]
+#[usage("[query]")]
+#[example("never gonna give you up")]
+#[min_args(1)]
+/// Search for a song and pick which of the results to add to the queue
+/// Pick them in the menu or answer with their numbers, like `1 3` or `2-4`
+async fn search(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    args.trimmed().unquoted();
+    let query = args.rest().trim().to_string();
+    let backend = ctx
+        .data
+        .read()
+        .await
+        .get::<crate::shared::search::SearchBackendKey>()
+        .unwrap()
+        .clone();
+    pick_and_play(ctx, msg, args, backend.as_ref(), &query).await
+}
+
+/// Show what the backend found for the query and add the results the author picks to the queue
+pub(crate) async fn pick_and_play(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+    backend: &dyn crate::shared::search::SearchBackend,
+    query: &str,
+) -> CommandResult {
+    use crate::shared::search::{fill_components, find, format_results, PickAnswer, PICK_TIMEOUT};
+
+    let results = find(backend, query).await?;
+    let list = msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(format!(
+                "{}\n{}",
+                format_results(&results),
+                fluent!(MUSIC_search_pick)
+            ))
+            .components(|c| fill_components(c, &results, true))
+        })
+        .await?;
+
+    let answer = tokio::select! {
+        reply = msg.author.await_reply(ctx).channel_id(msg.channel_id).timeout(PICK_TIMEOUT) => {
+            reply.map(|reply| PickAnswer::Message(reply.content.clone()))
+        }
+        interaction = list.await_component_interaction(ctx).author_id(msg.author.id).timeout(PICK_TIMEOUT) => {
+            match interaction {
+                Some(interaction) => {
+                    interaction
+                        .create_interaction_response(&ctx.http, |r| {
+                            r.kind(InteractionResponseType::DeferredUpdateMessage)
+                        })
+                        .await?;
+                    Some(PickAnswer::Menu(interaction.data.values.clone()))
+                }
+                None => None,
+            }
+        }
+    };
+    // The menu can't pick anything anymore
+    if let Err(e) = list
+        .channel_id
+        .edit_message(&ctx.http, list.id, |m| {
+            m.components(|c| fill_components(c, &results, false))
+        })
+        .await
+    {
+        warn!("Failed to remove the search menu: {}", e);
+    }
+
+    let answer = match answer {
+        Some(answer) => answer,
+        None => message_err!(fluent!(MUSIC_search_timeout)),
+    };
+    let picked = match answer.picked(&results) {
+        Some(picked) => picked,
+        None => message_err!(fluent!(MUSIC_search_cancelled)),
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let vc = guild.voice_states.get(&ctx.cache.current_user_id().await);
+    if vc.is_none() {
+        super::join(ctx, msg, args).await?;
+    }
+
+    let manager = songbird::get(ctx).await.unwrap();
+    let call = manager.get(guild.id).unwrap();
+
+    let show_addition = picked.len() == 1;
+    for result in &picked {
+        crate::shared::play_yt_url(call.clone(), result.url.clone(), ctx, msg, show_addition)
+            .await?;
+    }
+    if !show_addition {
+        reply_message!(
+            ctx,
+            msg,
+            format!(fluent!(MUSIC_add_to_queue_multiple), picked.len())
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[num_args(1)]
+#[usage("[timestamp]")]
+#[example("1:23")]
+/// Go to the given time in the current song
+pub async fn seek(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let timestamp = match crate::shared::queue_control::parse_timestamp(args.rest()) {
+        Some(timestamp) => timestamp,
+        None => message_err!(fluent!(MUSIC_ARG_timestamp)),
+    };
+    let call = songbird::get(ctx).await.unwrap().get(msg.guild_id.unwrap());
+    let call = match call {
+        Some(call) => call,
+        None => message_err!(fluent!(MUSIC_voice_not_connected)),
+    };
+    let channel_id = msg
+        .guild(&ctx.cache)
+        .await
+        .unwrap()
+        .voice_states
+        .get(&msg.author.id)
+        .and_then(|x| x.channel_id);
+    if channel_id.map(|c| c.0) != call.lock().await.current_channel().map(|c| c.0) {
+        message_err!(fluent!(MUSIC_not_same_channel));
+    }
+    let position = crate::shared::queue_control::seek_current(
+        msg.guild_id.unwrap().0,
+        &call,
+        |_, duration| match duration {
+            Some(duration) => timestamp.min(duration),
+            None => timestamp,
+        },
+    )
+    .await?;
+    reply_message!(
+        ctx,
+        msg,
+        format!(
+            fluent!(MUSIC_seeked),
+            crate::shared::queue_control::format_position(position)
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[usage("")]
+#[num_args(0)]
+/// Shuffle the songs waiting in the queue, the current one keeps playing
+pub async fn shuffle(ctx: &Context, msg: &Message) -> CommandResult {
+    let call = songbird::get(ctx).await.unwrap().get(msg.guild_id.unwrap());
+    let call = match call {
+        Some(call) => call,
+        None => message_err!(fluent!(MUSIC_voice_not_connected)),
+    };
+    let shuffled = crate::shared::queue_control::shuffle_queue(call.lock().await.queue());
+    if shuffled == 0 {
+        message_err!(fluent!(MUSIC_empty_queue));
+    }
+    reply_message!(ctx, msg, format!(fluent!(MUSIC_shuffled), shuffled));
+    Ok(())
+}
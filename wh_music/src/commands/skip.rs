@@ -6,6 +6,7 @@ use serenity::model::channel::Message;
 #[aliases("s")]
 #[only_in(guilds)]
 /// Skipped the current song
+/// Unless you added the song or can manage the music, enough listeners need to vote for it
 pub async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
     let sb = songbird::get(ctx).await.unwrap();
     let call_opt = sb.get(msg.guild_id.unwrap());
@@ -15,17 +16,26 @@ pub async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
         .voice_states
         .get(&msg.author.id)
         .and_then(|x| x.channel_id);
-    match call_opt {
-        Some(call) => {
-            if channel_id.map(|c| c.0) == call.lock().await.current_channel().map(|c| c.0) {
-                call.lock().await.queue().skip()?;
-            } else {
-                message_err!(fluent!(MUSIC_not_same_channel));
-            }
+    let call = match call_opt {
+        Some(call) => call,
+        None => message_err!(fluent!(MUSIC_voice_not_connected)),
+    };
+    if channel_id.map(|c| c.0) != call.lock().await.current_channel().map(|c| c.0) {
+        message_err!(fluent!(MUSIC_not_same_channel));
+    }
+    match crate::shared::vote_skip::request_skip(ctx, &guild, msg.author.id, msg.channel_id, &call)
+        .await?
+    {
+        crate::shared::vote_skip::SkipOutcome::Skipped => (),
+        crate::shared::vote_skip::SkipOutcome::Voted { votes, required } => {
+            reply_message!(ctx, msg, format!(fluent!(MUSIC_skip_voted), votes, required));
         }
-        None => {
-            message_err!(fluent!(MUSIC_voice_not_connected));
+        crate::shared::vote_skip::SkipOutcome::AlreadyVoted { votes, required } => {
+            message_err!(format!(
+                fluent!(MUSIC_skip_already_voted),
+                votes, required
+            ))
         }
-    };
+    }
     Ok(())
 }
@@ -0,0 +1,45 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(1)]
+#[usage("[?volume]")]
+#[example("50")]
+/// Set the volume of the guild in percent, up to 200, without a volume it shows the current one
+pub async fn volume(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guildid = msg.guild_id.unwrap().0;
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    if args.is_empty() {
+        let settings = crate::shared::audio::audio_settings(db, guildid).await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!(fluent!(MUSIC_volume_current), settings.volume)
+        );
+        return Ok(());
+    }
+    let volume = match args.parse::<u16>() {
+        Ok(volume) if volume <= crate::shared::audio::MAX_VOLUME => volume,
+        _ => message_err!(fluent!(MUSIC_ARG_volume)),
+    };
+    let settings = wh_config::shared::update_config::<crate::shared::audio::AudioSettings, _, _>(
+        db,
+        guildid,
+        Some(msg.author.id.0),
+        |c| {
+            c.volume = volume;
+            Ok(*c)
+        },
+    )
+    .await?;
+    if let Some(call) = songbird::get(ctx).await.unwrap().get(guildid) {
+        for track in call.lock().await.queue().current_queue() {
+            track.set_volume(settings.track_volume())?;
+        }
+    }
+    reply_message!(ctx, msg, format!(fluent!(MUSIC_volume_set), volume));
+    Ok(())
+}
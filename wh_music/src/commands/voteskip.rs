@@ -0,0 +1,56 @@
+use serenity::client::Context;
+use serenity::framework::standard::{macros::*, Args, CommandResult};
+use serenity::model::channel::Message;
+
+#[command]
+#[only_in(guilds)]
+#[max_args(1)]
+#[usage("[?percent|off]")]
+#[example("50")]
+/// Set the percentage of the listeners who need to vote to skip a song, without a value it shows the current one
+/// With `off` or 0 anyone can skip a song
+pub async fn voteskip(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guildid = msg.guild_id.unwrap().0;
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    if args.is_empty() {
+        let scopes = wh_config::shared::Scope::chain(guildid, None, None);
+        let config = wh_config::shared::resolve_config_or_default::<
+            crate::shared::vote_skip::SkipVote,
+        >(db, &scopes)
+        .await?;
+        if config.threshold == 0 {
+            reply_message!(ctx, msg, fluent!(MUSIC_voteskip_disabled));
+        } else {
+            reply_message!(
+                ctx,
+                msg,
+                format!(fluent!(MUSIC_voteskip_current), config.threshold)
+            );
+        }
+        return Ok(());
+    }
+    let threshold = match args.rest().trim() {
+        "off" => 0,
+        arg => match arg.trim_end_matches('%').parse::<u8>() {
+            Ok(threshold) if threshold <= 100 => threshold,
+            _ => message_err!(fluent!(MUSIC_ARG_voteskip)),
+        },
+    };
+    wh_config::shared::update_config::<crate::shared::vote_skip::SkipVote, _, _>(
+        db,
+        guildid,
+        Some(msg.author.id.0),
+        |c| {
+            c.threshold = threshold;
+            Ok(())
+        },
+    )
+    .await?;
+    if threshold == 0 {
+        reply_message!(ctx, msg, fluent!(MUSIC_voteskip_disabled));
+    } else {
+        reply_message!(ctx, msg, format!(fluent!(MUSIC_voteskip_set), threshold));
+    }
+    Ok(())
+}
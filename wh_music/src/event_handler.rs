@@ -0,0 +1,84 @@
+use serenity::{
+    client::Context,
+    model::{
+        gateway::Ready,
+        interactions::{
+            Interaction, InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `ready` is sent again after a reconnection, the saving task must only be started once
+static TASKS_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The gateway events of the module, the songbird ones are handled by [`crate::shared::MusicEventHandler`]
+pub struct MusicClientHandler;
+
+#[serenity::async_trait]
+impl serenity::client::EventHandler for MusicClientHandler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        let guilds = ready.guilds.iter().map(|g| g.id()).collect::<Vec<_>>();
+        crate::shared::saved_queue::restore_queues(&ctx, &guilds).await;
+        if TASKS_STARTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(crate::shared::saved_queue::SAVE_INTERVAL);
+            // The first tick is immediate, the queues are still being restored
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let guilds = ctx.cache.guilds().await;
+                if let Err(e) = crate::shared::saved_queue::save_queues(&ctx.data, &guilds).await {
+                    error!("Couldn't save the music queues: {}", e);
+                }
+            }
+        });
+    }
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let component = match interaction {
+            Interaction::MessageComponent(component) => component,
+            _ => return,
+        };
+        let pressed = if crate::shared::now_playing::is_button(&component.data.custom_id) {
+            crate::shared::now_playing::press_button(&ctx, &component).await
+        } else if crate::shared::recording::is_button(&component.data.custom_id) {
+            crate::shared::recording::press_button(&ctx, &component).await
+        } else {
+            return;
+        };
+        let answer = match pressed {
+            Ok(answer) => answer,
+            Err(e) => match e.downcast_ref::<wh_core::Error>() {
+                Some(wh_core::Error::Message(msg)) => Some(msg.clone()),
+                Some(wh_core::Error::Both { msg, err }) => {
+                    error!("[music buttons]{}", err);
+                    Some(msg.clone())
+                }
+                Some(wh_core::Error::Error(err)) => {
+                    error!("[music buttons]{}", err);
+                    None
+                }
+                None => {
+                    error!("[music buttons] {}", e);
+                    None
+                }
+            },
+        };
+        let response = component
+            .create_interaction_response(&ctx.http, |r| match answer {
+                Some(answer) => r
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.content(answer)
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    }),
+                None => r.kind(InteractionResponseType::DeferredUpdateMessage),
+            })
+            .await;
+        if let Err(e) = response {
+            error!("Couldn't answer the music buttons: {}", e);
+        }
+    }
+}
@@ -22,8 +22,10 @@ extern crate serde_json;
 extern crate serenity;
 extern crate songbird;
 extern crate tokio;
+extern crate wh_config;
 extern crate wh_database;
 
 pub mod commands;
+pub mod event_handler;
 pub mod module;
 pub mod shared;
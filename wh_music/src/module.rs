@@ -3,6 +3,7 @@ pub static MODULE_DECLARATION: wh_core::ModuleDeclaration = wh_core::ModuleDecla
     command_groups: &[
         &crate::commands::MUSIC_GROUP,
         &crate::commands::MUSICPRIV_GROUP,
+        &crate::commands::MUSICRECORD_GROUP,
     ],
     register_typemap: |t| Box::pin(register_typemap(t)),
     register_event_handler: |e| Box::pin(register_event_handler(e)),
@@ -11,9 +12,16 @@ pub static MODULE_DECLARATION: wh_core::ModuleDeclaration = wh_core::ModuleDecla
     register_init,
 };
 
-async fn register_typemap(_: &mut serenity::prelude::TypeMap) {}
+async fn register_typemap(tm: &mut serenity::prelude::TypeMap) {
+    tm.insert::<crate::shared::search::SearchBackendKey>(std::sync::Arc::new(
+        crate::shared::search::YoutubeDlSearch,
+    ));
+    tm.insert::<crate::shared::recording::RecordingsKey>(Default::default());
+}
 
-async fn register_event_handler(_: &mut wh_core::event_handler::WhEventHandlerManager) {}
+async fn register_event_handler(eh: &mut wh_core::event_handler::WhEventHandlerManager) {
+    eh.push(crate::event_handler::MusicClientHandler);
+}
 
 fn register_builder(
     client: serenity::client::ClientBuilder<'_>,
@@ -29,6 +37,4 @@ fn register_intent(
     intent | I::GUILD_VOICE_STATES | I::GUILD_MESSAGES | I::GUILDS
 }
 
-fn register_init() {
-    wh_permission::shared::user_permission::add_permission(&["music.manage"]);
-}
+fn register_init() {}
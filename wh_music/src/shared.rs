@@ -2,6 +2,17 @@ use serenity::framework::standard::{macros::hook, CommandError, CommandResult};
 use serenity::model::id::UserId;
 use serenity::prelude::TypeMapKey;
 
+pub mod audio;
+pub mod autoplay;
+pub mod history;
+pub mod library;
+pub mod now_playing;
+pub mod queue_control;
+pub mod recording;
+pub mod saved_queue;
+pub mod search;
+pub mod vote_skip;
+
 pub const MAX_QUEUED_ITEM: usize = 1000;
 pub const TIME_BEFORE_LEAVE: u64 = 5 * 60 * 1000;
 
@@ -22,14 +33,151 @@ pub struct TrackMetadata {
 
 pub struct MusicEventHandler {
     pub(crate) call: std::sync::Arc<tokio::sync::Mutex<songbird::Call>>,
+    pub(crate) guild_id: serenity::model::id::GuildId,
+    pub(crate) typemap: std::sync::Arc<tokio::sync::RwLock<serenity::prelude::TypeMap>>,
+    pub(crate) http: std::sync::Arc<serenity::http::Http>,
+    pub(crate) cache: std::sync::Arc<serenity::cache::Cache>,
+    /// Who the songs picked by autoplay are added by
+    pub(crate) bot_id: UserId,
+}
+
+impl MusicEventHandler {
+    /// Add the song autoplay picks after `last` to the queue, returns whether one was added
+    async fn autoplay(&self, last: Option<&str>) -> bool {
+        // Nobody would hear it, the bot leaves the call instead
+        let channel = self.call.lock().await.current_channel();
+        let listeners = match channel {
+            Some(channel) => {
+                self.cache
+                    .guild_field(self.guild_id, |guild| {
+                        crate::shared::vote_skip::listener_count(
+                            guild,
+                            serenity::model::id::ChannelId(channel.0),
+                        )
+                    })
+                    .await
+            }
+            None => None,
+        };
+        if listeners == Some(0) {
+            return false;
+        }
+        let db = self
+            .typemap
+            .read()
+            .await
+            .get::<DatabaseKey>()
+            .unwrap()
+            .clone();
+        let url = match crate::shared::autoplay::next_url(&db, self.guild_id.0, last).await {
+            Ok(Some(url)) => url,
+            Ok(None) => return false,
+            Err(e) => {
+                error!("Couldn't pick the song to autoplay: {}", e);
+                return false;
+            }
+        };
+        let settings = crate::shared::audio::audio_settings(&db, self.guild_id.0)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Couldn't read the audio settings: {}", e);
+                Default::default()
+            });
+        match create_track(url.clone(), self.bot_id, self.guild_id, &settings).await {
+            Ok((track, _)) => {
+                self.call.lock().await.enqueue(track);
+                true
+            }
+            Err(e) => {
+                error!("Couldn't autoplay {}: {}", url, e);
+                false
+            }
+        }
+    }
 }
 
 #[serenity::async_trait]
 impl songbird::events::EventHandler for MusicEventHandler {
-    async fn act(&self, _: &songbird::events::EventContext<'_>) -> Option<songbird::events::Event> {
+    async fn act(
+        &self,
+        ctx: &songbird::events::EventContext<'_>,
+    ) -> Option<songbird::events::Event> {
+        let mut last_played = None;
+        if let songbird::EventContext::Track(tracks) = ctx {
+            for (state, handle) in tracks.iter() {
+                if state.play_time.is_zero() {
+                    continue;
+                }
+                if let Some(url) = &handle.metadata().source_url {
+                    crate::shared::autoplay::remember_played(self.guild_id.0, url);
+                    last_played = Some(url.clone());
+                }
+                let metadata = handle
+                    .typemap()
+                    .read()
+                    .await
+                    .get::<TrackMetadataKey>()
+                    .cloned();
+                if let Some(metadata) = metadata {
+                    let lock = self.typemap.read().await;
+                    let db = lock.get::<DatabaseKey>().unwrap();
+                    if let Err(e) =
+                        crate::shared::history::record_played(db, self.guild_id.0, &metadata).await
+                    {
+                        error!("Couldn't add the song to the history: {}", e);
+                    }
+                }
+            }
+            if crate::shared::queue_control::is_queue_looping(self.guild_id.0) {
+                for (state, handle) in tracks.iter() {
+                    // A track that failed to start would fail again forever
+                    if state.play_time.is_zero() {
+                        continue;
+                    }
+                    let (url, added_by) = {
+                        let typemap = handle.typemap().read().await;
+                        match typemap.get::<TrackMetadataKey>() {
+                            Some(TrackMetadata {
+                                url: Some(url),
+                                added_by,
+                                ..
+                            }) => (url.clone(), *added_by),
+                            _ => continue,
+                        }
+                    };
+                    let settings = {
+                        let lock = self.typemap.read().await;
+                        let db = lock.get::<DatabaseKey>().unwrap();
+                        crate::shared::audio::audio_settings(db, self.guild_id.0).await
+                    };
+                    let settings = settings.unwrap_or_else(|e| {
+                        error!("Couldn't read the audio settings: {}", e);
+                        Default::default()
+                    });
+                    match create_track(url, added_by, self.guild_id, &settings).await {
+                        Ok((track, _)) => self.call.lock().await.enqueue(track),
+                        Err(e) => error!("Couldn't add the track back to the queue: {}", e),
+                    }
+                }
+            }
+        }
+        if self.call.lock().await.queue().is_empty()
+            && !crate::shared::autoplay::take_halt(self.guild_id.0)
+            && self.autoplay(last_played.as_deref()).await
+        {
+            return None;
+        }
         if self.call.lock().await.queue().is_empty() {
+            crate::shared::now_playing::remove_announcement(&self.http, self.guild_id).await;
             tokio::time::sleep(tokio::time::Duration::from_millis(TIME_BEFORE_LEAVE)).await;
-            if self.call.lock().await.queue().is_empty() {
+            // The recording would end without its files
+            let recording = self
+                .typemap
+                .read()
+                .await
+                .get::<crate::shared::recording::RecordingsKey>()
+                .is_some_and(|recordings| recordings.is_recording(&self.guild_id));
+            if self.call.lock().await.queue().is_empty() && !recording {
                 match self.call.lock().await.leave().await {
                     Ok(_) => (),
                     Err(e) => error!("Error when disconnecting: {}", e),
@@ -56,12 +204,18 @@ pub enum SongUrl {
     Spotify(url::Url),
     Query(String),
     Deezer(url::Url),
+    /// An audio file, played with ffmpeg
+    Direct(url::Url),
+    /// A song of the local library
+    Library(url::Url),
 }
 
 impl SongUrl {
     pub fn from_url(url: url::Url) -> Self {
         match url.scheme() {
             "spotify" => Self::Spotify(url),
+            crate::shared::library::LIBRARY_SCHEME => Self::Library(url),
+            _ if crate::shared::library::is_direct_audio_url(&url) => Self::Direct(url),
             "http" | "https" => match url.host() {
                 Some(url::Host::Domain(u)) => match u {
                     "youtube.com" | "youtu.be" | "www.youtube.com" | "www.youtu.be" => {
@@ -87,6 +241,7 @@ impl SongUrl {
             Self::Query(s) => SongType::SingleQuery(s),
             Self::Spotify(q) => SongType::MultipleQuery(handle_spotify(q).await?),
             Self::Deezer(q) => SongType::MultipleQuery(handle_deezer(q).await?),
+            Self::Direct(u) | Self::Library(u) => SongType::SingleUrl(u),
         })
     }
 }
@@ -418,6 +573,54 @@ pub async fn get_video_name(url: &str) -> CommandResult<Option<String>> {
     Ok(title)
 }
 
+/// Create the track of a youtube url, `ytsearch` query, audio file url or `library:` url, with its [`TrackMetadata`] and the audio settings of the guild
+pub async fn create_track<U>(
+    url: U,
+    added_by: UserId,
+    guild_id: serenity::model::id::GuildId,
+    settings: &crate::shared::audio::AudioSettings,
+) -> Result<(songbird::tracks::Track, songbird::tracks::TrackHandle), songbird::input::error::Error>
+where
+    U: AsRef<str> + Send + Sync + Clone + 'static,
+{
+    let uri = url.as_ref().to_string();
+    let song: songbird::input::Input = match crate::shared::library::ffmpeg_input(&uri)? {
+        Some(input) => {
+            let restarter = crate::shared::audio::FilteredFile {
+                input,
+                url: uri,
+                guildid: guild_id.0,
+            };
+            songbird::input::restartable::Restartable::new(restarter, true)
+                .await?
+                .into()
+        }
+        None => {
+            let restarter = crate::shared::audio::FilteredYtdl {
+                uri,
+                guildid: guild_id.0,
+            };
+            songbird::input::restartable::Restartable::new(restarter, true)
+                .await?
+                .into()
+        }
+    };
+    let metadata = crate::shared::TrackMetadata {
+        url: song.metadata.source_url.clone(),
+        title: song.metadata.title.clone(),
+        duration: song.metadata.duration,
+        added_by,
+    };
+    let (mut track, handle) = songbird::tracks::create_player(song);
+    track.set_volume(settings.track_volume());
+    handle
+        .typemap()
+        .write()
+        .await
+        .insert::<crate::shared::TrackMetadataKey>(metadata);
+    Ok((track, handle))
+}
+
 pub async fn play_yt_url<U>(
     call: std::sync::Arc<tokio::sync::Mutex<songbird::Call>>,
     url: U,
@@ -428,32 +631,24 @@ pub async fn play_yt_url<U>(
 where
     U: AsRef<str> + Send + Sync + Clone + 'static,
 {
-    match songbird::input::restartable::Restartable::ytdl(url, true).await {
-        Ok(y) => {
-            use songbird::input::Input;
+    let guild_id = msg.guild_id.unwrap();
+    let settings = {
+        let lock = ctx.data.read().await;
+        let db = lock.get::<DatabaseKey>().unwrap();
+        crate::shared::audio::audio_settings(db, guild_id.0).await?
+    };
+    match create_track(url, msg.author.id, guild_id, &settings).await {
+        Ok((track, handle)) => {
             if call.lock().await.queue().len() >= crate::shared::MAX_QUEUED_ITEM {
                 message_err!("❌There is too many items in the queue!");
             }
-            let song: Input = y.into();
-            let metadata = crate::shared::TrackMetadata {
-                url: song.metadata.source_url.clone(),
-                title: song.metadata.title.clone(),
-                duration: song.metadata.duration,
-                added_by: msg.author.id,
-            };
             if show_addition {
-                if let Some(u) = metadata.url.as_ref() {
+                if let Some(u) = handle.metadata().source_url.as_ref() {
                     reply_message!(ctx, msg, format!("Added {url} to the queue", url = u));
                 } else {
                     reply_message!(ctx, msg, "Added the song to the queue");
                 }
             }
-            let (track, handle) = songbird::tracks::create_player(song);
-            handle
-                .typemap()
-                .write()
-                .await
-                .insert::<crate::shared::TrackMetadataKey>(metadata);
             let mut call_lock = call.lock().await;
             call_lock.enqueue(track);
             Ok(())
@@ -468,6 +663,56 @@ where
     }
 }
 
+/// Add the handlers every call of the bot needs, done after joining.
+/// The call stays in the manager after leaving, so the handlers of the previous join are removed first
+pub async fn register_call_events(
+    ctx: &Context,
+    guild_id: serenity::model::id::GuildId,
+    call: &std::sync::Arc<tokio::sync::Mutex<songbird::Call>>,
+) {
+    call.lock().await.remove_all_global_events();
+    let meh = crate::shared::MusicEventHandler {
+        call: call.clone(),
+        guild_id,
+        typemap: ctx.data.clone(),
+        http: ctx.http.clone(),
+        cache: ctx.cache.clone(),
+        bot_id: ctx.cache.current_user_id().await,
+    };
+    call.lock().await.add_global_event(
+        songbird::events::Event::Track(songbird::events::TrackEvent::End),
+        meh,
+    );
+    let now_playing = crate::shared::now_playing::NowPlayingHandler {
+        http: ctx.http.clone(),
+        typemap: ctx.data.clone(),
+        guild_id,
+    };
+    call.lock().await.add_global_event(
+        songbird::events::Event::Track(songbird::events::TrackEvent::Play),
+        now_playing,
+    );
+    let voice_recorder = crate::shared::recording::VoiceRecorder {
+        recordings: ctx
+            .data
+            .read()
+            .await
+            .get::<crate::shared::recording::RecordingsKey>()
+            .unwrap()
+            .clone(),
+        guild_id,
+    };
+    for event in [
+        songbird::events::CoreEvent::SpeakingStateUpdate,
+        songbird::events::CoreEvent::SpeakingUpdate,
+        songbird::events::CoreEvent::VoicePacket,
+    ] {
+        call.lock()
+            .await
+            .add_global_event(songbird::events::Event::Core(event), voice_recorder.clone());
+    }
+}
+
 /*
    _  ____  _             _ _     _    __
  / / |  _ \| | __ _ _   _| (_)___| |_  \ \
@@ -575,96 +820,6 @@ pub async fn create_playlist_if_not_exist(
     Ok(true)
 }
 
-/*
-  __  ____                        _ _              __
- / / |  _ \ ___  ___ ___  _ __ __| (_)_ __   __ _  \ \
-/ /  | |_) / _ \/ __/ _ \| '__/ _` | | '_ \ / _` |  \ \
-\ \  |  _ <  __/ (_| (_) | | | (_| | | | | | (_| |  / /
- \_\ |_| \_\___|\___\___/|_|  \__,_|_|_| |_|\__, | /_/
-                                            |___/
-
-*/
-
-#[derive(Clone, Debug, Default)]
-pub struct GuildRecording {
-    sound_buffer: std::collections::VecDeque<i32>,
-}
-
-impl GuildRecording {
-    pub fn append_buffer(&mut self, data: &[i32]) {
-        self.sound_buffer.extend(data);
-    }
-
-    pub fn record_buffer(&self) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let mut buffer = Vec::new();
-        {
-            let mut writter = hound::WavWriter::new(
-                std::io::Cursor::new(&mut buffer),
-                hound::WavSpec {
-                    channels: 4,
-                    sample_rate: 44100,
-                    bits_per_sample: 32,
-                    sample_format: hound::SampleFormat::Int,
-                },
-            )?;
-
-            for &sample in &self.sound_buffer {
-                writter.write_sample(sample)?;
-            }
-        }
-        Ok(buffer)
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct Recordings {
-    inner: std::collections::HashMap<serenity::model::id::GuildId, GuildRecording>,
-}
-
-impl Recordings {
-    pub async fn get_guild(
-        &self,
-        guild_id: &serenity::model::id::GuildId,
-    ) -> Option<&GuildRecording> {
-        self.inner.get(guild_id)
-    }
-    pub async fn get_guild_mut(
-        &mut self,
-        guild_id: &serenity::model::id::GuildId,
-    ) -> Option<&mut GuildRecording> {
-        self.inner.get_mut(guild_id)
-    }
-
-    pub async fn get_guild_default(
-        &mut self,
-        guild_id: &serenity::model::id::GuildId,
-    ) -> &mut GuildRecording {
-        self.inner.entry(*guild_id).or_default()
-    }
-}
-
-#[derive(Clone)]
-pub struct VoiceRecorder {
-    pub typemap: std::sync::Arc<tokio::sync::RwLock<serenity::prelude::TypeMap>>,
-    pub guild_id: serenity::model::id::GuildId,
-}
-
-#[serenity::async_trait]
-impl songbird::events::EventHandler for VoiceRecorder {
-    async fn act(
-        &self,
-        ctx: &songbird::events::EventContext<'_>,
-    ) -> Option<songbird::events::Event> {
-        match ctx {
-            songbird::EventContext::VoicePacket(_vc) => {
-                // dbg!(vc);
-                None
-            }
-            _ => None,
-        }
-    }
-}
-
 // --------------------------------------------
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -713,6 +868,9 @@ pub struct Song {
 pub struct NowPlaying {
     pub time_in: std::time::Duration,
     pub song: Song,
+    /// Whether the finished tracks are added back to the queue
+    #[serde(default)]
+    pub queue_loop: bool,
 }
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QueueRequest {
@@ -727,3 +885,33 @@ pub static BASE_URL: once_cell::sync::Lazy<String> = once_cell::sync::Lazy::new(
     dotenv::dotenv().expect("Error with dotenv");
     std::env::var("WH_WEB_SERVER").expect("You need to provide the WH_WEB_SERVER env variable")
 });
+
+/// Whether the `Music` commands can be used in a channel, set per channel with a guild wide fallback
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MusicChannel {
+    pub enabled: bool,
+}
+
+impl Default for MusicChannel {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl wh_config::shared::Config for MusicChannel {
+    const KEY: &'static str = "music.channel";
+}
+wh_config::register_config!(MusicChannel);
+
+/// Whether the `Music` commands can be used in the channel
+pub async fn music_channel_enabled(
+    ctx: &Context,
+    guildid: u64,
+    channelid: u64,
+) -> wh_config::shared::AllResult<bool> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let scopes = wh_config::shared::Scope::chain(guildid, Some(channelid), None);
+    let config = wh_config::shared::resolve_config_or_default::<MusicChannel>(db, &scopes).await?;
+    Ok(config.enabled)
+}
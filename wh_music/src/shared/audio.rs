@@ -0,0 +1,298 @@
+use once_cell::sync::Lazy;
+use songbird::input::{error::Error, Codec, Container, Input, Metadata};
+use std::{collections::HashMap, process::Stdio, time::Duration};
+use wh_config::shared::{AllResult, Scope};
+
+pub const MAX_VOLUME: u16 = 200;
+
+/// The same selection `songbird` uses for its youtube-dl sources
+const YTDL_ARGS: &[&str] = &[
+    "-f",
+    "webm[abr>0]/bestaudio/best",
+    "-R",
+    "infinite",
+    "--no-playlist",
+    "--ignore-config",
+    "--no-warnings",
+];
+
+/// An ffmpeg filter applied to every song of a guild
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterPreset {
+    #[default]
+    None,
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    Speed,
+    Slow,
+    PitchUp,
+    PitchDown,
+}
+
+impl FilterPreset {
+    pub const ALL: [FilterPreset; 8] = [
+        FilterPreset::None,
+        FilterPreset::BassBoost,
+        FilterPreset::Nightcore,
+        FilterPreset::Vaporwave,
+        FilterPreset::Speed,
+        FilterPreset::Slow,
+        FilterPreset::PitchUp,
+        FilterPreset::PitchDown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterPreset::None => "none",
+            FilterPreset::BassBoost => "bassboost",
+            FilterPreset::Nightcore => "nightcore",
+            FilterPreset::Vaporwave => "vaporwave",
+            FilterPreset::Speed => "speed",
+            FilterPreset::Slow => "slow",
+            FilterPreset::PitchUp => "pitchup",
+            FilterPreset::PitchDown => "pitchdown",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|f| f.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The value given to ffmpeg's `-af`.
+    /// The input is resampled to 48kHz first so changing the rate changes the speed and pitch the same way for every song
+    pub fn ffmpeg_filter(self) -> Option<&'static str> {
+        match self {
+            FilterPreset::None => None,
+            FilterPreset::BassBoost => Some("bass=g=10"),
+            FilterPreset::Nightcore => Some("aresample=48000,asetrate=60000,aresample=48000"),
+            FilterPreset::Vaporwave => Some("aresample=48000,asetrate=38400,aresample=48000"),
+            FilterPreset::Speed => Some("atempo=1.25"),
+            FilterPreset::Slow => Some("atempo=0.8"),
+            // Two semitones, the tempo is put back to normal
+            FilterPreset::PitchUp => {
+                Some("aresample=48000,asetrate=53879,aresample=48000,atempo=0.8909")
+            }
+            FilterPreset::PitchDown => {
+                Some("aresample=48000,asetrate=42763,aresample=48000,atempo=1.1225")
+            }
+        }
+    }
+
+    /// How much faster the song goes with the filter
+    pub fn tempo(self) -> f64 {
+        match self {
+            FilterPreset::Nightcore | FilterPreset::Speed => 1.25,
+            FilterPreset::Vaporwave | FilterPreset::Slow => 0.8,
+            FilterPreset::None
+            | FilterPreset::BassBoost
+            | FilterPreset::PitchUp
+            | FilterPreset::PitchDown => 1.0,
+        }
+    }
+
+    /// Where the song is once it played for `played` with the filter.
+    /// songbird counts the position of a track in played time, the users and ffmpeg in song time
+    pub fn song_time(self, played: Duration) -> Duration {
+        played.mul_f64(self.tempo())
+    }
+
+    /// How long the filter plays to reach `song` in the song
+    pub fn played_time(self, song: Duration) -> Duration {
+        song.div_f64(self.tempo())
+    }
+}
+
+impl std::fmt::Display for FilterPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The volume and filter of the songs played in a guild
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AudioSettings {
+    /// In percent, up to [`MAX_VOLUME`]
+    pub volume: u16,
+    #[serde(default)]
+    pub filter: FilterPreset,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            volume: 100,
+            filter: FilterPreset::None,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// The volume as given to songbird
+    pub fn track_volume(&self) -> f32 {
+        self.volume.min(MAX_VOLUME) as f32 / 100.0
+    }
+}
+
+impl wh_config::shared::Config for AudioSettings {
+    const KEY: &'static str = "music.audio";
+}
+wh_config::register_config!(AudioSettings);
+
+/// The filter each guild currently plays with, read by the tracks every time they (re)start
+static ACTIVE_FILTERS: Lazy<parking_lot::Mutex<HashMap<u64, FilterPreset>>> =
+    Lazy::new(Default::default);
+
+pub fn active_filter(guildid: u64) -> FilterPreset {
+    ACTIVE_FILTERS
+        .lock()
+        .get(&guildid)
+        .copied()
+        .unwrap_or_default()
+}
+
+pub fn set_active_filter(guildid: u64, filter: FilterPreset) {
+    ACTIVE_FILTERS.lock().insert(guildid, filter);
+}
+
+/// Read the settings of the guild, the filter becomes the one the guild plays with
+pub async fn audio_settings(database: &sqlx::PgPool, guildid: u64) -> AllResult<AudioSettings> {
+    let scopes = Scope::chain(guildid, None, None);
+    let settings =
+        *wh_config::shared::resolve_config_or_default::<AudioSettings>(database, &scopes).await?;
+    set_active_filter(guildid, settings.filter);
+    Ok(settings)
+}
+
+/// ffmpeg reading `input` from `time` with the filter of the guild, writing the PCM songbird plays on its stdout.
+/// `time` is the played time songbird seeks to
+fn ffmpeg_command(input: &str, time: Option<Duration>, guildid: u64) -> std::process::Command {
+    let filter = active_filter(guildid);
+    let mut ffmpeg = std::process::Command::new("ffmpeg");
+    if let Some(time) = time {
+        ffmpeg
+            .arg("-ss")
+            .arg(format!("{:.3}", filter.song_time(time).as_secs_f64()));
+    }
+    ffmpeg.args(["-i", input]);
+    if let Some(filter) = filter.ffmpeg_filter() {
+        ffmpeg.args(["-af", filter]);
+    }
+    ffmpeg
+        .args([
+            "-f",
+            "s16le",
+            "-ac",
+            "2",
+            "-ar",
+            "48000",
+            "-acodec",
+            "pcm_f32le",
+            "-",
+        ])
+        .stderr(Stdio::null())
+        .stdout(Stdio::piped());
+    ffmpeg
+}
+
+/// Plays a youtube-dl url through ffmpeg with the filter of the guild.
+/// The filter is read on every restart, so seeking to the current position applies a new one
+pub struct FilteredYtdl {
+    pub uri: String,
+    pub guildid: u64,
+}
+
+#[serenity::async_trait]
+impl songbird::input::restartable::Restart for FilteredYtdl {
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input, Error> {
+        let mut youtube_dl = std::process::Command::new("youtube-dl")
+            .args(YTDL_ARGS)
+            .arg(&self.uri)
+            .args(["-o", "-"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = youtube_dl.stdout.take().ok_or(Error::Stdout)?;
+        let ffmpeg = ffmpeg_command("-", time, self.guildid)
+            .stdin(stdout)
+            .spawn()?;
+
+        Ok(Input::new(
+            true,
+            songbird::input::children_to_reader::<f32>(vec![youtube_dl, ffmpeg]),
+            Codec::FloatPcm,
+            Container::Raw,
+            None,
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container), Error> {
+        let output = tokio::process::Command::new("youtube-dl")
+            .arg("-j")
+            .args(YTDL_ARGS)
+            .arg(&self.uri)
+            .args(["-o", "-"])
+            .stdin(Stdio::null())
+            .output()
+            .await?;
+        // With `-o -` the JSON is written to stderr like everything but the video, some versions still use stdout
+        let json = if output.stdout.starts_with(b"{") {
+            &output.stdout
+        } else {
+            &output.stderr
+        };
+        let end = json.iter().position(|&c| c == b'\n').unwrap_or(json.len());
+        let value = serde_json::from_slice(&json[..end]).map_err(|error| Error::Json {
+            error,
+            parsed_text: String::from_utf8_lossy(json).into_owned(),
+        })?;
+        Ok((
+            Some(Metadata::from_ytdl_output(value)),
+            Codec::FloatPcm,
+            Container::Raw,
+        ))
+    }
+}
+
+/// Plays a file or a direct url with ffmpeg alone, with the filter of the guild
+pub struct FilteredFile {
+    /// What ffmpeg reads
+    pub input: String,
+    /// The url of the song, kept as its source url
+    pub url: String,
+    pub guildid: u64,
+}
+
+#[serenity::async_trait]
+impl songbird::input::restartable::Restart for FilteredFile {
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input, Error> {
+        let ffmpeg = ffmpeg_command(&self.input, time, self.guildid)
+            .stdin(Stdio::null())
+            .spawn()?;
+
+        Ok(Input::new(
+            true,
+            songbird::input::children_to_reader::<f32>(vec![ffmpeg]),
+            Codec::FloatPcm,
+            Container::Raw,
+            None,
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container), Error> {
+        let value = crate::shared::library::probe(&self.input).await?;
+        let mut metadata = Metadata::from_ffprobe_json(&value);
+        metadata.title = metadata.track.clone().or_else(|| {
+            let name = self.input.rsplit(['/', '\\']).next().unwrap_or(&self.input);
+            let name = name.split('?').next().unwrap_or(name);
+            Some(name.to_string())
+        });
+        metadata.source_url = Some(self.url.clone());
+        Ok((Some(metadata), Codec::FloatPcm, Container::Raw))
+    }
+}
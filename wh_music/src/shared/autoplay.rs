@@ -0,0 +1,160 @@
+use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
+use serenity::framework::standard::CommandResult;
+use std::collections::{HashMap, HashSet, VecDeque};
+use wh_database::shared::Id;
+
+/// How many of the last played songs of a guild autoplay won't pick again
+pub const RECENT_TRACKS: usize = 50;
+/// How many songs of the youtube mix are considered
+const RELATED_COUNT: usize = 25;
+
+/// Where the next song comes from when the queue is empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoplayMode {
+    #[default]
+    Off,
+    /// A song related to the last one played
+    Related,
+    /// A random song of the saved playlist with this name
+    Playlist(String),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Autoplay {
+    pub mode: AutoplayMode,
+}
+
+impl wh_config::shared::Config for Autoplay {
+    const KEY: &'static str = "music.autoplay";
+}
+wh_config::register_config!(Autoplay);
+
+/// The last songs played in each guild, the most recent last
+static RECENT: Lazy<parking_lot::Mutex<HashMap<u64, VecDeque<String>>>> =
+    Lazy::new(Default::default);
+
+/// The guilds where the queue was emptied on purpose, so nothing is picked after it
+static HALTED: Lazy<parking_lot::Mutex<HashSet<u64>>> = Lazy::new(Default::default);
+
+fn video_id(url: &str) -> Option<String> {
+    url::Url::parse(url.trim()).ok().and_then(|u| {
+        u.query_pairs()
+            .find(|(k, _)| k == "v")
+            .map(|(_, v)| v.into_owned())
+    })
+}
+
+/// What identifies a song, the video id for youtube as the same video has many urls
+fn track_key(url: &str) -> String {
+    video_id(url).unwrap_or_else(|| url.trim().to_string())
+}
+
+pub fn remember_played(guildid: u64, url: &str) {
+    let key = track_key(url);
+    let mut recent = RECENT.lock();
+    let recent = recent.entry(guildid).or_default();
+    recent.retain(|k| *k != key);
+    recent.push_back(key);
+    while recent.len() > RECENT_TRACKS {
+        recent.pop_front();
+    }
+}
+
+/// How long ago the song was played, `None` if it wasn't played recently
+fn played_ago(guildid: u64, url: &str) -> Option<usize> {
+    let key = track_key(url);
+    RECENT
+        .lock()
+        .get(&guildid)
+        .and_then(|recent| recent.iter().rev().position(|k| *k == key))
+}
+
+/// Don't pick anything when the queue becomes empty, used when it is cleared
+pub fn halt(guildid: u64) {
+    HALTED.lock().insert(guildid);
+}
+
+/// Whether the guild was halted, it can autoplay again afterward
+pub fn take_halt(guildid: u64) -> bool {
+    HALTED.lock().remove(&guildid)
+}
+
+/// The songs of the youtube mix of a video, which are the ones related to it
+async fn related_urls(url: &str) -> CommandResult<Vec<String>> {
+    let id = match video_id(url) {
+        Some(id) => id,
+        None => return Ok(Vec::new()),
+    };
+    let output = tokio::process::Command::new("youtube-dl")
+        .args(["--flat-playlist", "--get-id", "--no-warnings"])
+        .args(["--playlist-end", &RELATED_COUNT.to_string()])
+        .arg(format!(
+            "https://www.youtube.com/watch?v={id}&list=RD{id}",
+            id = id
+        ))
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != id)
+        .map(|v| format!("https://www.youtube.com/watch?v={}", v))
+        .collect())
+}
+
+/// The next song to play in the guild, `last` is the url of the song which just ended.
+/// A song played recently is only picked when there is no other choice in the playlist
+pub async fn next_url(
+    database: &sqlx::PgPool,
+    guildid: u64,
+    last: Option<&str>,
+) -> CommandResult<Option<String>> {
+    let scopes = wh_config::shared::Scope::chain(guildid, None, None);
+    let config =
+        wh_config::shared::resolve_config_or_default::<Autoplay>(database, &scopes).await?;
+    match &config.mode {
+        AutoplayMode::Off => Ok(None),
+        AutoplayMode::Related => {
+            let last = match last {
+                Some(last) => last,
+                None => return Ok(None),
+            };
+            Ok(related_urls(last)
+                .await?
+                .into_iter()
+                .find(|url| played_ago(guildid, url).is_none()))
+        }
+        AutoplayMode::Playlist(name) => {
+            let playlist = query!(
+                "SELECT items FROM user_playlist WHERE guildid = $1::int8 AND name = UPPER($2::varchar(32))",
+                Id(guildid) as _,
+                name
+            )
+            .fetch_optional(database)
+            .await?;
+            let items = match playlist {
+                Some(playlist) => playlist.items,
+                None => return Ok(None),
+            };
+            let items = items
+                .iter()
+                .map(|i| i.trim())
+                .filter(|i| !i.is_empty())
+                .collect::<Vec<_>>();
+            let fresh = items
+                .iter()
+                .filter(|url| played_ago(guildid, url).is_none())
+                .collect::<Vec<_>>();
+            let picked = match fresh.choose(&mut rand::thread_rng()) {
+                Some(url) => Some(**url),
+                None => items
+                    .iter()
+                    .copied()
+                    .max_by_key(|url| played_ago(guildid, url)),
+            };
+            Ok(picked.map(ToString::to_string))
+        }
+    }
+}
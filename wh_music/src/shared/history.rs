@@ -0,0 +1,121 @@
+use serenity::model::id::UserId;
+use std::time::Duration;
+use wh_database::shared::Id;
+
+/// How many songs a page of `history` shows
+pub const HISTORY_PAGE_SIZE: i64 = 10;
+/// The songs kept for each guild, the oldest ones are removed when a song is played
+pub const MAX_HISTORY_LEN: i64 = 1000;
+
+/// A song played in a guild
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+    pub added_by: UserId,
+    /// Unix timestamp in seconds
+    pub played_at: i64,
+}
+
+#[derive(Debug, Clone)]
+struct HistoryEntryRaw {
+    url: String,
+    title: Option<String>,
+    duration_ms: Option<i64>,
+    added_by: i64,
+    played_at: i64,
+}
+
+impl HistoryEntryRaw {
+    fn into_processed(self) -> HistoryEntry {
+        HistoryEntry {
+            url: self.url,
+            title: self.title,
+            duration: self
+                .duration_ms
+                .map(|ms| Duration::from_millis(ms.max(0) as u64)),
+            added_by: UserId(Id::from(self.added_by).0),
+            played_at: self.played_at,
+        }
+    }
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "**{}**", self.title.as_deref().unwrap_or(&self.url))?;
+        if let Some(duration) = self.duration {
+            write!(
+                f,
+                " ({})",
+                crate::shared::queue_control::format_position(duration)
+            )?;
+        }
+        write!(f, " added by <@{}> <t:{}:R>", self.added_by, self.played_at)
+    }
+}
+
+/// Add a song that was played in the guild, only the last [`MAX_HISTORY_LEN`] songs are kept
+pub async fn record_played(
+    database: &sqlx::PgPool,
+    guildid: u64,
+    metadata: &crate::shared::TrackMetadata,
+) -> Result<(), sqlx::Error> {
+    let url = match &metadata.url {
+        Some(url) => url,
+        None => return Ok(()),
+    };
+    query!(
+        "INSERT INTO music_history (guildid, url, title, duration_ms, added_by) VALUES ($1::int8, $2::text, $3::text, $4::int8, $5::int8)",
+        Id(guildid) as _,
+        url,
+        metadata.title.as_deref(),
+        metadata.duration.map(|d| d.as_millis() as i64),
+        Id(metadata.added_by.0) as _,
+    )
+    .execute(database)
+    .await?;
+    query!(
+        "DELETE FROM music_history WHERE uid IN (SELECT uid FROM music_history WHERE guildid = $1::int8 ORDER BY played_at DESC, uid DESC OFFSET $2::int8)",
+        Id(guildid) as _,
+        MAX_HISTORY_LEN,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// How many songs were played in the guild
+pub async fn history_len(database: &sqlx::PgPool, guildid: u64) -> Result<i64, sqlx::Error> {
+    Ok(query!(
+        r#"SELECT COUNT(*) AS "count!" FROM music_history WHERE guildid = $1::int8"#,
+        Id(guildid) as _,
+    )
+    .fetch_one(database)
+    .await?
+    .count)
+}
+
+/// The songs played in the guild, the most recent first
+pub async fn get_history(
+    database: &sqlx::PgPool,
+    guildid: u64,
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<HistoryEntry>, sqlx::Error> {
+    let entries = query_as!(
+        HistoryEntryRaw,
+        r#"SELECT url, title, duration_ms, added_by, EXTRACT(EPOCH FROM played_at)::int8 AS "played_at!"
+        FROM music_history WHERE guildid = $1::int8
+        ORDER BY played_at DESC, uid DESC OFFSET $2::int8 LIMIT $3::int8"#,
+        Id(guildid) as _,
+        offset,
+        limit,
+    )
+    .fetch_all(database)
+    .await?;
+    Ok(entries
+        .into_iter()
+        .map(HistoryEntryRaw::into_processed)
+        .collect())
+}
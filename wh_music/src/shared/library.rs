@@ -0,0 +1,357 @@
+use once_cell::sync::Lazy;
+use serenity::framework::standard::CommandResult;
+use serenity::futures::stream::{self, StreamExt};
+use serenity::model::channel::Attachment;
+use std::path::{Path, PathBuf};
+use std::{process::Stdio, sync::Arc, time::Duration};
+
+/// The scheme of the urls of the songs of the library, followed by their path in it
+pub const LIBRARY_SCHEME: &str = "library";
+/// The files ffmpeg is asked to play, by extension
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "ogg", "opus", "flac", "wav", "m4a", "aac", "webm", "mka",
+];
+/// What is encoded in the paths of the `library:` urls, so they are a single argument
+const PATH_ENCODE: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+/// How many files are read at the same time when scanning the library
+const SCAN_CONCURRENCY: usize = 8;
+
+/// The directory of the local songs, set with `WH_MUSIC_LIBRARY`
+pub static LIBRARY_DIR: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    dotenv::dotenv().ok();
+    std::env::var_os("WH_MUSIC_LIBRARY").and_then(|dir| std::fs::canonicalize(dir).ok())
+});
+
+/// The songs found the last time the library was scanned
+static LIBRARY_INDEX: Lazy<tokio::sync::Mutex<Option<Arc<Vec<LibraryTrack>>>>> =
+    Lazy::new(Default::default);
+
+pub fn has_audio_extension(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|ext| ext.eq_ignore_ascii_case(e))
+        })
+}
+
+pub fn is_audio_attachment(attachment: &Attachment) -> bool {
+    attachment
+        .content_type
+        .as_deref()
+        .is_some_and(|c| c.starts_with("audio/"))
+        || has_audio_extension(&attachment.filename)
+}
+
+/// An http url to an audio file, which ffmpeg can play without youtube-dl
+pub fn is_direct_audio_url(url: &url::Url) -> bool {
+    matches!(url.scheme(), "http" | "https") && has_audio_extension(url.path())
+}
+
+/// The file of a `library:` url, only if it is inside the library
+pub fn library_path(url: &url::Url) -> Option<PathBuf> {
+    library_path_in(LIBRARY_DIR.as_ref()?, url)
+}
+
+/// The file of a `library:` url, only if it is inside `dir` which must be canonical
+fn library_path_in(dir: &Path, url: &url::Url) -> Option<PathBuf> {
+    if url.scheme() != LIBRARY_SCHEME {
+        return None;
+    }
+    let relative = percent_encoding::percent_decode_str(url.path())
+        .decode_utf8()
+        .ok()?;
+    let path = std::fs::canonicalize(dir.join(relative.trim_start_matches('/'))).ok()?;
+    if path.starts_with(dir) && path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// What ffmpeg reads to play the url, `None` if it needs youtube-dl
+pub fn ffmpeg_input(url: &str) -> Result<Option<String>, songbird::input::error::Error> {
+    let url = match url::Url::parse(url.trim()) {
+        Ok(url) => url,
+        Err(_) => return Ok(None),
+    };
+    if url.scheme() == LIBRARY_SCHEME {
+        match library_path(&url) {
+            Some(path) => Ok(Some(path.to_string_lossy().into_owned())),
+            None => Err(songbird::input::error::Error::Metadata),
+        }
+    } else if is_direct_audio_url(&url) {
+        Ok(Some(url.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The JSON ffprobe gives about the format and the streams of the input
+pub async fn probe(input: &str) -> Result<serde_json::Value, songbird::input::error::Error> {
+    let output = tokio::process::Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-of",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        .args(["-i", input])
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+    serde_json::from_slice(&output.stdout).map_err(|error| songbird::input::error::Error::Json {
+        error,
+        parsed_text: String::from_utf8_lossy(&output.stdout).into_owned(),
+    })
+}
+
+/// A song of the library with its tags
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryTrack {
+    /// From the library directory
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl LibraryTrack {
+    /// The url `play` and the queue use for the song
+    pub fn url(&self) -> String {
+        let path = self
+            .path
+            .components()
+            .map(|c| {
+                percent_encoding::utf8_percent_encode(&c.as_os_str().to_string_lossy(), PATH_ENCODE)
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("{}:{}", LIBRARY_SCHEME, path)
+    }
+
+    pub fn display_title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            self.path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Whether every word of the query is in the tags or the file name
+    fn matches(&self, words: &[String]) -> bool {
+        let haystack = [
+            self.title.as_deref(),
+            self.artist.as_deref(),
+            self.album.as_deref(),
+            self.path.to_str(),
+        ]
+        .iter()
+        .flatten()
+        .map(|s| s.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+        words.iter().all(|word| haystack.contains(word.as_str()))
+    }
+}
+
+/// A tag of the format, whose case depends on the file type
+fn tag(value: &serde_json::Value, name: &str) -> Option<String> {
+    value
+        .pointer("/format/tags")?
+        .as_object()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .and_then(|(_, v)| v.as_str())
+        .map(ToString::to_string)
+}
+
+async fn read_track(dir: &Path, path: PathBuf) -> Option<LibraryTrack> {
+    let value = match probe(&path.to_string_lossy()).await {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Couldn't read the tags of {}: {:?}", path.display(), e);
+            return None;
+        }
+    };
+    Some(LibraryTrack {
+        path: path.strip_prefix(dir).ok()?.to_path_buf(),
+        title: tag(&value, "title"),
+        artist: tag(&value, "artist"),
+        album: tag(&value, "album"),
+        duration: value
+            .pointer("/format/duration")
+            .and_then(|d| d.as_str())
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| d.is_finite() && *d >= 0.0)
+            .map(Duration::from_secs_f64),
+    })
+}
+
+/// The audio files of the library.
+/// The symlinks are skipped, they could lead out of the library or loop forever
+fn library_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in std::fs::read_dir(current)? {
+            let path = entry?.path();
+            let file_type = std::fs::symlink_metadata(&path)?.file_type();
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() && has_audio_extension(&path.to_string_lossy()) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Read the tags of every audio file of the library
+async fn scan_library(dir: &Path) -> std::io::Result<Vec<LibraryTrack>> {
+    let files = {
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || library_files(&dir))
+            .await
+            .map_err(std::io::Error::other)??
+    };
+    let mut tracks = stream::iter(files)
+        .map(|path| read_track(dir, path))
+        .buffer_unordered(SCAN_CONCURRENCY)
+        .filter_map(|track| async move { track })
+        .collect::<Vec<_>>()
+        .await;
+    tracks.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(tracks)
+}
+
+/// The songs of the library, it is scanned the first time or when `rescan` is set
+pub async fn library_index(rescan: bool) -> CommandResult<Arc<Vec<LibraryTrack>>> {
+    let dir = match LIBRARY_DIR.as_ref() {
+        Some(dir) => dir,
+        None => message_err!(fluent!(MUSIC_library_not_configured)),
+    };
+    let mut index = LIBRARY_INDEX.lock().await;
+    match &*index {
+        Some(tracks) if !rescan => Ok(tracks.clone()),
+        _ => {
+            let tracks = Arc::new(scan_library(dir).await?);
+            *index = Some(tracks.clone());
+            Ok(tracks)
+        }
+    }
+}
+
+/// Searches the tags of the songs of the library
+pub struct LibrarySearch;
+
+#[serenity::async_trait]
+impl crate::shared::search::SearchBackend for LibrarySearch {
+    async fn search(
+        &self,
+        query: &str,
+        count: usize,
+    ) -> CommandResult<Vec<crate::shared::search::SearchResult>> {
+        let words = query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        Ok(library_index(false)
+            .await?
+            .iter()
+            .filter(|track| track.matches(&words))
+            .take(count)
+            .map(|track| crate::shared::search::SearchResult {
+                url: track.url(),
+                title: track.display_title(),
+                channel: track.artist.clone(),
+                duration: track.duration,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A library with a song in it and a file next to it, removed on drop
+    struct TestLibrary {
+        root: PathBuf,
+        dir: PathBuf,
+    }
+
+    impl TestLibrary {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("wh_music_{}_{}", name, std::process::id()));
+            let dir = root.join("library");
+            std::fs::create_dir_all(dir.join("Some Artist")).unwrap();
+            std::fs::write(dir.join("Some Artist").join("Ünicode song #1.mp3"), b"").unwrap();
+            std::fs::write(root.join("secret.mp3"), b"").unwrap();
+            let dir = std::fs::canonicalize(dir).unwrap();
+            Self { root, dir }
+        }
+
+        fn path(&self, url: &str) -> Option<PathBuf> {
+            library_path_in(&self.dir, &url::Url::parse(url).unwrap())
+        }
+    }
+
+    impl Drop for TestLibrary {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn paths_outside_the_library_are_rejected() {
+        let library = TestLibrary::new("traversal");
+        assert_eq!(library.path("library:../../etc/passwd"), None);
+        assert_eq!(library.path("library:../secret.mp3"), None);
+        assert_eq!(library.path("library:%2E%2E/secret.mp3"), None);
+        assert_eq!(library.path("library:Some%20Artist/../../secret.mp3"), None);
+        assert_eq!(library.path("library:/etc/passwd"), None);
+        assert_eq!(library.path("library:Some%20Artist"), None);
+        assert_eq!(library.path("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn track_urls_lead_back_to_the_file() {
+        let library = TestLibrary::new("round_trip");
+        let track = LibraryTrack {
+            path: PathBuf::from("Some Artist").join("Ünicode song #1.mp3"),
+            title: None,
+            artist: None,
+            album: None,
+            duration: None,
+        };
+        let url = track.url();
+        assert_eq!(url, "library:Some%20Artist/%C3%9Cnicode%20song%20%231.mp3");
+        assert_eq!(library.path(&url), Some(library.dir.join(&track.path)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_scanned() {
+        let library = TestLibrary::new("symlinks");
+        std::os::unix::fs::symlink(library.root.join("secret.mp3"), library.dir.join("a.mp3"))
+            .unwrap();
+        std::os::unix::fs::symlink(&library.root, library.dir.join("outside")).unwrap();
+        assert_eq!(
+            library_files(&library.dir).unwrap(),
+            vec![library.dir.join("Some Artist").join("Ünicode song #1.mp3")]
+        );
+    }
+}
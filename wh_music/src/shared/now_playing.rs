@@ -0,0 +1,391 @@
+use once_cell::sync::Lazy;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::http::Http;
+use serenity::model::channel::ReactionType;
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+use serenity::model::interactions::message_component::{ButtonStyle, MessageComponentInteraction};
+use serenity::prelude::TypeMap;
+use std::{collections::HashMap, sync::Arc};
+
+pub const PAUSE_BUTTON: &str = "music_now_playing_pause";
+pub const SKIP_BUTTON: &str = "music_now_playing_skip";
+
+/// How the song being played is shown
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnounceStyle {
+    /// The image of `queue`, sent again for every song
+    #[default]
+    Image,
+    /// An embed, edited in place for every song
+    Embed,
+}
+
+impl AnnounceStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            AnnounceStyle::Image => "image",
+            AnnounceStyle::Embed => "embed",
+        }
+    }
+}
+
+/// Where and how a guild announces the songs when they start, nothing is announced without a channel
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NowPlayingAnnounce {
+    pub channel: Option<u64>,
+    #[serde(default)]
+    pub style: AnnounceStyle,
+    /// Whether the message has buttons to pause and skip
+    #[serde(default)]
+    pub buttons: bool,
+}
+
+impl wh_config::shared::Config for NowPlayingAnnounce {
+    const KEY: &'static str = "music.now_playing";
+}
+wh_config::register_config!(NowPlayingAnnounce);
+
+/// The message announcing the current song of a guild
+#[derive(Debug, Clone, Copy)]
+struct Announcement {
+    channel: ChannelId,
+    message: MessageId,
+    track: u128,
+}
+
+static ANNOUNCEMENTS: Lazy<parking_lot::Mutex<HashMap<u64, Announcement>>> =
+    Lazy::new(Default::default);
+
+/// The titles are cut after this many characters in the images
+const MAX_TITLE_LEN: usize = 60;
+
+/// What is shown about a track in the images of `queue`
+pub async fn song(
+    track: &songbird::tracks::TrackHandle,
+) -> songbird::tracks::TrackResult<crate::shared::Song> {
+    let info = track.get_info().await?;
+    let typemap = track.typemap().read().await;
+    let metadata = typemap.get::<crate::shared::TrackMetadataKey>().unwrap();
+
+    Ok(crate::shared::Song {
+        duration: metadata.duration.unwrap_or(std::time::Duration::ZERO),
+        title: {
+            let mut title = metadata.title.clone().unwrap_or_else(|| {
+                metadata
+                    .url
+                    .clone()
+                    .unwrap_or_else(|| String::from("Unknown"))
+            });
+            if title.chars().count() > MAX_TITLE_LEN {
+                title = title.chars().take(MAX_TITLE_LEN - 3).collect::<String>() + "...";
+            }
+            title
+        },
+        added_by: metadata.added_by.0,
+        loop_num: match info.loops {
+            songbird::tracks::LoopState::Infinite => crate::shared::LoopState::Infinite,
+            songbird::tracks::LoopState::Finite(1 | 0) => crate::shared::LoopState::None,
+            songbird::tracks::LoopState::Finite(n) => {
+                crate::shared::LoopState::Finite(n.min(100) as u8)
+            }
+        },
+    })
+}
+
+/// What is shown about the track, the same as the first image of `queue`
+pub async fn now_playing(
+    guildid: u64,
+    track: &songbird::tracks::TrackHandle,
+) -> songbird::tracks::TrackResult<crate::shared::NowPlaying> {
+    let position = track.get_info().await?.position;
+    Ok(crate::shared::NowPlaying {
+        time_in: crate::shared::audio::active_filter(guildid).song_time(position),
+        song: song(track).await?,
+        queue_loop: crate::shared::queue_control::is_queue_looping(guildid),
+    })
+}
+
+/// The image of the song rendered by the webserver
+pub async fn render_now_playing(now_playing: &crate::shared::NowPlaying) -> CommandResult<Vec<u8>> {
+    let request = reqwest::Client::new()
+        .post(format!(
+            "{base}/api/queue/now_playing",
+            base = *crate::shared::BASE_URL
+        ))
+        .json(now_playing)
+        .send()
+        .await?;
+    Ok(request.bytes().await?.to_vec())
+}
+
+fn fill_embed<'a>(
+    embed: &'a mut CreateEmbed,
+    now_playing: &crate::shared::NowPlaying,
+    url: Option<&str>,
+) -> &'a mut CreateEmbed {
+    embed.title(fluent!(MUSIC_now_playing_title));
+    match url {
+        Some(url) => embed.description(format!("[{}]({})", now_playing.song.title, url)),
+        None => embed.description(&now_playing.song.title),
+    };
+    embed
+        .field(
+            fluent!(MUSIC_now_playing_duration),
+            crate::shared::queue_control::format_position(now_playing.song.duration),
+            true,
+        )
+        .field(
+            fluent!(MUSIC_now_playing_added_by),
+            format!("<@{}>", now_playing.song.added_by),
+            true,
+        );
+    match now_playing.song.loop_num {
+        crate::shared::LoopState::None => (),
+        crate::shared::LoopState::Infinite => {
+            embed.field(fluent!(MUSIC_now_playing_loop), "∞", true);
+        }
+        crate::shared::LoopState::Finite(n) => {
+            embed.field(fluent!(MUSIC_now_playing_loop), n, true);
+        }
+    }
+    embed
+}
+
+/// The buttons of the message, none if they are disabled
+fn fill_components(components: &mut CreateComponents, buttons: bool) -> &mut CreateComponents {
+    if buttons {
+        components.create_action_row(|row| {
+            row.create_button(|b| {
+                b.style(ButtonStyle::Secondary)
+                    .emoji(ReactionType::Unicode("⏯".into()))
+                    .custom_id(PAUSE_BUTTON)
+            })
+            .create_button(|b| {
+                b.style(ButtonStyle::Secondary)
+                    .emoji(ReactionType::Unicode("⏭".into()))
+                    .custom_id(SKIP_BUTTON)
+            })
+        });
+    }
+    components
+}
+
+/// Announce the track that just started in the channel of the guild.
+/// Nothing is sent again when the track is resumed
+pub async fn announce(
+    http: &Http,
+    typemap: &tokio::sync::RwLock<TypeMap>,
+    guild_id: GuildId,
+    track: &songbird::tracks::TrackHandle,
+) -> CommandResult {
+    let config = {
+        let lock = typemap.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let scopes = wh_config::shared::Scope::chain(guild_id.0, None, None);
+        *wh_config::shared::resolve_config_or_default::<NowPlayingAnnounce>(db, &scopes).await?
+    };
+    let channel = match config.channel {
+        Some(channel) => ChannelId(channel),
+        None => {
+            remove_announcement(http, guild_id).await;
+            return Ok(());
+        }
+    };
+    let track_id = track.uuid().as_u128();
+    let previous = ANNOUNCEMENTS.lock().get(&guild_id.0).copied();
+    if previous.is_some_and(|a| a.track == track_id) {
+        return Ok(());
+    }
+
+    let now_playing = now_playing(guild_id.0, track).await?;
+    let message = match config.style {
+        AnnounceStyle::Embed => {
+            let url = track.metadata().source_url.clone();
+            let edited = match previous.filter(|a| a.channel == channel) {
+                Some(previous) => channel
+                    .edit_message(http, previous.message, |m| {
+                        m.embed(|e| fill_embed(e, &now_playing, url.as_deref()))
+                            .components(|c| fill_components(c, config.buttons))
+                    })
+                    .await
+                    .ok(),
+                None => {
+                    remove_announcement(http, guild_id).await;
+                    None
+                }
+            };
+            match edited {
+                Some(message) => message,
+                None => {
+                    channel
+                        .send_message(http, |m| {
+                            m.embed(|e| fill_embed(e, &now_playing, url.as_deref()))
+                                .components(|c| fill_components(c, config.buttons))
+                        })
+                        .await?
+                }
+            }
+        }
+        AnnounceStyle::Image => {
+            // Attachments can't be replaced by editing the message
+            let image = render_now_playing(&now_playing).await?;
+            remove_announcement(http, guild_id).await;
+            channel
+                .send_files(http, [(&image[..], "now_playing.png")], |m| {
+                    m.components(|c| fill_components(c, config.buttons))
+                })
+                .await?
+        }
+    };
+    ANNOUNCEMENTS.lock().insert(
+        guild_id.0,
+        Announcement {
+            channel,
+            message: message.id,
+            track: track_id,
+        },
+    );
+    Ok(())
+}
+
+/// Delete the announcement of the guild, once nothing is played anymore
+pub async fn remove_announcement(http: &Http, guild_id: GuildId) {
+    let announcement = ANNOUNCEMENTS.lock().remove(&guild_id.0);
+    if let Some(announcement) = announcement {
+        if let Err(e) = announcement
+            .channel
+            .delete_message(http, announcement.message)
+            .await
+        {
+            error!("Couldn't delete the now playing message: {}", e);
+        }
+    }
+}
+
+/// Announces the tracks when they start playing
+pub struct NowPlayingHandler {
+    pub(crate) http: Arc<Http>,
+    pub(crate) typemap: Arc<tokio::sync::RwLock<TypeMap>>,
+    pub(crate) guild_id: GuildId,
+}
+
+#[serenity::async_trait]
+impl songbird::events::EventHandler for NowPlayingHandler {
+    async fn act(
+        &self,
+        ctx: &songbird::events::EventContext<'_>,
+    ) -> Option<songbird::events::Event> {
+        if let songbird::EventContext::Track(tracks) = ctx {
+            if let Some((_, handle)) = tracks.last() {
+                if let Err(e) = announce(&self.http, &self.typemap, self.guild_id, handle).await {
+                    error!("Couldn't announce the song being played: {}", e);
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn is_button(custom_id: &str) -> bool {
+    custom_id == PAUSE_BUTTON || custom_id == SKIP_BUTTON
+}
+
+/// Pause or skip the song from the buttons of the announcement, like the commands would.
+/// Returns what to answer to the user, if anything
+pub async fn press_button(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+) -> CommandResult<Option<String>> {
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(None),
+    };
+    let guild = match guild_id.to_guild_cached(&ctx.cache).await {
+        Some(guild) => guild,
+        None => return Ok(None),
+    };
+    let call = match songbird::get(ctx).await.unwrap().get(guild_id) {
+        Some(call) => call,
+        None => message_err!(fluent!(MUSIC_voice_not_connected)),
+    };
+    let channel_id = guild
+        .voice_states
+        .get(&interaction.user.id)
+        .and_then(|x| x.channel_id);
+    if channel_id.is_none()
+        || channel_id.map(|c| c.0) != call.lock().await.current_channel().map(|c| c.0)
+    {
+        message_err!(fluent!(MUSIC_not_same_channel));
+    }
+    // The buttons do what the commands do, so the same rules apply
+    let command = match interaction.data.custom_id.as_str() {
+        PAUSE_BUTTON => "pause",
+        SKIP_BUTTON => "skip",
+        _ => return Ok(None),
+    };
+    if !crate::shared::music_channel_enabled(ctx, guild_id.0, interaction.channel_id.0).await? {
+        message_err!(fluent!(MUSIC_channel_disabled_here));
+    }
+    if let Some(permission) = wh_permission::shared::command_binding::missing_requirement(
+        ctx,
+        guild_id.0,
+        interaction.user.id.0,
+        interaction.channel_id.0,
+        command,
+    )
+    .await?
+    {
+        message_err!(format!(
+            fluent!(MUSIC_button_missing_permission),
+            permission
+        ));
+    }
+
+    match interaction.data.custom_id.as_str() {
+        PAUSE_BUTTON => {
+            let track = match call.lock().await.queue().current() {
+                Some(track) => track,
+                None => message_err!(fluent!(MUSIC_empty_queue)),
+            };
+            let playing = track.get_info().await?.playing == songbird::tracks::PlayMode::Play;
+            let lock = call.lock().await;
+            if playing {
+                if let Err(e) = lock.queue().pause() {
+                    both_err!(
+                        fluent!(MUSIC_err_pausing),
+                        format!(fluent!(MUSIC_LOG_err_pausing), e)
+                    );
+                }
+            } else if let Err(e) = lock.queue().resume() {
+                both_err!(
+                    fluent!(MUSIC_error_resuming),
+                    format!(fluent!(MUSIC_LOG_err_resuming), e)
+                );
+            }
+            Ok(None)
+        }
+        SKIP_BUTTON => {
+            match crate::shared::vote_skip::request_skip(
+                ctx,
+                &guild,
+                interaction.user.id,
+                interaction.channel_id,
+                &call,
+            )
+            .await?
+            {
+                crate::shared::vote_skip::SkipOutcome::Skipped => Ok(None),
+                crate::shared::vote_skip::SkipOutcome::Voted { votes, required } => {
+                    Ok(Some(format!(fluent!(MUSIC_skip_voted), votes, required)))
+                }
+                crate::shared::vote_skip::SkipOutcome::AlreadyVoted { votes, required } => {
+                    message_err!(format!(fluent!(MUSIC_skip_already_voted), votes, required))
+                }
+            }
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,179 @@
+use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
+use std::{collections::HashSet, time::Duration};
+
+/// The guilds where finished tracks are added back at the end of the queue
+static QUEUE_LOOP: Lazy<parking_lot::Mutex<HashSet<u64>>> = Lazy::new(Default::default);
+
+pub fn is_queue_looping(guildid: u64) -> bool {
+    QUEUE_LOOP.lock().contains(&guildid)
+}
+
+pub fn set_queue_loop(guildid: u64, enabled: bool) {
+    if enabled {
+        QUEUE_LOOP.lock().insert(guildid);
+    } else {
+        QUEUE_LOOP.lock().remove(&guildid);
+    }
+}
+
+/// Shuffle every track after the one playing, returns how many were shuffled
+pub fn shuffle_queue(queue: &songbird::tracks::TrackQueue) -> usize {
+    queue.modify_queue(|queue| {
+        let upcoming = queue.make_contiguous().get_mut(1..).unwrap_or_default();
+        upcoming.shuffle(&mut rand::thread_rng());
+        upcoming.len()
+    })
+}
+
+/// A position in a track like `83`, `1:23` or `1:02:03`, or a duration like `90s`, `1m30s` or `1h`
+pub fn parse_timestamp(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    let mut secs: u64 = 0;
+    if input.contains(':') {
+        let parts = input.split(':').collect::<Vec<_>>();
+        if parts.len() > 3 {
+            return None;
+        }
+        for part in parts {
+            secs = secs.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+        }
+    } else if input.bytes().all(|c| c.is_ascii_digit()) {
+        secs = input.parse().ok()?;
+    } else {
+        let mut number = String::new();
+        for c in input.chars() {
+            match c {
+                '0'..='9' => number.push(c),
+                'h' | 'm' | 's' if !number.is_empty() => {
+                    let unit = match c {
+                        'h' => 3600,
+                        'm' => 60,
+                        _ => 1,
+                    };
+                    let value: u64 = number.parse().ok()?;
+                    secs = secs.checked_add(value.checked_mul(unit)?)?;
+                    number.clear();
+                }
+                _ => return None,
+            }
+        }
+        if !number.is_empty() {
+            return None;
+        }
+    }
+    Some(Duration::from_secs(secs))
+}
+
+/// Where to seek to move the current position by `offset`, kept inside the track
+pub fn offset_position(
+    position: Duration,
+    offset: Duration,
+    forward: bool,
+    duration: Option<Duration>,
+) -> Duration {
+    if forward {
+        let target = position.saturating_add(offset);
+        match duration {
+            Some(duration) => target.min(duration),
+            None => target,
+        }
+    } else {
+        position.saturating_sub(offset)
+    }
+}
+
+/// Display a position like the queue image does
+pub fn format_position(position: Duration) -> String {
+    let secs = position.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Seek the current track of the call to the position `target` gives from its position and duration.
+/// Returns the new position, the positions are in song time whatever the filter of the guild
+pub async fn seek_current(
+    guildid: u64,
+    call: &std::sync::Arc<tokio::sync::Mutex<songbird::Call>>,
+    target: impl FnOnce(Duration, Option<Duration>) -> Duration,
+) -> serenity::framework::standard::CommandResult<Duration> {
+    let track = call.lock().await.queue().current();
+    let track = match track {
+        Some(track) => track,
+        None => message_err!(fluent!(MUSIC_empty_queue)),
+    };
+    if !track.is_seekable() {
+        message_err!(fluent!(MUSIC_not_seekable));
+    }
+    let filter = crate::shared::audio::active_filter(guildid);
+    let position = filter.song_time(track.get_info().await?.position);
+    let position = target(position, track.metadata().duration);
+    track.seek_time(filter.played_time(position))?;
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_with_colons() {
+        assert_eq!(parse_timestamp("83"), Some(Duration::from_secs(83)));
+        assert_eq!(parse_timestamp("1:23"), Some(Duration::from_secs(83)));
+        assert_eq!(parse_timestamp("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_timestamp("1:2:3"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("1::3"), None);
+        assert_eq!(parse_timestamp("1:x"), None);
+    }
+
+    #[test]
+    fn timestamp_with_units() {
+        assert_eq!(parse_timestamp("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_timestamp(" 2m "), Some(Duration::from_secs(120)));
+        assert_eq!(parse_timestamp("m5"), None);
+        assert_eq!(parse_timestamp("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_timestamp("1m5"), None);
+        assert_eq!(parse_timestamp("5d"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    #[test]
+    fn timestamp_overflow() {
+        assert_eq!(parse_timestamp("99999999999999999999"), None);
+        assert_eq!(parse_timestamp("9999999999999999999h"), None);
+        assert_eq!(parse_timestamp("9999999999999999999:0:0"), None);
+        assert_eq!(parse_timestamp("18446744073709551615s1s"), None);
+    }
+
+    #[test]
+    fn offset_stays_inside_the_track() {
+        let secs = Duration::from_secs;
+        assert_eq!(
+            offset_position(secs(30), secs(10), true, Some(secs(60))),
+            secs(40)
+        );
+        assert_eq!(
+            offset_position(secs(55), secs(10), true, Some(secs(60))),
+            secs(60)
+        );
+        assert_eq!(offset_position(secs(55), secs(10), true, None), secs(65));
+        assert_eq!(
+            offset_position(secs(30), secs(10), false, Some(secs(60))),
+            secs(20)
+        );
+        assert_eq!(
+            offset_position(secs(5), secs(10), false, Some(secs(60))),
+            secs(0)
+        );
+        assert_eq!(
+            offset_position(secs(5), Duration::MAX, true, None),
+            Duration::MAX
+        );
+    }
+}
@@ -0,0 +1,665 @@
+use serenity::builder::CreateComponents;
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::http::AttachmentType;
+use serenity::model::channel::ReactionType;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use serenity::model::interactions::message_component::{ButtonStyle, MessageComponentInteraction};
+use serenity::prelude::TypeMapKey;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+
+pub const CONSENT_BUTTON: &str = "music_record_consent";
+pub const WITHDRAW_BUTTON: &str = "music_record_withdraw";
+
+/// Discord sends 48 kHz stereo audio
+pub const SAMPLE_RATE: u32 = 48000;
+pub const CHANNELS: u16 = 2;
+/// Nothing is kept after this time, so a forgotten recording doesn't fill the memory
+pub const MAX_RECORDING_DURATION: Duration = Duration::from_secs(10 * 60);
+/// The audio kept for a recording, it is stopped when it reaches this so many speakers don't fill the memory
+pub const MAX_RECORDING_BYTES: usize = 256 * 1024 * 1024;
+/// The size of the files Discord accepts from a bot without boosts
+pub const UPLOAD_LIMIT: usize = 8 * 1024 * 1024;
+/// Discord accepts at most 10 attachments per message
+const FILES_PER_MESSAGE: usize = 10;
+
+/// Where the recordings too big to be sent are stored, set with `WH_RECORDINGS_DIR`
+pub static RECORDINGS_DIR: once_cell::sync::Lazy<Option<PathBuf>> =
+    once_cell::sync::Lazy::new(|| {
+        dotenv::dotenv().ok();
+        std::env::var_os("WH_RECORDINGS_DIR").map(PathBuf::from)
+    });
+
+const MAX_SAMPLES: usize =
+    MAX_RECORDING_DURATION.as_secs() as usize * SAMPLE_RATE as usize * CHANNELS as usize;
+/// The samples mixed at once when writing a file
+const MIX_CHUNK: usize = SAMPLE_RATE as usize * CHANNELS as usize;
+
+/// The file type of the exported recordings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Wav,
+    /// Opus in an ogg container, encoded by ffmpeg
+    Ogg,
+}
+
+impl RecordingFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Ogg => "ogg",
+        }
+    }
+}
+
+/// The audio sent with one SSRC, a user gets a new one when they reconnect
+#[derive(Clone, Debug)]
+struct SpeakerTrack {
+    /// The RTP timestamp of the first packet
+    first_timestamp: u32,
+    /// Where the first packet is from the start of the recording, in frames
+    start: usize,
+    /// Interleaved stereo samples from the first packet
+    samples: Vec<i16>,
+}
+
+/// When someone started or stopped speaking, from the start of the recording
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpeakingEvent {
+    pub user: UserId,
+    pub at: Duration,
+    pub speaking: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildRecording {
+    /// The voice channel recorded
+    pub channel: ChannelId,
+    /// Where the consent announcement was sent
+    pub text_channel: ChannelId,
+    pub announcement: Option<MessageId>,
+    pub started_by: UserId,
+    started: Instant,
+    /// Only the audio of the users who agreed is kept
+    consented: HashSet<UserId>,
+    speakers: HashMap<u32, (UserId, SpeakerTrack)>,
+    /// The samples of every track, kept under [`MAX_RECORDING_BYTES`]
+    stored: usize,
+    /// Woken when a packet doesn't fit under [`MAX_RECORDING_BYTES`]
+    full: Arc<tokio::sync::Notify>,
+    events: Vec<SpeakingEvent>,
+}
+
+impl GuildRecording {
+    pub fn new(channel: ChannelId, text_channel: ChannelId, started_by: UserId) -> Self {
+        Self {
+            channel,
+            text_channel,
+            announcement: None,
+            started_by,
+            started: Instant::now(),
+            consented: std::iter::once(started_by).collect(),
+            speakers: HashMap::new(),
+            stored: 0,
+            full: Arc::new(tokio::sync::Notify::new()),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn started_at(&self) -> Instant {
+        self.started
+    }
+
+    /// Notified once the recording can't keep more audio
+    pub fn full(&self) -> Arc<tokio::sync::Notify> {
+        self.full.clone()
+    }
+
+    pub fn consented(&self) -> &HashSet<UserId> {
+        &self.consented
+    }
+
+    pub fn consent(&mut self, user: UserId) -> bool {
+        self.consented.insert(user)
+    }
+
+    /// The audio of the user recorded so far is dropped with their consent
+    pub fn withdraw(&mut self, user: UserId) -> bool {
+        self.speakers.retain(|_, (u, _)| *u != user);
+        self.stored = self
+            .speakers
+            .values()
+            .map(|(_, track)| track.samples.len())
+            .sum();
+        self.events.retain(|e| e.user != user);
+        self.consented.remove(&user)
+    }
+
+    /// Add the decoded audio of a packet, placed with its RTP timestamp.
+    /// Nothing is added once the recording is full
+    pub fn append_buffer(&mut self, user: UserId, ssrc: u32, timestamp: u32, data: &[i16]) {
+        if !self.consented.contains(&user) || data.is_empty() {
+            return;
+        }
+        let elapsed = self.started.elapsed();
+        let (_, track) = self.speakers.entry(ssrc).or_insert_with(|| {
+            (
+                user,
+                SpeakerTrack {
+                    first_timestamp: timestamp,
+                    start: (elapsed.as_secs_f64() * SAMPLE_RATE as f64) as usize,
+                    samples: Vec::new(),
+                },
+            )
+        });
+        // Packets older than the first one wrap around and are dropped by the size check
+        let frame = timestamp.wrapping_sub(track.first_timestamp) as usize;
+        let offset = frame.saturating_mul(CHANNELS as usize);
+        let end = offset.saturating_add(data.len());
+        if end.saturating_add(track.start * CHANNELS as usize) > MAX_SAMPLES {
+            return;
+        }
+        if track.samples.len() < end {
+            let added = end - track.samples.len();
+            if (self.stored + added) * std::mem::size_of::<i16>() > MAX_RECORDING_BYTES {
+                self.full.notify_one();
+                return;
+            }
+            track.samples.resize(end, 0);
+            self.stored += added;
+        }
+        track.samples[offset..end].copy_from_slice(data);
+    }
+
+    pub fn speaking_update(&mut self, user: UserId, speaking: bool) {
+        if self.consented.contains(&user) && self.started.elapsed() < MAX_RECORDING_DURATION {
+            self.events.push(SpeakingEvent {
+                user,
+                at: self.started.elapsed(),
+                speaking,
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.speakers.is_empty()
+    }
+
+    /// The users heard in the recording, in the order they first spoke
+    pub fn speakers(&self) -> Vec<UserId> {
+        let mut tracks = self.speakers.values().collect::<Vec<_>>();
+        tracks.sort_by_key(|(_, track)| track.start);
+        let mut users = Vec::new();
+        for (user, _) in tracks {
+            if !users.contains(user) {
+                users.push(*user);
+            }
+        }
+        users
+    }
+
+    pub fn events(&self) -> &[SpeakingEvent] {
+        &self.events
+    }
+
+    /// Write the tracks added together, of every speaker if `user` is `None`.
+    /// They are mixed by chunks so only the file is held in memory
+    fn write_mix<W: std::io::Write + std::io::Seek>(
+        &self,
+        user: Option<UserId>,
+        writter: &mut hound::WavWriter<W>,
+    ) -> hound::Result<()> {
+        let tracks = self
+            .speakers
+            .values()
+            .filter(|(u, _)| user.is_none_or(|user| user == *u))
+            .map(|(_, track)| (track.start * CHANNELS as usize, track.samples.as_slice()))
+            .collect::<Vec<_>>();
+        let len = tracks
+            .iter()
+            .map(|(start, samples)| start + samples.len())
+            .max()
+            .unwrap_or_default();
+        let mut mixed = vec![0i32; MIX_CHUNK];
+        for chunk_start in (0..len).step_by(MIX_CHUNK) {
+            let chunk = &mut mixed[..MIX_CHUNK.min(len - chunk_start)];
+            chunk.fill(0);
+            for &(start, samples) in &tracks {
+                let from = chunk_start.max(start);
+                let to = (chunk_start + chunk.len()).min(start + samples.len());
+                for i in from..to {
+                    chunk[i - chunk_start] += samples[i - start] as i32;
+                }
+            }
+            for &sample in chunk.iter() {
+                writter.write_sample(sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A WAV file with everyone, or with only `user`
+    pub fn record_buffer(
+        &self,
+        user: Option<UserId>,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut buffer = Vec::new();
+        {
+            let mut writter = hound::WavWriter::new(
+                std::io::Cursor::new(&mut buffer),
+                hound::WavSpec {
+                    channels: CHANNELS,
+                    sample_rate: SAMPLE_RATE,
+                    bits_per_sample: 16,
+                    sample_format: hound::SampleFormat::Int,
+                },
+            )?;
+
+            self.write_mix(user, &mut writter)?;
+            writter.finalize()?;
+        }
+        Ok(buffer)
+    }
+}
+
+pub struct RecordingsKey;
+
+impl TypeMapKey for RecordingsKey {
+    type Value = Arc<Recordings>;
+}
+
+/// Each recording has its own lock, the packets of a guild never wait for another guild.
+/// The locks are only held for a packet, never across an `.await`
+#[derive(Debug, Default)]
+pub struct Recordings {
+    inner: parking_lot::RwLock<HashMap<GuildId, parking_lot::Mutex<GuildRecording>>>,
+    /// Who sends each SSRC, Discord only tells it once so it is kept even when nothing is recorded
+    ssrcs: parking_lot::RwLock<HashMap<GuildId, HashMap<u32, UserId>>>,
+}
+
+impl Recordings {
+    /// Run `f` on the recording of the guild, `None` if it isn't recorded
+    pub fn with_guild<R>(
+        &self,
+        guild_id: &GuildId,
+        f: impl FnOnce(&mut GuildRecording) -> R,
+    ) -> Option<R> {
+        let inner = self.inner.read();
+        let mut recording = inner.get(guild_id)?.lock();
+        Some(f(&mut recording))
+    }
+
+    pub fn is_recording(&self, guild_id: &GuildId) -> bool {
+        self.inner.read().contains_key(guild_id)
+    }
+
+    /// Returns false if the guild is already being recorded
+    pub fn start(&self, guild_id: GuildId, recording: GuildRecording) -> bool {
+        match self.inner.write().entry(guild_id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(parking_lot::Mutex::new(recording));
+                true
+            }
+        }
+    }
+
+    pub fn stop(&self, guild_id: &GuildId) -> Option<GuildRecording> {
+        self.inner
+            .write()
+            .remove(guild_id)
+            .map(parking_lot::Mutex::into_inner)
+    }
+
+    /// Stops the recording of the guild only if it is the one started at `started`
+    pub fn stop_started_at(&self, guild_id: &GuildId, started: Instant) -> Option<GuildRecording> {
+        let mut inner = self.inner.write();
+        if inner.get_mut(guild_id)?.get_mut().started != started {
+            return None;
+        }
+        inner.remove(guild_id).map(parking_lot::Mutex::into_inner)
+    }
+
+    fn set_user(&self, guild_id: GuildId, ssrc: u32, user: UserId) {
+        self.ssrcs
+            .write()
+            .entry(guild_id)
+            .or_default()
+            .insert(ssrc, user);
+    }
+
+    fn user(&self, guild_id: &GuildId, ssrc: u32) -> Option<UserId> {
+        self.ssrcs.read().get(guild_id)?.get(&ssrc).copied()
+    }
+}
+
+/// Receives the audio of the call, registered on the voice packets and the speaking events
+#[derive(Clone)]
+pub struct VoiceRecorder {
+    pub recordings: Arc<Recordings>,
+    pub guild_id: GuildId,
+}
+
+#[serenity::async_trait]
+impl songbird::events::EventHandler for VoiceRecorder {
+    async fn act(
+        &self,
+        ctx: &songbird::events::EventContext<'_>,
+    ) -> Option<songbird::events::Event> {
+        let recordings = &self.recordings;
+        match ctx {
+            songbird::EventContext::SpeakingStateUpdate(speaking) => {
+                if let Some(user) = speaking.user_id {
+                    recordings.set_user(self.guild_id, speaking.ssrc, UserId(user.0));
+                }
+            }
+            songbird::EventContext::SpeakingUpdate(update) => {
+                let user = recordings.user(&self.guild_id, update.ssrc)?;
+                recordings.with_guild(&self.guild_id, |recording| {
+                    recording.speaking_update(user, update.speaking)
+                });
+            }
+            songbird::EventContext::VoicePacket(vc) => {
+                // Only decoded while the guild is recorded, nothing is locked for the other packets
+                let audio = vc.audio.as_ref()?;
+                let user = recordings.user(&self.guild_id, vc.packet.ssrc)?;
+                recordings.with_guild(&self.guild_id, |recording| {
+                    recording.append_buffer(user, vc.packet.ssrc, vc.packet.timestamp.0 .0, audio)
+                });
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// The audio is only decoded while the guild is recorded
+pub async fn set_decoding(call: &tokio::sync::Mutex<songbird::Call>, decode: bool) {
+    let mut call = call.lock().await;
+    let mode = if decode {
+        songbird::driver::DecodeMode::Decode
+    } else {
+        songbird::driver::DecodeMode::Decrypt
+    };
+    let config = call.config().clone().decode_mode(mode);
+    call.set_config(config);
+}
+
+/// The consent buttons under the announcement, removed once the recording is over
+pub fn fill_components(components: &mut CreateComponents, running: bool) -> &mut CreateComponents {
+    if running {
+        components.create_action_row(|row| {
+            row.create_button(|b| {
+                b.style(ButtonStyle::Success)
+                    .emoji(ReactionType::Unicode("🎙".into()))
+                    .label(fluent!(MUSIC_record_consent_button))
+                    .custom_id(CONSENT_BUTTON)
+            })
+            .create_button(|b| {
+                b.style(ButtonStyle::Danger)
+                    .label(fluent!(MUSIC_record_withdraw_button))
+                    .custom_id(WITHDRAW_BUTTON)
+            })
+        });
+    }
+    components
+}
+
+pub fn is_button(custom_id: &str) -> bool {
+    custom_id == CONSENT_BUTTON || custom_id == WITHDRAW_BUTTON
+}
+
+/// Agree to be recorded or withdraw, only on the announcement of the current recording
+pub async fn press_button(
+    ctx: &Context,
+    interaction: &MessageComponentInteraction,
+) -> CommandResult<Option<String>> {
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(None),
+    };
+    let recordings = ctx
+        .data
+        .read()
+        .await
+        .get::<RecordingsKey>()
+        .unwrap()
+        .clone();
+    let answer = recordings.with_guild(&guild_id, |recording| {
+        if recording.announcement != Some(interaction.message.id) {
+            return None;
+        }
+        match interaction.data.custom_id.as_str() {
+            CONSENT_BUTTON => {
+                recording.consent(interaction.user.id);
+                Some(Some(fluent!(MUSIC_record_consented).to_string()))
+            }
+            WITHDRAW_BUTTON => {
+                recording.withdraw(interaction.user.id);
+                Some(Some(fluent!(MUSIC_record_withdrawn).to_string()))
+            }
+            _ => Some(None),
+        }
+    });
+    match answer.flatten() {
+        Some(answer) => Ok(answer),
+        None => message_err!(fluent!(MUSIC_record_not_running)),
+    }
+}
+
+/// Encode a WAV file to opus with ffmpeg
+async fn encode_ogg(wav: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let mut child = tokio::process::Command::new("ffmpeg")
+        .args(["-v", "quiet", "-f", "wav", "-i", "pipe:0"])
+        .args(["-c:a", "libopus", "-b:a", "96k", "-f", "ogg", "pipe:1"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    // Written while the output is read, ffmpeg would block on a full pipe
+    let writer = tokio::spawn(async move { stdin.write_all(&wav).await });
+    let output = child.wait_with_output().await?;
+    writer.await.map_err(std::io::Error::other)??;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "ffmpeg exited with {}",
+            output.status
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn format_offset(at: Duration) -> String {
+    let secs = at.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        at.subsec_millis()
+    )
+}
+
+/// The files of the recording: everyone mixed, each speaker alone and when they spoke.
+/// `names` gives the names written in the timeline
+pub async fn export(
+    recording: GuildRecording,
+    format: RecordingFormat,
+    names: &HashMap<UserId, String>,
+) -> Result<Vec<(String, Vec<u8>)>, Box<dyn std::error::Error + Send + Sync>> {
+    let recording = Arc::new(recording);
+    let speakers = recording.speakers();
+    let mut files = Vec::with_capacity(speakers.len() + 2);
+    // Each file is encoded before the next one is written, only one WAV is held at once
+    for (name, user) in std::iter::once(("mixed".to_string(), None)).chain(
+        speakers
+            .into_iter()
+            .map(|user| (format!("speaker-{}", user), Some(user))),
+    ) {
+        let wav = {
+            let recording = recording.clone();
+            tokio::task::spawn_blocking(move || recording.record_buffer(user)).await??
+        };
+        let data = match format {
+            RecordingFormat::Wav => wav,
+            RecordingFormat::Ogg => encode_ogg(wav).await?,
+        };
+        files.push((format!("{}.{}", name, format.extension()), data));
+    }
+
+    let mut timeline = String::new();
+    for event in recording.events() {
+        let name = names
+            .get(&event.user)
+            .map(String::as_str)
+            .unwrap_or_default();
+        timeline.push_str(&format!(
+            "{} {} ({}) {}\n",
+            format_offset(event.at),
+            event.user,
+            name,
+            if event.speaking { "started" } else { "stopped" }
+        ));
+    }
+    files.push(("speaking.txt".to_string(), timeline.into_bytes()));
+    Ok(files)
+}
+
+/// Send the files in the channel, or store them in the recordings directory if they are too big.
+/// Returns where they were stored
+pub async fn deliver(
+    ctx: &Context,
+    channel: ChannelId,
+    guild_id: GuildId,
+    files: Vec<(String, Vec<u8>)>,
+) -> CommandResult<Option<PathBuf>> {
+    if files.iter().map(|(_, data)| data.len()).sum::<usize>() <= UPLOAD_LIMIT {
+        for chunk in files.chunks(FILES_PER_MESSAGE) {
+            let attachments = chunk
+                .iter()
+                .map(|(name, data)| AttachmentType::Bytes {
+                    data: data.as_slice().into(),
+                    filename: name.clone(),
+                })
+                .collect::<Vec<_>>();
+            channel
+                .send_files(&ctx.http, attachments, |m| {
+                    m.content(fluent!(MUSIC_record_sent))
+                })
+                .await?;
+        }
+        return Ok(None);
+    }
+    let dir = match RECORDINGS_DIR.as_ref() {
+        Some(dir) => dir.join(guild_id.to_string()).join(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                .to_string(),
+        ),
+        None => message_err!(fluent!(MUSIC_record_too_big)),
+    };
+    tokio::fs::create_dir_all(&dir).await?;
+    for (name, data) in files {
+        tokio::fs::write(dir.join(name), data).await?;
+    }
+    Ok(Some(dir))
+}
+
+/// Stop taking the audio of the call, close the announcement and send the files of the stopped recording in `channel`.
+/// Returns where they were stored if they were too big to be sent
+pub async fn finish(
+    ctx: &Context,
+    channel: ChannelId,
+    guild_id: GuildId,
+    recording: GuildRecording,
+    format: RecordingFormat,
+) -> CommandResult<Option<PathBuf>> {
+    if let Some(call) = songbird::get(ctx).await.unwrap().get(guild_id) {
+        set_decoding(&call, false).await;
+    }
+    if let Some(announcement) = recording.announcement {
+        let ended = recording
+            .text_channel
+            .edit_message(&ctx.http, announcement, |m| {
+                m.content(format!(fluent!(MUSIC_record_ended), recording.channel))
+                    .components(|c| fill_components(c, false))
+            })
+            .await;
+        if let Err(e) = ended {
+            warn!("Couldn't edit the recording announcement: {}", e);
+        }
+    }
+    if recording.is_empty() {
+        message_err!(fluent!(MUSIC_record_empty));
+    }
+
+    let mut names = HashMap::new();
+    for user in recording.speakers() {
+        if let Some(user) = ctx.cache.user(user).await {
+            names.insert(user.id, user.tag());
+        }
+    }
+    let files = export(recording, format, &names).await?;
+    deliver(ctx, channel, guild_id, files).await
+}
+
+/// Stop the recording and send it once it reaches [`MAX_RECORDING_DURATION`] or [`MAX_RECORDING_BYTES`],
+/// if it wasn't stopped before. `full` is [`GuildRecording::full`]
+pub fn stop_at_limit(
+    ctx: Context,
+    guild_id: GuildId,
+    started: Instant,
+    full: Arc<tokio::sync::Notify>,
+) {
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = tokio::time::sleep(MAX_RECORDING_DURATION) => {}
+            _ = full.notified() => {}
+        }
+        let recordings = ctx
+            .data
+            .read()
+            .await
+            .get::<RecordingsKey>()
+            .unwrap()
+            .clone();
+        // A recording started after this one has its own timer
+        if let Some(recording) = recordings.stop_started_at(&guild_id, started) {
+            finish_in_text_channel(&ctx, guild_id, recording).await;
+        }
+    });
+}
+
+/// [`finish`] a recording stopped without a command, the files and the errors go where it was announced
+pub async fn finish_in_text_channel(ctx: &Context, guild_id: GuildId, recording: GuildRecording) {
+    let text_channel = recording.text_channel;
+    let answer = match finish(ctx, text_channel, guild_id, recording, RecordingFormat::Ogg).await {
+        Ok(Some(dir)) => format!(fluent!(MUSIC_record_stored), dir.display()),
+        Ok(None) => return,
+        Err(e) => match e.downcast_ref::<wh_core::Error>() {
+            Some(wh_core::Error::Message(msg)) => msg.clone(),
+            Some(wh_core::Error::Both { msg, err }) => {
+                error!("Couldn't send the recording: {}", err);
+                msg.clone()
+            }
+            _ => {
+                error!("Couldn't send the recording: {}", e);
+                return;
+            }
+        },
+    };
+    if let Err(e) = text_channel.say(&ctx.http, answer).await {
+        warn!("Couldn't tell the end of the recording: {}", e);
+    }
+}
@@ -0,0 +1,251 @@
+use super::{LoopState, TrackMetadataKey};
+use serenity::{
+    client::Context,
+    framework::standard::CommandResult,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::{RwLock, TypeMap},
+};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use wh_database::shared::{DatabaseKey, Id};
+
+/// How often the queue of every guild is saved
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The guilds whose queue is being restored, they aren't saved until it is done
+static RESTORING: once_cell::sync::Lazy<parking_lot::Mutex<HashSet<u64>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedTrack {
+    pub url: String,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+    pub added_by: u64,
+}
+
+/// What is needed to resume the queue of a guild
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedQueue {
+    pub tracks: Vec<SavedTrack>,
+    /// Where the first track was
+    pub position: Duration,
+    /// The loop state of the first track
+    pub loops: LoopState,
+    /// Whether the finished tracks are added back to the queue
+    #[serde(default)]
+    pub queue_loop: bool,
+}
+
+impl From<songbird::tracks::LoopState> for LoopState {
+    fn from(state: songbird::tracks::LoopState) -> Self {
+        match state {
+            songbird::tracks::LoopState::Infinite => LoopState::Infinite,
+            songbird::tracks::LoopState::Finite(0) => LoopState::None,
+            songbird::tracks::LoopState::Finite(n) => LoopState::Finite(n.min(99) as u8),
+        }
+    }
+}
+
+/// The channel the call is in and its queue, `None` if it isn't connected or has nothing to resume
+pub async fn queue_state(
+    guild: GuildId,
+    call: &Arc<tokio::sync::Mutex<songbird::Call>>,
+) -> Option<(ChannelId, SavedQueue)> {
+    let (channel, handles) = {
+        let lock = call.lock().await;
+        (lock.current_channel()?, lock.queue().current_queue())
+    };
+    let mut queue = SavedQueue {
+        tracks: Vec::with_capacity(handles.len()),
+        position: Duration::ZERO,
+        loops: LoopState::None,
+        queue_loop: super::queue_control::is_queue_looping(guild.0),
+    };
+    for (i, handle) in handles.iter().enumerate() {
+        let typemap = handle.typemap().read().await;
+        let metadata = match typemap.get::<TrackMetadataKey>() {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        let url = match &metadata.url {
+            Some(url) => url.clone(),
+            None => continue,
+        };
+        if i == 0 {
+            // The track may have ended since the queue was read
+            if let Ok(state) = handle.get_info().await {
+                queue.position = super::audio::active_filter(guild.0).song_time(state.position);
+                queue.loops = state.loops.into();
+            }
+        }
+        queue.tracks.push(SavedTrack {
+            url,
+            title: metadata.title.clone(),
+            duration: metadata.duration,
+            added_by: metadata.added_by.0,
+        });
+    }
+    if queue.tracks.is_empty() {
+        None
+    } else {
+        Some((ChannelId(channel.0), queue))
+    }
+}
+
+/// Save the queue of every guild the bot is playing in, and forget the others
+pub async fn save_queues(data: &RwLock<TypeMap>, guilds: &[GuildId]) -> CommandResult {
+    let (db, manager) = {
+        let lock = data.read().await;
+        (
+            lock.get::<DatabaseKey>().unwrap().clone(),
+            lock.get::<songbird::SongbirdKey>().unwrap().clone(),
+        )
+    };
+    let saved = query!("SELECT guildid FROM music_queue")
+        .fetch_all(&db)
+        .await?
+        .into_iter()
+        .map(|r| Id::from(r.guildid).0)
+        .collect::<HashSet<_>>();
+    for guild in guilds {
+        if RESTORING.lock().contains(&guild.0) {
+            continue;
+        }
+        let state = match manager.get(*guild) {
+            Some(call) => queue_state(*guild, &call).await,
+            None => None,
+        };
+        match state {
+            Some((channel, queue)) => {
+                query!(
+                    "INSERT INTO music_queue (guildid, channelid, queue) VALUES ($1::int8, $2::int8, $3::text::jsonb) ON CONFLICT (guildid) DO UPDATE SET channelid = EXCLUDED.channelid, queue = EXCLUDED.queue, saved_at = now()",
+                    Id(guild.0) as _,
+                    Id(channel.0) as _,
+                    serde_json::to_string(&queue)?,
+                )
+                .execute(&db)
+                .await?;
+            }
+            None if saved.contains(&guild.0) => {
+                query!(
+                    "DELETE FROM music_queue WHERE guildid = $1::int8",
+                    Id(guild.0) as _,
+                )
+                .execute(&db)
+                .await?;
+            }
+            None => (),
+        }
+    }
+    Ok(())
+}
+
+/// The saved queue of the guild and the channel it was played in
+pub async fn get_saved_queue(
+    ctx: &Context,
+    guildid: u64,
+) -> CommandResult<Option<(ChannelId, SavedQueue)>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let row = query!(
+        r#"SELECT channelid, queue::text AS "queue!" FROM music_queue WHERE guildid = $1::int8"#,
+        Id(guildid) as _,
+    )
+    .fetch_optional(db)
+    .await?;
+    match row {
+        Some(r) => Ok(Some((
+            ChannelId(Id::from(r.channelid).0),
+            serde_json::from_str(&r.queue)?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Rejoin the channel and queue the saved tracks again, starting where the first one was
+pub async fn restore_queue(
+    ctx: &Context,
+    guild: GuildId,
+    channel: ChannelId,
+    queue: SavedQueue,
+) -> CommandResult {
+    RESTORING.lock().insert(guild.0);
+    let res = restore(ctx, guild, channel, queue).await;
+    RESTORING.lock().remove(&guild.0);
+    res
+}
+
+async fn restore(
+    ctx: &Context,
+    guild: GuildId,
+    channel: ChannelId,
+    queue: SavedQueue,
+) -> CommandResult {
+    let manager = songbird::get(ctx).await.unwrap();
+    let (call, res) = manager.join(guild, channel).await;
+    res?;
+    super::register_call_events(ctx, guild, &call).await;
+    super::queue_control::set_queue_loop(guild.0, queue.queue_loop);
+    let settings = {
+        let lock = ctx.data.read().await;
+        let db = lock.get::<DatabaseKey>().unwrap();
+        super::audio::audio_settings(db, guild.0).await?
+    };
+    let mut first = true;
+    for saved in queue.tracks {
+        let (track, handle) =
+            match super::create_track(saved.url.clone(), UserId(saved.added_by), guild, &settings)
+                .await
+            {
+                Ok(track) => track,
+                Err(e) => {
+                    warn!("Couldn't restore `{}` in {}: {}", saved.url, guild, e);
+                    continue;
+                }
+            };
+        if first {
+            first = false;
+            if !queue.position.is_zero() {
+                handle.seek_time(settings.filter.played_time(queue.position))?;
+            }
+            match queue.loops {
+                LoopState::None => (),
+                LoopState::Finite(n) => handle.loop_for(n as usize)?,
+                LoopState::Infinite => handle.enable_loop()?,
+            }
+        }
+        call.lock().await.enqueue(track);
+    }
+    info!("Restored the queue of {}", guild);
+    Ok(())
+}
+
+/// Meant for `ready`: resume the saved queues and leave the voice channels the bot has nothing to play in.
+/// The guilds with a live call are left alone since it is only a reconnection
+pub async fn restore_queues(ctx: &Context, guilds: &[GuildId]) {
+    let manager = songbird::get(ctx).await.unwrap();
+    for &guild in guilds {
+        if let Some(call) = manager.get(guild) {
+            if call.lock().await.current_channel().is_some() {
+                continue;
+            }
+        }
+        match get_saved_queue(ctx, guild.0).await {
+            Ok(Some((channel, queue))) => {
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = restore_queue(&ctx, guild, channel, queue).await {
+                        error!("Couldn't restore the queue of {}: {}", guild, e);
+                    }
+                });
+            }
+            Ok(None) => {
+                let userid = ctx.cache.current_user_id().await;
+                if let Err(e) = guild.disconnect_member(&ctx.http, userid).await {
+                    error!("Error when disconnecting voice in {}: {}", guild, e);
+                }
+            }
+            Err(e) => error!("Couldn't get the saved queue of {}: {}", guild, e),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use serenity::builder::CreateComponents;
+use serenity::{framework::standard::CommandResult, prelude::TypeMapKey};
+use std::{sync::Arc, time::Duration};
+
+/// How many results `search` shows
+pub const SEARCH_RESULT_COUNT: usize = 5;
+/// How long the user has to pick the results
+pub const PICK_TIMEOUT: Duration = Duration::from_secs(60);
+/// The select menu under the results
+pub const PICK_MENU: &str = "music_search_pick";
+/// Discord refuses longer labels and descriptions in a select menu
+const OPTION_TEXT_LEN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub url: String,
+    pub title: String,
+    pub channel: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+impl SearchResult {
+    /// The channel and the duration, shown under the title in the select menu
+    fn details(&self) -> Option<String> {
+        match (&self.channel, self.duration) {
+            (Some(channel), Some(duration)) => {
+                Some(format!("{} ({})", channel, format_duration(duration)))
+            }
+            (Some(channel), None) => Some(channel.clone()),
+            (None, Some(duration)) => Some(format_duration(duration)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "**{}**", self.title)?;
+        if let Some(channel) = &self.channel {
+            write!(f, " by {}", channel)?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, " ({})", format_duration(duration))?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the songs matching a query for `search`.
+/// The one used is kept in the typemap with [`SearchBackendKey`], so it can be replaced by a local one
+#[serenity::async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// At most `count` results, the best match first
+    async fn search(&self, query: &str, count: usize) -> CommandResult<Vec<SearchResult>>;
+}
+
+pub struct SearchBackendKey;
+
+impl TypeMapKey for SearchBackendKey {
+    type Value = Arc<dyn SearchBackend>;
+}
+
+/// Searches youtube with youtube-dl, which is already needed to play the songs
+pub struct YoutubeDlSearch;
+
+#[derive(Debug, Clone, Deserialize)]
+struct YoutubeDlEntry {
+    id: String,
+    title: Option<String>,
+    channel: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+}
+
+#[serenity::async_trait]
+impl SearchBackend for YoutubeDlSearch {
+    async fn search(&self, query: &str, count: usize) -> CommandResult<Vec<SearchResult>> {
+        let output = tokio::process::Command::new("youtube-dl")
+            .args(["--dump-json", "--flat-playlist", "--no-warnings"])
+            .arg(format!("ytsearch{}:{}", count, query))
+            .output()
+            .await;
+        let output = match output {
+            Ok(output) => output,
+            Err(_) => error_err!("You need to have youtube-dl installed!"),
+        };
+        if !output.status.success() {
+            error_err!(format!(
+                "youtube-dl failed to search `{}`: {}",
+                query,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let mut results = Vec::with_capacity(count);
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let entry: YoutubeDlEntry = serde_json::from_str(line)?;
+            results.push(SearchResult {
+                url: format!("https://youtube.com/watch?v={}", entry.id),
+                title: entry.title.unwrap_or(entry.id),
+                channel: entry.channel.or(entry.uploader),
+                duration: entry
+                    .duration
+                    .filter(|d| d.is_finite() && *d >= 0.0)
+                    .map(Duration::from_secs_f64),
+            });
+        }
+        Ok(results)
+    }
+}
+
+/// The indexes picked in an answer like `1 3`, `1,3` or `2-4`, numbered from 1 up to `count`.
+/// Returns `None` if the answer isn't a valid pick
+pub fn parse_selection(answer: &str, count: usize) -> Option<Vec<usize>> {
+    let mut picked = Vec::new();
+    for part in answer
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+    {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?),
+            None => {
+                let n = part.parse::<usize>().ok()?;
+                (n, n)
+            }
+        };
+        if start == 0 || start > end || end > count {
+            return None;
+        }
+        for n in start..=end {
+            if !picked.contains(&(n - 1)) {
+                picked.push(n - 1);
+            }
+        }
+    }
+    if picked.is_empty() {
+        None
+    } else {
+        Some(picked)
+    }
+}
+
+/// The results picked in an answer, in the order they were picked, see [`parse_selection`]
+pub fn pick_results<'a>(
+    results: &'a [SearchResult],
+    answer: &str,
+) -> Option<Vec<&'a SearchResult>> {
+    Some(
+        parse_selection(answer, results.len())?
+            .into_iter()
+            .map(|i| &results[i])
+            .collect(),
+    )
+}
+
+/// How the author picked the results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PickAnswer {
+    /// A message like `1 3` or `2-4`
+    Message(String),
+    /// The values of the options chosen in the select menu
+    Menu(Vec<String>),
+}
+
+impl PickAnswer {
+    /// The results picked, `None` if the answer doesn't pick any
+    pub fn picked<'a>(&self, results: &'a [SearchResult]) -> Option<Vec<&'a SearchResult>> {
+        match self {
+            PickAnswer::Message(answer) => pick_results(results, answer),
+            // The options have the numbers of the results as values
+            PickAnswer::Menu(values) => pick_results(results, &values.join(" ")),
+        }
+    }
+}
+
+/// Search `query` with the backend, finding nothing is an error
+pub async fn find(backend: &dyn SearchBackend, query: &str) -> CommandResult<Vec<SearchResult>> {
+    let results = backend.search(query, SEARCH_RESULT_COUNT).await?;
+    if results.is_empty() {
+        message_err!(format!(fluent!(MUSIC_not_found_video), query));
+    }
+    Ok(results)
+}
+
+/// The results numbered like the answers pick them
+pub fn format_results(results: &[SearchResult]) -> String {
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| format!("`{}` {}", i + 1, result))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The select menu to pick the results, removed once they are picked
+pub fn fill_components<'a>(
+    components: &'a mut CreateComponents,
+    results: &[SearchResult],
+    open: bool,
+) -> &'a mut CreateComponents {
+    if open {
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(PICK_MENU)
+                    .placeholder(fluent!(MUSIC_search_menu))
+                    .min_values(1)
+                    .max_values(results.len() as u64)
+                    .options(|options| {
+                        for (i, result) in results.iter().enumerate() {
+                            options.create_option(|option| {
+                                let label = format!("{}. {}", i + 1, result.title);
+                                option
+                                    .label(label.chars().take(OPTION_TEXT_LEN).collect::<String>())
+                                    .value(i + 1);
+                                if let Some(details) = result.details() {
+                                    option.description(
+                                        details.chars().take(OPTION_TEXT_LEN).collect::<String>(),
+                                    );
+                                }
+                                option
+                            });
+                        }
+                        options
+                    })
+            })
+        });
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finds the same numbered songs for any query, without youtube-dl
+    struct FakeSearch {
+        available: usize,
+    }
+
+    #[serenity::async_trait]
+    impl SearchBackend for FakeSearch {
+        async fn search(&self, query: &str, count: usize) -> CommandResult<Vec<SearchResult>> {
+            Ok((1..=self.available.min(count))
+                .map(|i| SearchResult {
+                    url: format!("https://example.com/{}", i),
+                    title: format!("{} {}", query, i),
+                    channel: None,
+                    duration: Some(Duration::from_secs(60 * i as u64)),
+                })
+                .collect())
+        }
+    }
+
+    async fn fake_results(available: usize) -> Vec<SearchResult> {
+        find(&FakeSearch { available }, "song").await.unwrap()
+    }
+
+    fn urls(picked: Option<Vec<&SearchResult>>) -> Option<Vec<&str>> {
+        picked.map(|p| p.iter().map(|r| r.url.as_str()).collect())
+    }
+
+    #[test]
+    fn selection_single_and_lists() {
+        assert_eq!(parse_selection("1", 5), Some(vec![0]));
+        assert_eq!(parse_selection("1 3", 5), Some(vec![0, 2]));
+        assert_eq!(parse_selection("3,1", 5), Some(vec![2, 0]));
+        assert_eq!(parse_selection(" 1 ,  5 ", 5), Some(vec![0, 4]));
+    }
+
+    #[test]
+    fn selection_ranges() {
+        assert_eq!(parse_selection("2-4", 5), Some(vec![1, 2, 3]));
+        assert_eq!(parse_selection("3-3", 5), Some(vec![2]));
+        assert_eq!(parse_selection("4-2", 5), None);
+        assert_eq!(parse_selection("1-", 5), None);
+        assert_eq!(parse_selection("-2", 5), None);
+    }
+
+    #[test]
+    fn selection_out_of_range() {
+        assert_eq!(parse_selection("0", 5), None);
+        assert_eq!(parse_selection("6", 5), None);
+        assert_eq!(parse_selection("4-6", 5), None);
+        assert_eq!(parse_selection("1", 0), None);
+    }
+
+    #[test]
+    fn selection_duplicates() {
+        assert_eq!(parse_selection("2 2", 5), Some(vec![1]));
+        assert_eq!(parse_selection("1-3 2 3-4", 5), Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn selection_garbage() {
+        assert_eq!(parse_selection("", 5), None);
+        assert_eq!(parse_selection("  , ", 5), None);
+        assert_eq!(parse_selection("cancel", 5), None);
+        assert_eq!(parse_selection("1 two", 5), None);
+        assert_eq!(parse_selection("-1", 5), None);
+        assert_eq!(parse_selection("1.5", 5), None);
+        assert_eq!(parse_selection("99999999999999999999999", 5), None);
+    }
+
+    #[tokio::test]
+    async fn nothing_found_is_an_error() {
+        let err = find(&FakeSearch { available: 0 }, "song")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<wh_core::Error>(),
+            Some(wh_core::Error::Message(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn results_are_numbered_like_the_answers() {
+        let results = fake_results(2).await;
+        assert_eq!(
+            format_results(&results),
+            "`1` **song 1** (1:00)\n`2` **song 2** (2:00)"
+        );
+        assert_eq!(
+            urls(PickAnswer::Message("2".to_string()).picked(&results)),
+            Some(vec!["https://example.com/2"])
+        );
+    }
+
+    #[tokio::test]
+    async fn pick_from_the_menu() {
+        let results = fake_results(10).await;
+        let menu =
+            |values: &[&str]| PickAnswer::Menu(values.iter().map(|v| v.to_string()).collect());
+        assert_eq!(
+            urls(menu(&["3", "1"]).picked(&results)),
+            Some(vec!["https://example.com/3", "https://example.com/1"])
+        );
+        assert_eq!(urls(menu(&[]).picked(&results)), None);
+        assert_eq!(urls(menu(&["6"]).picked(&results)), None);
+        assert_eq!(
+            urls(menu(&["1-2"]).picked(&results)).map(|u| u.len()),
+            Some(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn pick_from_backend_results() {
+        let results = fake_results(10).await;
+        assert_eq!(
+            urls(pick_results(&results, "2-4")),
+            Some(vec![
+                "https://example.com/2",
+                "https://example.com/3",
+                "https://example.com/4"
+            ])
+        );
+        assert_eq!(
+            urls(pick_results(&results, "5 1")),
+            Some(vec!["https://example.com/5", "https://example.com/1"])
+        );
+        // Only what was shown can be picked
+        let results = fake_results(2).await;
+        assert_eq!(urls(pick_results(&results, "3")), None);
+        assert_eq!(urls(pick_results(&results, "nope")), None);
+    }
+}
@@ -0,0 +1,179 @@
+use once_cell::sync::Lazy;
+use serenity::client::Context;
+use serenity::framework::standard::CommandResult;
+use serenity::model::{
+    guild::Guild,
+    id::{ChannelId, UserId},
+};
+use std::collections::{HashMap, HashSet};
+
+/// How many of the listeners have to vote to skip a song, the requester and `music.manage` holders skip it at once
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SkipVote {
+    /// In percent, 0 lets anyone skip and is the default
+    pub threshold: u8,
+}
+
+impl wh_config::shared::Config for SkipVote {
+    const KEY: &'static str = "music.skip_vote";
+}
+wh_config::register_config!(SkipVote);
+
+/// The song being voted on and who voted
+#[derive(Debug, Default)]
+struct TrackVotes {
+    track: u128,
+    voters: HashSet<u64>,
+}
+
+static VOTES: Lazy<parking_lot::Mutex<HashMap<u64, TrackVotes>>> = Lazy::new(Default::default);
+
+/// The members of the channel who aren't bots
+pub fn listener_count(guild: &Guild, channel: ChannelId) -> usize {
+    guild
+        .voice_states
+        .values()
+        .filter(|state| state.channel_id == Some(channel))
+        .filter(|state| {
+            let member = state
+                .member
+                .as_ref()
+                .or_else(|| guild.members.get(&state.user_id));
+            !member.is_some_and(|m| m.user.bot)
+        })
+        .count()
+}
+
+/// How many votes are needed among `listeners`, at least one
+pub fn required_votes(listeners: usize, threshold: u8) -> usize {
+    (listeners * threshold as usize).div_ceil(100).max(1)
+}
+
+/// Add the vote of the user for the track, returns how many votes it has or `None` if the user already voted
+pub fn add_vote(guildid: u64, track: u128, userid: u64) -> Option<usize> {
+    let mut votes = VOTES.lock();
+    let votes = votes.entry(guildid).or_default();
+    if votes.track != track {
+        votes.track = track;
+        votes.voters.clear();
+    }
+    if votes.voters.insert(userid) {
+        Some(votes.voters.len())
+    } else {
+        None
+    }
+}
+
+/// How many votes the track has
+pub fn vote_count(guildid: u64, track: u128) -> usize {
+    match VOTES.lock().get(&guildid) {
+        Some(votes) if votes.track == track => votes.voters.len(),
+        _ => 0,
+    }
+}
+
+pub fn clear_votes(guildid: u64) {
+    VOTES.lock().remove(&guildid);
+}
+
+/// What happened to the current song after someone asked to skip it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipOutcome {
+    Skipped,
+    Voted { votes: usize, required: usize },
+    AlreadyVoted { votes: usize, required: usize },
+}
+
+/// Skip the current song of the call if the user can do it alone, otherwise add their vote.
+/// `channelid` is the text channel the request comes from, used to check the user's permissions
+pub async fn request_skip(
+    ctx: &Context,
+    guild: &Guild,
+    userid: UserId,
+    channelid: ChannelId,
+    call: &std::sync::Arc<tokio::sync::Mutex<songbird::Call>>,
+) -> CommandResult<SkipOutcome> {
+    let (track, voice_channel) = {
+        let lock = call.lock().await;
+        (lock.queue().current(), lock.current_channel())
+    };
+    let track = match track {
+        Some(track) => track,
+        None => message_err!(fluent!(MUSIC_empty_queue)),
+    };
+
+    let added_by = track
+        .typemap()
+        .read()
+        .await
+        .get::<crate::shared::TrackMetadataKey>()
+        .map(|m| m.added_by);
+    let threshold = {
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let scopes = wh_config::shared::Scope::chain(guild.id.0, None, None);
+        wh_config::shared::resolve_config_or_default::<SkipVote>(db, &scopes)
+            .await?
+            .threshold
+    };
+    let instant = threshold == 0
+        || added_by == Some(userid)
+        || match wh_permission::shared::user_permission::has_permission(
+            ctx,
+            userid.0,
+            guild.id.0,
+            Some(channelid.0),
+            "music.manage",
+        )
+        .await
+        {
+            Ok(allowed) => allowed,
+            Err(e) => {
+                error!("Error when checking the permission to skip: {:?}", e);
+                false
+            }
+        };
+    if !instant {
+        let listeners = voice_channel
+            .map(|c| listener_count(guild, ChannelId(c.0)))
+            .unwrap_or_default();
+        let required = required_votes(listeners, threshold);
+        let track_id = track.uuid().as_u128();
+        let votes = match add_vote(guild.id.0, track_id, userid.0) {
+            Some(votes) => votes,
+            None => {
+                return Ok(SkipOutcome::AlreadyVoted {
+                    votes: vote_count(guild.id.0, track_id),
+                    required,
+                })
+            }
+        };
+        if votes < required {
+            return Ok(SkipOutcome::Voted { votes, required });
+        }
+    }
+    clear_votes(guild.id.0);
+    call.lock().await.queue().skip()?;
+    Ok(SkipOutcome::Skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn votes_round_up() {
+        assert_eq!(required_votes(4, 50), 2);
+        assert_eq!(required_votes(5, 50), 3);
+        assert_eq!(required_votes(3, 34), 2);
+    }
+
+    #[test]
+    fn votes_at_the_bounds() {
+        assert_eq!(required_votes(10, 0), 1);
+        assert_eq!(required_votes(10, 100), 10);
+        assert_eq!(required_votes(0, 50), 1);
+        assert_eq!(required_votes(0, 100), 1);
+        assert_eq!(required_votes(1, 1), 1);
+    }
+}
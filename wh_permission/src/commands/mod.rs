@@ -4,6 +4,12 @@ use serenity::framework::standard::{Args, Check, CommandOptions, Reason};
 use serenity::model::channel::Message;
 use serenity::prelude::Context;
 
+crate::register_permission!(
+    "permission.manage",
+    fluent!(PERMISSION_PERMISSION_manage),
+    crate::shared::registry::DefaultPolicy::Nobody
+);
+
 const PERMISSION_MANAGE_CHECK: Check = Check {
     function: check_permission_manage_or_admin,
     name: "permission.manage",
@@ -11,6 +17,8 @@ const PERMISSION_MANAGE_CHECK: Check = Check {
     display_in_help: true,
 };
 
+/// Same as the `check_permission!` checks, kept as a function for the `permission` group.
+/// Discord administrators always pass since they come first in [`crate::shared::user_permission::evaluate_permission`]
 #[hook]
 async fn check_permission_manage_or_admin(
     ctx: &Context,
@@ -18,33 +26,5 @@ async fn check_permission_manage_or_admin(
     _: &mut Args,
     _: &CommandOptions,
 ) -> Result<(), Reason> {
-    let permission = "permission.manage";
-    let res = crate::shared::user_permission::has_permission(
-        ctx,
-        msg,
-        msg.author.id.0,
-        msg.guild_id.unwrap().0,
-        permission,
-    )
-    .await?;
-    let discord_permission = msg
-        .guild(&ctx.cache)
-        .await
-        .unwrap()
-        .member_permissions(ctx, msg.author.id)
-        .await;
-    if let Err(e) = &discord_permission {
-        return Err(Reason::UserAndLog {
-            user: "❌Internal Error".into(),
-            log: format!("Internal Error: {}", e),
-        });
-    }
-    let discord_permission = discord_permission.unwrap();
-    if !(res || discord_permission.administrator()) {
-        return Err(Reason::User(format!(
-            "❌You don't have the permission `{}`",
-            permission
-        )));
-    }
-    Ok(())
+    crate::shared::user_permission::check_permission(ctx, msg, "permission.manage").await
 }
@@ -2,17 +2,20 @@ use serenity::client::Context;
 use serenity::framework::standard::{macros::*, Args, CommandResult};
 use serenity::model::channel::Message;
 
+use crate::shared::temporary_permission::PermissionTarget;
+use command::COMMAND_COMMAND;
+use group::GROUP_COMMAND;
 use role::ROLE_COMMAND;
 
 #[command]
 #[only_in(guilds)]
-#[sub_commands(grant, remove, view, role, list)]
+#[sub_commands(grant, deny, remove, view, explain, role, group, command, list)]
 /// This is a top level command that manages roles' and users' permissions
 pub async fn permission(ctx: &Context, msg: &Message) -> CommandResult {
     reply_message!(
         ctx,
         msg,
-        "This command is separated into sub commands: `grant`, `remove`, `view`, `list` and `role`"
+        "This command is separated into sub commands: `grant`, `deny`, `remove`, `view`, `explain`, `list`, `role`, `group` and `command`"
     );
     Ok(())
 }
@@ -21,27 +24,39 @@ pub async fn permission(ctx: &Context, msg: &Message) -> CommandResult {
 #[only_in(guilds)]
 #[usage("")]
 #[example("")]
-/// List all valid permissions
+/// List all valid permissions with their module, description and who has them by default.
+/// A permission ending with `.*` grants every permission under it
 pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
-    let perms = crate::shared::user_permission::static_get_permission();
+    let mut perms = crate::shared::registry::registered_permissions();
+    let names = perms.iter().map(|p| p.name).collect::<Vec<_>>();
+    let tree = crate::shared::permission_tree(&names);
+    perms.sort_by_key(|p| (p.module, p.name));
+    let mut details = String::new();
+    for (i, perm) in perms.iter().enumerate() {
+        if i == 0 || perms[i - 1].module != perm.module {
+            details.push_str(&format!("\n**{}**\n", perm.module_name()));
+        }
+        details.push_str(&format!(
+            "`{}`: {} (default: {})\n",
+            perm.name, perm.description, perm.default
+        ));
+    }
     reply_message!(
         ctx,
         msg,
-        format!(
-            "Valid permissions are: {}",
-            perms
-                .iter()
-                .map(|p| format!("`{}` ", p))
-                .collect::<String>()
-        )
+        format!("Valid permissions are:\n```\n{}```{}", tree, details)
     );
     Ok(())
 }
 #[command]
 #[only_in(guilds)]
-#[usage("[@user] [permission]")]
-#[example("@-|Maix|#1010 permission.manage")]
-/// This grant the given permission the the mentioned user
+#[usage("[@user] [permission] [?#channel] [?duration]")]
+#[example("@-|Maix|#1010 music.*")]
+#[example("@-|Maix|#1010 music.manage #music 2h")]
+/// This grant the given permission the the mentioned user, `music.*` grants every music permission and `*` grants everything.
+/// A permission group is granted as `group:name`.
+/// When a channel or a category is given, the permission only applies there.
+/// When a duration is given (like `30m`, `12h` or `1w2d`) the permission is removed once it expires
 pub async fn grant(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let user_mention = msg.mentions.first();
     if user_mention.is_none() {
@@ -54,54 +69,107 @@ pub async fn grant(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
         message_err!("You need to provide a permission to give!");
     }
     let permission = permission.unwrap();
-    if !crate::shared::user_permission::static_get_permission().contains(&permission.as_str()) {
-        message_err!("This permission does't exist!");
+    let (channelid, duration) = grant_args(&mut args)?;
+    crate::shared::ensure_valid_and_manageable(ctx, msg, &permission).await?;
+    let lock = ctx.data.read().await;
+    let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+    // A temporary grant is kept apart and ranks above the deny, which applies again once it expires
+    let res = query!("insert into user_permission (userid, guildid, channelid, ids) values ($1::int8, $2::int8, $4::int8, CASE WHEN $5::bool THEN '{}'::text[] ELSE ARRAY[$3::text] END) on conflict (guildid, userid, channelid) do update set ids = CASE WHEN $5::bool THEN user_permission.ids ELSE array_distinct(array_append(user_permission.ids, $3::text)) END, denied = CASE WHEN $5::bool THEN user_permission.denied ELSE array_diff(user_permission.denied, ARRAY[$3::text]) END;",
+        wh_database::shared::Id(user_mention.id.0) as _,
+        wh_database::shared::Id(msg.guild_id.unwrap().0) as _,
+        permission,
+        wh_database::shared::Id(channelid.unwrap_or(0)) as _,
+        duration.is_some()
+    ).execute(db).await;
+
+    if let Err(e) = &res {
+        both_err!(
+            "An error occured with the database",
+            format!("Error when granting permission: {}", e)
+        );
     }
-    if permission == "permission.manage" {
-        let discord_permission = msg
-            .guild(&ctx.cache)
-            .await
-            .unwrap()
-            .member_permissions(ctx, msg.author.id)
-            .await;
-        if let Err(e) = &discord_permission {
-            both_err!("Internal Error", format!("Internal Error: {}", e));
-        }
-        let discord_permission = discord_permission.unwrap();
-        if !discord_permission.administrator() {
-            message_err!("This permission can only be managed by having the ADMINISTRATOR discord permission")
+    drop(lock);
+    crate::shared::user_permission::invalidate_user_cache(
+        msg.guild_id.unwrap().0,
+        user_mention.id.0,
+    );
+
+    let reply = match duration {
+        Some(duration) => {
+            grant_temporary(
+                ctx,
+                msg,
+                PermissionTarget::User(user_mention.id.0),
+                channelid,
+                &permission,
+                duration,
+            )
+            .await?
         }
+        None => scoped_reply("The permission has been granted", channelid),
+    };
+    reply_message!(ctx, msg, reply);
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[usage("[@user] [permission] [?#channel]")]
+#[example("@-|Maix|#1010 music.manage #music")]
+/// This deny the given permission to the mentioned user, even if one of their roles grants it
+pub async fn deny(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_mention = msg.mentions.first();
+    if user_mention.is_none() {
+        message_err!("You need to mention someone");
     }
+    let user_mention = user_mention.unwrap();
+    args.advance();
+    let permission = args.single::<String>();
+    if permission.is_err() {
+        message_err!("You need to provide a permission to deny!");
+    }
+    let permission = permission.unwrap();
+    let channelid = channel_arg(&mut args)?;
+    crate::shared::ensure_valid_and_manageable(ctx, msg, &permission).await?;
     let lock = ctx.data.read().await;
     let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
-    crate::shared::user_permission::create_permission_if_not_exists(
-        ctx,
-        user_mention.id.0,
-        msg.guild_id.unwrap().0,
-    )
-    .await?;
-    let res = query!("update user_permission set ids = array_distinct(array_append(ids, $3::text)) where userid  = $1::int8 and guildid = $2::int8;",
+    let res = query!("insert into user_permission (userid, guildid, channelid, ids, denied) values ($1::int8, $2::int8, $4::int8, '{}'::text[], ARRAY[$3::text]) on conflict (guildid, userid, channelid) do update set denied = array_distinct(array_append(user_permission.denied, $3::text)), ids = array_diff(user_permission.ids, ARRAY[$3::text]);",
         wh_database::shared::Id(user_mention.id.0) as _,
         wh_database::shared::Id(msg.guild_id.unwrap().0) as _,
-        permission
+        permission,
+        wh_database::shared::Id(channelid.unwrap_or(0)) as _
     ).execute(db).await;
 
     if let Err(e) = &res {
         both_err!(
             "An error occured with the database",
-            format!("Error when granting permission: {}", e)
+            format!("Error when denying permission: {}", e)
         );
     }
+    drop(lock);
+    crate::shared::user_permission::invalidate_user_cache(
+        msg.guild_id.unwrap().0,
+        user_mention.id.0,
+    );
+    // A temporary grant would win over the new deny
+    crate::shared::temporary_permission::remove_temporary_permission(
+        ctx,
+        msg.guild_id.unwrap().0,
+        PermissionTarget::User(user_mention.id.0),
+        channelid,
+        &permission,
+    )
+    .await?;
 
-    reply_message!(ctx, msg, "The permission has been granted");
+    reply_message!(ctx, msg, scoped_reply("The permission has been denied", channelid));
     Ok(())
 }
 
 #[command]
 #[only_in(guilds)]
-#[usage("[@user] [permission]")]
+#[usage("[@user] [permission] [?#channel]")]
 #[example("@-|Maix|#1010 permission.manage")]
-/// This remove the given permission from the user
+/// This remove the given permission from the user, whether it was granted or denied
 pub async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let user_mention = msg.mentions.first();
     if user_mention.is_none() {
@@ -114,38 +182,23 @@ pub async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
         message_err!("You need to provide a permission to remove!");
     }
     let permission = permission.unwrap();
+    let channelid = channel_arg(&mut args)?;
 
-    if !crate::shared::user_permission::static_get_permission().contains(&permission.as_str()) {
-        message_err!("This permission does't exist!");
-    }
-
-    if permission == "permission.manage" {
-        let discord_permission = msg
-            .guild(&ctx.cache)
-            .await
-            .unwrap()
-            .member_permissions(ctx, msg.author.id)
-            .await;
-        if let Err(e) = &discord_permission {
-            both_err!("Internal Error", format!("Internal Error: {}", e));
-        }
-        let discord_permission = discord_permission.unwrap();
-        if !discord_permission.administrator() {
-            message_err!("This permission can only be managed by having the ADMINISTRATOR discord permission")
-        }
-    }
+    crate::shared::ensure_valid_and_manageable(ctx, msg, &permission).await?;
     let lock = ctx.data.read().await;
     let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
     crate::shared::user_permission::create_permission_if_not_exists(
         ctx,
         user_mention.id.0,
         msg.guild_id.unwrap().0,
+        channelid,
     )
     .await?;
-    let res = query!("update user_permission set ids = array_distinct(array_diff(ids, ARRAY[$3::text])) where userid  = $1::int8 and guildid = $2::int8;",
+    let res = query!("update user_permission set ids = array_diff(ids, ARRAY[$3::text]), denied = array_diff(denied, ARRAY[$3::text]) where userid  = $1::int8 and guildid = $2::int8 and channelid = $4::int8;",
         wh_database::shared::Id(user_mention.id.0) as _,
         wh_database::shared::Id(msg.guild_id.unwrap().0) as _,
-        permission
+        permission,
+        wh_database::shared::Id(channelid.unwrap_or(0)) as _
     ).execute(db).await;
 
     if let Err(e) = &res {
@@ -154,7 +207,20 @@ pub async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
             format!("Error when removing permission: {}", e)
         );
     }
-    reply_message!(ctx, msg, "The permission has been removed");
+    drop(lock);
+    crate::shared::user_permission::invalidate_user_cache(
+        msg.guild_id.unwrap().0,
+        user_mention.id.0,
+    );
+    crate::shared::temporary_permission::remove_temporary_permission(
+        ctx,
+        msg.guild_id.unwrap().0,
+        PermissionTarget::User(user_mention.id.0),
+        channelid,
+        &permission,
+    )
+    .await?;
+    reply_message!(ctx, msg, scoped_reply("The permission has been removed", channelid));
     Ok(())
 }
 
@@ -167,59 +233,241 @@ pub async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
 /// This view all the permission that are granted to a user (not showing role given permission)
 pub async fn view(ctx: &Context, msg: &Message) -> CommandResult {
     let usr_mention = msg.mentions.first().unwrap_or(&msg.author);
-    crate::shared::user_permission::create_permission_if_not_exists(
+    let data = crate::shared::user_permission::get_permissions(
         ctx,
         usr_mention.id.0,
         msg.guild_id.unwrap().0,
     )
     .await?;
-    let data = crate::shared::user_permission::get_permission(
+    let temporary = crate::shared::temporary_permission::get_temporary_permissions(
         ctx,
-        usr_mention.id.0,
         msg.guild_id.unwrap().0,
+        PermissionTarget::User(usr_mention.id.0),
     )
     .await?;
-    let data = data.unwrap();
     use serenity::prelude::Mentionable;
 
     reply_message!(
         ctx,
         msg,
         format!(
-            "{} permissions are: {}",
+            "{} permissions are: {}{}",
             usr_mention.mention(),
-            data.ids
-                .iter()
-                .map(|p| format!("`{}` ", p))
-                .collect::<String>()
+            format_permissions(data.iter().map(|d| (
+                d.channelid.map(|c| c.0),
+                &d.ids[..],
+                &d.denied[..]
+            ))),
+            format_temporary(&temporary)
         )
     );
     Ok(())
 }
 
+#[command]
+#[only_in(guilds)]
+#[usage("[@user] [permission] [?#channel]")]
+#[example("@-|Maix|#1010 music.manage")]
+#[min_args(2)]
+#[max_args(3)]
+/// This show every grant and deny that applies to the user for the permission in the channel (this one by default),
+/// in the order they are looked at, and the final decision
+pub async fn explain(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_mention = msg.mentions.first();
+    if user_mention.is_none() {
+        message_err!("You need to mention someone");
+    }
+    let user_mention = user_mention.unwrap();
+    args.advance();
+    let permission = args.single::<String>();
+    if permission.is_err() {
+        message_err!("You need to provide a permission to explain!");
+    }
+    let permission = permission.unwrap();
+    let channelid = channel_arg(&mut args)?.unwrap_or(msg.channel_id.0);
+    if !crate::shared::registry::is_registered(&permission) {
+        message_err!("This permission does't exist!");
+    }
+    let explanation = crate::shared::user_permission::explain_permission(
+        ctx,
+        user_mention.id.0,
+        msg.guild_id.unwrap().0,
+        Some(channelid),
+        &permission,
+    )
+    .await?;
+    use serenity::prelude::Mentionable;
+
+    let mut out = format!(
+        "`{}` for {} in <#{}>:\nDiscord administrator: {}\n",
+        permission,
+        user_mention.mention(),
+        channelid,
+        if explanation.administrator { "yes" } else { "no" }
+    );
+    if explanation.sources.is_empty() {
+        out.push_str("No grant or deny applies\n");
+    }
+    for (i, source) in explanation.sources.iter().enumerate() {
+        out.push_str(&format!("{}. {}\n", i + 1, source));
+    }
+    if let Some(default) = explanation.default {
+        out.push_str(&format!(
+            "Default: {} ({})\n",
+            default,
+            if explanation.default_allows {
+                "applies"
+            } else {
+                "doesn't apply"
+            }
+        ));
+    }
+    out.push_str(&format!(
+        "**Decision: {}** ({})",
+        if explanation.decision.allowed() {
+            "granted"
+        } else {
+            "refused"
+        },
+        explanation.decision
+    ));
+    reply_message!(ctx, msg, out);
+    Ok(())
+}
+
+/// Parse the optional channel (or category) a permission is limited to
+fn channel_arg(args: &mut Args) -> CommandResult<Option<u64>> {
+    match args.single::<String>() {
+        Ok(arg) => match serenity::utils::parse_channel(&arg) {
+            Some(channelid) => Ok(Some(channelid)),
+            None => message_err!("You need to mention a valid channel"),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parse the optional channel (or category) and duration of a grant, in any order
+fn grant_args(args: &mut Args) -> CommandResult<(Option<u64>, Option<chrono::Duration>)> {
+    let mut channelid = None;
+    let mut duration = None;
+    while let Ok(arg) = args.single::<String>() {
+        if let Some(c) = serenity::utils::parse_channel(&arg) {
+            channelid = Some(c);
+        } else if let Some(d) = crate::shared::temporary_permission::parse_duration(&arg) {
+            duration = Some(d);
+        } else {
+            message_err!(format!(
+                "`{}` is neither a channel nor a duration like `30m`, `12h` or `1w2d`",
+                arg
+            ));
+        }
+    }
+    Ok((channelid, duration))
+}
+
+/// Record a grant that expires after `duration` and return the reply to send
+async fn grant_temporary(
+    ctx: &Context,
+    msg: &Message,
+    target: PermissionTarget,
+    channelid: Option<u64>,
+    permission: &str,
+    duration: chrono::Duration,
+) -> CommandResult<String> {
+    let expires_at = chrono::Utc::now() + duration;
+    crate::shared::temporary_permission::add_temporary_permission(
+        ctx,
+        msg.guild_id.unwrap().0,
+        target,
+        channelid,
+        permission,
+        msg.author.id.0,
+        expires_at,
+    )
+    .await?;
+    Ok(format!(
+        "{}, it expires <t:{}:R>",
+        scoped_reply("The permission has been granted", channelid),
+        expires_at.timestamp()
+    ))
+}
+
+fn scoped_reply(message: &str, channelid: Option<u64>) -> String {
+    match channelid {
+        Some(channelid) => format!("{} in <#{}>", message, channelid),
+        None => message.to_string(),
+    }
+}
+
+/// Format the permissions of each channel, the guild wide ones first
+fn format_permissions<'a>(
+    entries: impl Iterator<Item = (Option<u64>, &'a [String], &'a [String])>,
+) -> String {
+    let list = |ids: &[String]| ids.iter().map(|p| format!("`{}` ", p)).collect::<String>();
+    let mut out = String::new();
+    for (channelid, ids, denied) in entries {
+        if ids.is_empty() && denied.is_empty() {
+            continue;
+        }
+        let place = match channelid {
+            Some(channelid) => format!("In <#{}>", channelid),
+            None => String::from("In the guild"),
+        };
+        out.push_str(&format!(
+            "\n{}: {}\nDenied: {}",
+            place,
+            list(ids),
+            list(denied)
+        ));
+    }
+    out
+}
+
+/// Format the temporary grants that haven't expired yet
+fn format_temporary(
+    temporary: &[crate::shared::temporary_permission::TemporaryPermission],
+) -> String {
+    let mut out = String::new();
+    for t in temporary {
+        out.push_str(&format!(
+            "\nTemporary: `{}`{} until <t:{}:R>",
+            t.permission,
+            t.channelid
+                .map(|c| format!(" in <#{}>", c.0))
+                .unwrap_or_default(),
+            t.expires_at.timestamp()
+        ));
+    }
+    out
+}
+
 mod role {
+    use super::{channel_arg, grant_args, grant_temporary, scoped_reply};
+    use crate::shared::temporary_permission::PermissionTarget;
     use serenity::client::Context;
     use serenity::framework::standard::{macros::*, Args, CommandResult};
     use serenity::model::channel::Message;
 
     #[command]
-    #[sub_commands(grant, remove, view)]
+    #[sub_commands(grant, deny, remove, view)]
     /// This is a top level command that manage roles' permissions
     pub async fn role(ctx: &Context, msg: &Message) -> CommandResult {
         reply_message!(
             ctx,
             msg,
-            "This command is separated into sub commands: `grant`, `remove`, `view`"
+            "This command is separated into sub commands: `grant`, `deny`, `remove`, `view`"
         );
         Ok(())
     }
 
     #[command]
     #[only_in(guilds)]
-    #[usage("[@role] [permission]")]
+    #[usage("[@role] [permission] [?#channel] [?duration]")]
     #[example("@role permission.manage")]
-    #[num_args(2)]
-    /// This grant a permission to the mentioned role
+    #[example("@role music.manage #music 1d")]
+    #[min_args(2)]
+    #[max_args(4)]
+    /// This grant a permission to the mentioned role, for the given duration if there is one
     pub async fn grant(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
         let role_mention = msg.mention_roles.first();
         if role_mention.is_none() {
@@ -232,55 +480,104 @@ mod role {
             message_err!("You need to provide a permission to give!");
         }
         let permission = permission.unwrap();
-        if !crate::shared::user_permission::static_get_permission().contains(&permission.as_str()) {
-            message_err!("This permission does't exist!");
+        let (channelid, duration) = grant_args(&mut args)?;
+        crate::shared::ensure_valid_and_manageable(ctx, msg, &permission).await?;
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let res = query!("INSERT INTO role_permission (roleid, guildid, channelid, ids) VALUES ($1::int8, $2::int8, $4::int8, CASE WHEN $5::bool THEN '{}'::text[] ELSE ARRAY[$3::text] END) ON CONFLICT (guildid, roleid, channelid) DO UPDATE SET ids = CASE WHEN $5::bool THEN role_permission.ids ELSE array_distinct(array_append(role_permission.ids, $3::text)) END, denied = CASE WHEN $5::bool THEN role_permission.denied ELSE array_diff(role_permission.denied, ARRAY[$3::text]) END;",
+        wh_database::shared::Id(role_mention.0) as _,
+        wh_database::shared::Id(msg.guild_id.unwrap().0) as _,
+        permission,
+        wh_database::shared::Id(channelid.unwrap_or(0)) as _,
+        duration.is_some()
+    ).execute(db).await;
+
+        if let Err(e) = &res {
+            both_err!(
+                "An error occured with the database",
+                format!("Error when granting permission: {}", e)
+            );
         }
-        if permission == "permission.manage" {
-            let discord_permission = msg
-                .guild(&ctx.cache)
-                .await
-                .unwrap()
-                .member_permissions(ctx, msg.author.id)
-                .await;
-            if let Err(e) = &discord_permission {
-                both_err!("Internal Error", format!("Internal Error: {}", e));
-            }
-            let discord_permission = discord_permission.unwrap();
-            if !discord_permission.administrator() {
-                message_err!("This permission can only be managed by having the ADMINISTRATOR discord permission")
+        drop(lock);
+        crate::shared::role_permission::invalidate_role_cache(msg.guild_id.unwrap().0);
+
+        let reply = match duration {
+            Some(duration) => {
+                grant_temporary(
+                    ctx,
+                    msg,
+                    PermissionTarget::Role(role_mention.0),
+                    channelid,
+                    &permission,
+                    duration,
+                )
+                .await?
             }
+            None => scoped_reply("The permission has been granted", channelid),
+        };
+        reply_message!(ctx, msg, reply);
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[@role] [permission] [?#channel]")]
+    #[example("@role music.manage #music")]
+    #[min_args(2)]
+    #[max_args(3)]
+    /// This deny a permission to the mentioned role, it wins over the grants of the other roles
+    pub async fn deny(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let role_mention = msg.mention_roles.first();
+        if role_mention.is_none() {
+            message_err!("You need to mention a role");
+        }
+        let role_mention = role_mention.unwrap();
+        args.advance();
+        let permission = args.single::<String>();
+        if permission.is_err() {
+            message_err!("You need to provide a permission to deny!");
         }
+        let permission = permission.unwrap();
+        let channelid = channel_arg(&mut args)?;
+        crate::shared::ensure_valid_and_manageable(ctx, msg, &permission).await?;
         let lock = ctx.data.read().await;
         let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
-        crate::shared::role_permission::create_role_permission_if_not_exist(
-            ctx,
-            role_mention.0,
-            msg.guild_id.unwrap().0,
-        )
-        .await?;
-        let res = query!("UPDATE role_permission SET ids = array_distinct(array_append(ids, $3::text)) WHERE roleid = $1::int8 AND guildid = $2::int8;",
+        let res = query!("INSERT INTO role_permission (roleid, guildid, channelid, ids, denied) VALUES ($1::int8, $2::int8, $4::int8, '{}'::text[], ARRAY[$3::text]) ON CONFLICT (guildid, roleid, channelid) DO UPDATE SET denied = array_distinct(array_append(role_permission.denied, $3::text)), ids = array_diff(role_permission.ids, ARRAY[$3::text]);",
         wh_database::shared::Id(role_mention.0) as _,
         wh_database::shared::Id(msg.guild_id.unwrap().0) as _,
-        permission
+        permission,
+        wh_database::shared::Id(channelid.unwrap_or(0)) as _
     ).execute(db).await;
 
         if let Err(e) = &res {
             both_err!(
                 "An error occured with the database",
-                format!("Error when granting permission: {}", e)
+                format!("Error when denying permission: {}", e)
             );
         }
+        drop(lock);
+        // A temporary grant would win over the new deny
+        crate::shared::temporary_permission::remove_temporary_permission(
+            ctx,
+            msg.guild_id.unwrap().0,
+            PermissionTarget::Role(role_mention.0),
+            channelid,
+            &permission,
+        )
+        .await?;
+        crate::shared::role_permission::invalidate_role_cache(msg.guild_id.unwrap().0);
 
-        reply_message!(ctx, msg, "The permission has been granted");
+        reply_message!(ctx, msg, scoped_reply("The permission has been denied", channelid));
         Ok(())
     }
 
     #[command]
     #[only_in(guilds)]
-    #[usage("[@role] [permission]")]
+    #[usage("[@role] [permission] [?#channel]")]
     #[example("@role permission.manage")]
-    #[num_args(2)]
-    /// This remove the given permission from the mentioned role
+    #[min_args(2)]
+    #[max_args(3)]
+    /// This remove the given permission from the mentioned role, whether it was granted or denied
     pub async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
         let role_mention = msg.mention_roles.first();
         if role_mention.is_none() {
@@ -293,36 +590,22 @@ mod role {
             message_err!("You need to provide a permission to give!");
         }
         let permission = permission.unwrap();
-        if !crate::shared::user_permission::static_get_permission().contains(&permission.as_str()) {
-            message_err!("This permission does't exist!");
-        }
-        if permission == "permission.manage" {
-            let discord_permission = msg
-                .guild(&ctx.cache)
-                .await
-                .unwrap()
-                .member_permissions(ctx, msg.author.id)
-                .await;
-            if let Err(e) = &discord_permission {
-                both_err!("Internal Error", format!("Internal Error: {}", e));
-            }
-            let discord_permission = discord_permission.unwrap();
-            if !discord_permission.administrator() {
-                message_err!("This permission can only be managed by having the ADMINISTRATOR discord permission")
-            }
-        }
+        let channelid = channel_arg(&mut args)?;
+        crate::shared::ensure_valid_and_manageable(ctx, msg, &permission).await?;
         let lock = ctx.data.read().await;
         let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
         crate::shared::role_permission::create_role_permission_if_not_exist(
             ctx,
             role_mention.0,
             msg.guild_id.unwrap().0,
+            channelid,
         )
         .await?;
-        let res = query!("UPDATE role_permission SET ids = array_distinct(array_diff(ids, ARRAY[$3::text])) WHERE roleid = $1::int8 AND guildid = $2::int8;",
+        let res = query!("UPDATE role_permission SET ids = array_diff(ids, ARRAY[$3::text]), denied = array_diff(denied, ARRAY[$3::text]) WHERE roleid = $1::int8 AND guildid = $2::int8 AND channelid = $4::int8;",
         wh_database::shared::Id(role_mention.0) as _,
         wh_database::shared::Id(msg.guild_id.unwrap().0) as _,
-        permission
+        permission,
+        wh_database::shared::Id(channelid.unwrap_or(0)) as _
     ).execute(db).await;
 
         if let Err(e) = &res {
@@ -331,8 +614,18 @@ mod role {
                 format!("Error when granting permission: {}", e)
             );
         }
+        drop(lock);
+        crate::shared::temporary_permission::remove_temporary_permission(
+            ctx,
+            msg.guild_id.unwrap().0,
+            PermissionTarget::Role(role_mention.0),
+            channelid,
+            &permission,
+        )
+        .await?;
+        crate::shared::role_permission::invalidate_role_cache(msg.guild_id.unwrap().0);
 
-        reply_message!(ctx, msg, "The permission has been granted");
+        reply_message!(ctx, msg, scoped_reply("The permission has been removed", channelid));
         Ok(())
     }
 
@@ -348,33 +641,309 @@ mod role {
             message_err!("You need to mention a role!");
         }
         let role_mention = role_mention.unwrap();
-        crate::shared::role_permission::create_role_permission_if_not_exist(
+        let data = crate::shared::role_permission::get_role_permissions(
             ctx,
             role_mention.0,
             msg.guild_id.unwrap().0,
         )
         .await?;
-        let data = crate::shared::role_permission::get_role_permission(
+        let temporary = crate::shared::temporary_permission::get_temporary_permissions(
             ctx,
-            role_mention.0,
             msg.guild_id.unwrap().0,
+            PermissionTarget::Role(role_mention.0),
         )
         .await?;
-        let data = data.unwrap();
         use serenity::prelude::Mentionable;
 
         reply_message!(
             ctx,
             msg,
             format!(
-                "{} permissions are: {}",
+                "{} permissions are: {}{}",
                 role_mention.mention(),
-                data.ids
+                super::format_permissions(data.iter().map(|d| (
+                    d.channelid.map(|c| c.0),
+                    &d.ids[..],
+                    &d.denied[..]
+                ))),
+                super::format_temporary(&temporary)
+            )
+        );
+        Ok(())
+    }
+}
+
+mod group {
+    use crate::shared::permission_group::{get_group, get_groups, is_valid_group_name};
+    use serenity::client::Context;
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+
+    #[command]
+    #[sub_commands(set, add, remove, delete, list)]
+    /// This is a top level command that manage the permission groups, a group is granted like a permission with `group:name`
+    pub async fn group(ctx: &Context, msg: &Message) -> CommandResult {
+        reply_message!(
+            ctx,
+            msg,
+            "This command is separated into sub commands: `set`, `add`, `remove`, `delete`, `list`"
+        );
+        Ok(())
+    }
+
+    fn name_arg(args: &mut Args) -> CommandResult<String> {
+        match args.single::<String>() {
+            Ok(name) if is_valid_group_name(&name) => Ok(name),
+            _ => message_err!("You need to provide a group name (lowercase letters, digits, `-` and `_`, 32 characters max)"),
+        }
+    }
+
+    /// Groups can't contain other groups
+    async fn ensure_group_permission(
+        ctx: &Context,
+        msg: &Message,
+        permission: &str,
+    ) -> CommandResult {
+        if crate::shared::permission_group::group_name(permission).is_some() {
+            message_err!("A group can't contain another group!");
+        }
+        crate::shared::ensure_valid_and_manageable(ctx, msg, permission).await
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[name] [permissions...]")]
+    #[example("moderator points.manage music.manage")]
+    #[min_args(2)]
+    /// This create the group or replace its permissions, everyone holding it gets the new ones
+    pub async fn set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let name = name_arg(&mut args)?;
+        let mut permissions = Vec::new();
+        for permission in args.iter::<String>().flatten() {
+            ensure_group_permission(ctx, msg, &permission).await?;
+            if !permissions.contains(&permission) {
+                permissions.push(permission);
+            }
+        }
+        let guildid = msg.guild_id.unwrap().0;
+        if let Some(old) = get_group(ctx, guildid, &name).await? {
+            for permission in &old.permissions {
+                crate::shared::ensure_can_manage(ctx, msg, permission).await?;
+            }
+        }
+        crate::shared::permission_group::set_group(ctx, guildid, &name, &permissions).await?;
+        reply_message!(ctx, msg, format!("The group `{}` has been set", name));
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[name] [permission]")]
+    #[example("moderator config.manage")]
+    #[num_args(2)]
+    /// This add a permission to the group
+    pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let name = name_arg(&mut args)?;
+        let permission = args.single::<String>()?;
+        ensure_group_permission(ctx, msg, &permission).await?;
+        let guildid = msg.guild_id.unwrap().0;
+        let group = get_group(ctx, guildid, &name).await?;
+        if group.is_none() {
+            message_err!("This permission group doesn't exist!");
+        }
+        let mut permissions = group.unwrap().permissions;
+        if !permissions.contains(&permission) {
+            permissions.push(permission);
+        }
+        crate::shared::permission_group::set_group(ctx, guildid, &name, &permissions).await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!("The permission has been added to the group `{}`", name)
+        );
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[name] [permission]")]
+    #[example("moderator config.manage")]
+    #[num_args(2)]
+    /// This remove a permission from the group
+    pub async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let name = name_arg(&mut args)?;
+        let permission = args.single::<String>()?;
+        crate::shared::ensure_can_manage(ctx, msg, &permission).await?;
+        let guildid = msg.guild_id.unwrap().0;
+        let group = get_group(ctx, guildid, &name).await?;
+        if group.is_none() {
+            message_err!("This permission group doesn't exist!");
+        }
+        let mut permissions = group.unwrap().permissions;
+        permissions.retain(|p| p != &permission);
+        crate::shared::permission_group::set_group(ctx, guildid, &name, &permissions).await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!("The permission has been removed from the group `{}`", name)
+        );
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[name]")]
+    #[example("moderator")]
+    #[num_args(1)]
+    /// This delete the group and take it back from every user and role holding it
+    pub async fn delete(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let name = name_arg(&mut args)?;
+        let guildid = msg.guild_id.unwrap().0;
+        if let Some(group) = get_group(ctx, guildid, &name).await? {
+            for permission in &group.permissions {
+                crate::shared::ensure_can_manage(ctx, msg, permission).await?;
+            }
+        }
+        if !crate::shared::permission_group::delete_group(ctx, guildid, &name).await? {
+            message_err!("This permission group doesn't exist!");
+        }
+        reply_message!(ctx, msg, format!("The group `{}` has been deleted", name));
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("")]
+    #[example("")]
+    /// This list the groups of the guild and their permissions
+    pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+        let groups = get_groups(ctx, msg.guild_id.unwrap().0).await?;
+        if groups.is_empty() {
+            message_err!("This guild doesn't have any permission group");
+        }
+        let mut out = String::from("Permission groups are:");
+        for group in groups {
+            out.push_str(&format!(
+                "\n`group:{}`: {}",
+                group.name,
+                group
+                    .permissions
                     .iter()
                     .map(|p| format!("`{}` ", p))
                     .collect::<String>()
-            )
+            ));
+        }
+        reply_message!(ctx, msg, out);
+        Ok(())
+    }
+}
+
+mod command {
+    use crate::shared::command_binding::{resolve_command, CommandRequirement};
+    use serenity::client::Context;
+    use serenity::framework::standard::{macros::*, Args, CommandResult};
+    use serenity::model::channel::Message;
+
+    #[command]
+    #[sub_commands(set, reset, list)]
+    /// This is a top level command that manage the permission required by each command in this guild
+    pub async fn command(ctx: &Context, msg: &Message) -> CommandResult {
+        reply_message!(
+            ctx,
+            msg,
+            "This command is separated into sub commands: `set`, `reset`, `list`"
+        );
+        Ok(())
+    }
+
+    /// Parse the rest of the arguments as a command path, the `permission` commands can't be changed
+    fn command_arg(args: &Args) -> CommandResult<String> {
+        let command = resolve_command(args.rest().split_whitespace());
+        if command.is_none() {
+            message_err!("You need to provide a valid command!");
+        }
+        let command = command.unwrap();
+        // Or the guild could lock itself out of the permissions
+        if command == "permission" || command.starts_with("permission ") {
+            message_err!("The permission commands can't be changed!");
+        }
+        Ok(command)
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[permission|everyone] [command]")]
+    #[example("music.manage playlist delete")]
+    #[example("everyone skip")]
+    #[min_args(2)]
+    /// This make the command (and its sub commands) require the permission instead of its default checks, or open it to everyone
+    pub async fn set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        let requirement = match args.single::<String>()?.as_str() {
+            "everyone" => CommandRequirement::Everyone,
+            permission if crate::shared::registry::is_registered(permission) => {
+                CommandRequirement::Permission(permission.to_string())
+            }
+            _ => message_err!("This permission does't exist!"),
+        };
+        let command = command_arg(&args)?;
+        crate::shared::command_binding::set_command_requirement(
+            ctx,
+            msg.guild_id.unwrap().0,
+            &command,
+            &requirement,
+        )
+        .await?;
+        reply_message!(
+            ctx,
+            msg,
+            format!("`{}` can now be used by {}", command, requirement)
+        );
+        Ok(())
+    }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("[command]")]
+    #[example("playlist delete")]
+    #[min_args(1)]
+    /// This make the command use its default checks again
+    pub async fn reset(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+        let command = command_arg(&args)?;
+        let removed = crate::shared::command_binding::remove_command_requirement(
+            ctx,
+            msg.guild_id.unwrap().0,
+            &command,
+        )
+        .await?;
+        if !removed {
+            message_err!("This command already uses its default checks");
+        }
+        reply_message!(
+            ctx,
+            msg,
+            format!("`{}` uses its default checks again", command)
         );
         Ok(())
     }
+
+    #[command]
+    #[only_in(guilds)]
+    #[usage("")]
+    #[example("")]
+    /// This list the commands whose requirement has been changed in this guild
+    pub async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+        let bindings =
+            crate::shared::command_binding::get_bindings(ctx, msg.guild_id.unwrap().0).await?;
+        if bindings.is_empty() {
+            message_err!("Every command uses its default checks in this guild");
+        }
+        let mut bindings = bindings.iter().collect::<Vec<_>>();
+        bindings.sort_by_key(|(command, _)| *command);
+        let mut out = String::from("Changed commands are:");
+        for (command, requirement) in bindings {
+            out.push_str(&format!("\n`{}`: {}", command, requirement));
+        }
+        reply_message!(ctx, msg, out);
+        Ok(())
+    }
 }
@@ -0,0 +1,63 @@
+use serenity::{
+    client::Context,
+    model::{
+        gateway::Ready,
+        guild::Role,
+        id::{GuildId, RoleId},
+    },
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `ready` is sent again after a reconnection, the background tasks must only be started once
+static TASKS_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub struct PermissionEventHandler;
+
+#[serenity::async_trait]
+impl serenity::client::EventHandler for PermissionEventHandler {
+    async fn ready(&self, ctx: Context, _: Ready) {
+        if TASKS_STARTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(crate::shared::notify::listen_permission_changes(
+            ctx.clone(),
+        ));
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(crate::shared::temporary_permission::CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) =
+                    crate::shared::temporary_permission::expire_temporary_permissions(&ctx).await
+                {
+                    error!("Couldn't remove the expired permissions: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn guild_role_delete(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        removed_role_id: RoleId,
+        _: Option<Role>,
+    ) {
+        if let Err(e) = crate::shared::role_permission::delete_role_permissions(
+            &ctx,
+            guild_id.0,
+            removed_role_id.0,
+        )
+        .await
+        {
+            error!(
+                "Couldn't remove the permissions of the deleted role {}: {}",
+                removed_role_id, e
+            );
+        }
+    }
+
+    async fn guild_role_update(&self, _: Context, guild_id: GuildId, _: Option<Role>, _: Role) {
+        crate::shared::role_permission::invalidate_role_cache(guild_id.0);
+    }
+}
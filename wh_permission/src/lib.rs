@@ -5,10 +5,15 @@ extern crate wh_database;
 extern crate sqlx;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate fluent_const;
+extern crate chrono;
 extern crate lru;
 extern crate once_cell;
 extern crate parking_lot;
+extern crate tokio;
 
 mod commands;
+mod event_handler;
 pub mod module;
 pub mod shared;
@@ -10,9 +10,8 @@ pub static MODULE_DECLARATION: wh_core::ModuleDeclaration = wh_core::ModuleDecla
 
 async fn register_typemap(_: &mut serenity::prelude::TypeMap) {}
 
-async fn register_event_handler(
-    _: &mut wh_core::event_handler::WhEventHandlerManager,
-) {
+async fn register_event_handler(eh: &mut wh_core::event_handler::WhEventHandlerManager) {
+    eh.push(crate::event_handler::PermissionEventHandler);
 }
 
 fn register_builder(
@@ -25,9 +24,7 @@ fn register_intent(
     intent: serenity::client::bridge::gateway::GatewayIntents,
 ) -> serenity::client::bridge::gateway::GatewayIntents {
     use serenity::client::bridge::gateway::GatewayIntents as I;
-    intent | I::GUILD_MESSAGES
+    intent | I::GUILD_MESSAGES | I::GUILDS
 }
 
-fn register_init() {
-    crate::shared::user_permission::add_permission(&["permission.manage"]);
-}
+fn register_init() {}
@@ -0,0 +1,280 @@
+use once_cell::sync::{Lazy, OnceCell};
+use serenity::{
+    client::Context,
+    framework::standard::{Command, CommandGroup, CommandResult},
+    model::channel::Message,
+};
+use std::{collections::HashMap, sync::Arc};
+use wh_database::shared::{DatabaseKey, Id};
+
+const CACHE_SIZE: usize = 100;
+
+/// What a guild requires to use a command instead of its default checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandRequirement {
+    Permission(String),
+    /// Opened to everyone, even if the command needs a permission by default
+    Everyone,
+}
+
+impl std::fmt::Display for CommandRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandRequirement::Permission(permission) => write!(f, "`{}`", permission),
+            CommandRequirement::Everyone => write!(f, "everyone"),
+        }
+    }
+}
+
+/// The prefix of the bot and every top level command, set once by [`register_commands`]
+static COMMANDS: OnceCell<(&'static str, Vec<&'static Command>)> = OnceCell::new();
+
+type BindingCache = parking_lot::Mutex<
+    lru::LruCache<u64 /*guildid*/, Arc<HashMap<String /*command*/, CommandRequirement>>>,
+>;
+
+static BINDING_CACHE: Lazy<BindingCache> =
+    Lazy::new(|| parking_lot::Mutex::new(lru::LruCache::new(CACHE_SIZE)));
+
+/// Give the command groups of every module so the command a message calls can be found
+pub fn register_commands(prefix: &'static str, groups: &[&'static CommandGroup]) {
+    fn walk(group: &'static CommandGroup, out: &mut Vec<&'static Command>) {
+        out.extend(group.options.commands);
+        for sub_group in group.options.sub_groups {
+            walk(sub_group, out);
+        }
+    }
+    let mut commands = Vec::new();
+    for group in groups {
+        walk(group, &mut commands);
+    }
+    if COMMANDS.set((prefix, commands)).is_err() {
+        warn!("The commands have already been registered");
+    }
+}
+
+/// The full path of the command named by `words` using the main name of each command, like `playlist delete`.
+/// The words after the last (sub) command are ignored
+pub fn resolve_command<'a>(words: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let (_, commands) = COMMANDS.get()?;
+    let mut level: &[&'static Command] = commands;
+    let mut path: Vec<&'static str> = Vec::new();
+    for word in words {
+        let found = level.iter().find(|c| {
+            c.options
+                .names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(word))
+        });
+        match found {
+            Some(command) => {
+                path.push(command.options.names[0]);
+                level = command.options.sub_commands;
+            }
+            None => break,
+        }
+    }
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.join(" "))
+    }
+}
+
+/// The path of the command the message calls, see [`resolve_command`]
+pub fn message_command(msg: &Message) -> Option<String> {
+    let (prefix, _) = COMMANDS.get()?;
+    if !msg
+        .content
+        .get(..prefix.len())?
+        .eq_ignore_ascii_case(prefix)
+    {
+        return None;
+    }
+    let content = &msg.content[prefix.len()..];
+    resolve_command(content.split_whitespace())
+}
+
+/// Forget the cached command bindings of the guild
+pub fn invalidate_binding_cache(guildid: u64) {
+    super::notify::bump_generation();
+    BINDING_CACHE.lock().pop(&guildid);
+}
+
+pub(crate) fn clear_binding_cache() {
+    super::notify::bump_generation();
+    BINDING_CACHE.lock().clear();
+}
+
+/// Every command of the guild with a requirement
+pub async fn get_bindings(
+    ctx: &Context,
+    guildid: u64,
+) -> Result<Arc<HashMap<String, CommandRequirement>>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(bindings) = BINDING_CACHE.lock().get(&guildid) {
+        return Ok(bindings.clone());
+    }
+    let generation = super::notify::generation();
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let rows = query!(
+        "SELECT command, permission FROM command_permission WHERE guildid = $1::int8",
+        Id(guildid) as _,
+    )
+    .fetch_all(db)
+    .await?;
+    let bindings = Arc::new(
+        rows.into_iter()
+            .map(|r| {
+                let requirement = match r.permission {
+                    Some(permission) => CommandRequirement::Permission(permission),
+                    None => CommandRequirement::Everyone,
+                };
+                (r.command, requirement)
+            })
+            .collect::<HashMap<_, _>>(),
+    );
+    // Don't keep what was read if the bindings changed in the meantime
+    if generation == super::notify::generation() {
+        BINDING_CACHE.lock().put(guildid, bindings.clone());
+    }
+    Ok(bindings)
+}
+
+/// The requirement of the command, or the one of its closest parent command if it has none.
+/// A requirement on `playlist` applies to `playlist delete`
+pub async fn command_requirement(
+    ctx: &Context,
+    guildid: u64,
+    command: &str,
+) -> Result<Option<CommandRequirement>, Box<dyn std::error::Error + Send + Sync>> {
+    let bindings = get_bindings(ctx, guildid).await?;
+    let mut path = command;
+    loop {
+        if let Some(requirement) = bindings.get(path) {
+            return Ok(Some(requirement.clone()));
+        }
+        match path.rsplit_once(' ') {
+            Some((parent, _)) => path = parent,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Set what the guild requires to use the command
+pub async fn set_command_requirement(
+    ctx: &Context,
+    guildid: u64,
+    command: &str,
+    requirement: &CommandRequirement,
+) -> CommandResult {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let permission = match requirement {
+        CommandRequirement::Permission(permission) => Some(permission.as_str()),
+        CommandRequirement::Everyone => None,
+    };
+    query!(
+        "INSERT INTO command_permission (guildid, command, permission) VALUES ($1::int8, $2::text, $3::text) ON CONFLICT (guildid, command) DO UPDATE SET permission = EXCLUDED.permission",
+        Id(guildid) as _,
+        command,
+        permission as _,
+    )
+    .execute(db)
+    .await?;
+    invalidate_binding_cache(guildid);
+    Ok(())
+}
+
+/// Go back to the default checks of the command, returns false if it had no requirement
+pub async fn remove_command_requirement(
+    ctx: &Context,
+    guildid: u64,
+    command: &str,
+) -> CommandResult<bool> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let removed = query!(
+        "DELETE FROM command_permission WHERE guildid = $1::int8 AND command = $2::text",
+        Id(guildid) as _,
+        command,
+    )
+    .execute(db)
+    .await?
+    .rows_affected()
+        > 0;
+    invalidate_binding_cache(guildid);
+    Ok(removed)
+}
+
+/// The requirement the guild set for the command the message calls, if there is one.
+/// The default permission checks let it through in that case, it is enforced by [`enforce_command_requirement`] instead
+pub async fn message_requirement(
+    ctx: &Context,
+    msg: &Message,
+) -> Result<Option<CommandRequirement>, Box<dyn std::error::Error + Send + Sync>> {
+    match (msg.guild_id, message_command(msg)) {
+        (Some(guildid), Some(command)) => command_requirement(ctx, guildid.0, &command).await,
+        _ => Ok(None),
+    }
+}
+
+/// The permission the user lacks to use the command in the channel, `None` if they can use it.
+/// For what does the work of a command without a message, like buttons
+pub async fn missing_requirement(
+    ctx: &Context,
+    guildid: u64,
+    userid: u64,
+    channelid: u64,
+    command: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let permission = match command_requirement(ctx, guildid, command).await? {
+        Some(CommandRequirement::Permission(permission)) => permission,
+        _ => return Ok(None),
+    };
+    match super::user_permission::has_permission(ctx, userid, guildid, Some(channelid), &permission)
+        .await
+    {
+        Ok(true) => Ok(None),
+        Ok(false) => Ok(Some(permission)),
+        Err(e) => Err(format!("{:?}", e).into()),
+    }
+}
+
+/// Meant for the framework's before hook, returns false and tells the user if they can't use the command
+pub async fn enforce_command_requirement(ctx: &Context, msg: &Message) -> bool {
+    let permission = match message_requirement(ctx, msg).await {
+        Ok(Some(CommandRequirement::Permission(permission))) => permission,
+        Ok(_) => return true,
+        Err(e) => {
+            error!("Error when fetching the command permissions: {}", e);
+            return false;
+        }
+    };
+    let allowed = super::user_permission::has_permission(
+        ctx,
+        msg.author.id.0,
+        msg.guild_id.unwrap().0,
+        Some(msg.channel_id.0),
+        &permission,
+    )
+    .await;
+    match allowed {
+        Ok(true) => true,
+        Ok(false) => {
+            reply_message!(
+                ctx,
+                msg,
+                format!(
+                    "❌You don't have the permission `{}` required to use this command",
+                    permission
+                )
+            );
+            false
+        }
+        Err(e) => {
+            error!("Error when checking the command permission: {:?}", e);
+            false
+        }
+    }
+}
@@ -1,9 +1,38 @@
+pub mod command_binding;
+pub mod notify;
+pub mod permission_group;
+pub mod registry;
 pub mod role_permission;
+pub mod temporary_permission;
 pub mod user_permission;
 
+/// Register a permission at link time, it shows up in `permission list` without any call at startup.
+/// The description should come from the fluent file, a permission registered without one gets the `Nobody` default
+#[macro_export]
+macro_rules! register_permission {
+    ($permission:literal) => {
+        $crate::register_permission!(
+            $permission,
+            "",
+            $crate::shared::registry::DefaultPolicy::Nobody
+        );
+    };
+    ($permission:literal, $description:expr, $default:expr) => {
+        $crate::shared::registry::inventory::submit! {
+            $crate::shared::registry::PermissionInfo {
+                name: $permission,
+                module: env!("CARGO_PKG_NAME"),
+                description: $description,
+                default: $default,
+            }
+        }
+    };
+}
+
+/// Declare a check requiring the permission and register the permission, see [`register_permission!`]
 #[macro_export]
 macro_rules! check_permission {
-    ($struct_name:ident, $permission:literal) => {
+    ($struct_name:ident, $permission:literal $(, $description:expr, $default:expr)?) => {
         const $struct_name: serenity::framework::standard::Check =
             serenity::framework::standard::Check {
                 function: |ctx, msg, _, _| {
@@ -13,5 +42,183 @@ macro_rules! check_permission {
                 display_in_help: true,
                 check_in_help: true,
             };
+        $crate::register_permission!($permission $(, $description, $default)?);
     };
 }
+
+/// Permissions are dotted paths, granting `music.*` implies every permission starting with `music.` and `*` implies every permission
+pub const WILDCARD: &str = "*";
+
+/// Returns the permission itself followed by every wildcard that implies it, from the narrowest to the widest.
+/// `music.queue.clear` gives `["music.queue.clear", "music.queue.*", "music.*", "*"]`
+pub fn permission_ancestors(permission: &str) -> Vec<String> {
+    let mut out = vec![permission.to_string()];
+    let mut rest = permission;
+    while let Some((parent, _)) = rest.rsplit_once('.') {
+        out.push(format!("{}.{}", parent, WILDCARD));
+        rest = parent;
+    }
+    if permission != WILDCARD {
+        out.push(WILDCARD.to_string());
+    }
+    out
+}
+
+/// Returns true if granting `granted` implies `permission`
+pub fn permission_implies(granted: &str, permission: &str) -> bool {
+    permission_ancestors(permission)
+        .iter()
+        .any(|p| p == granted)
+}
+
+/// Returns true if `permission` is a registered permission or a wildcard covering at least one of them
+pub fn is_valid_permission(permission: &str) -> bool {
+    if permission == WILDCARD {
+        return true;
+    }
+    match permission.strip_suffix(".*") {
+        Some(prefix) => registry::registered_permissions()
+            .iter()
+            .any(|p| p.name.starts_with(prefix) && p.name[prefix.len()..].starts_with('.')),
+        None => registry::is_registered(permission),
+    }
+}
+
+/// Make sure `permission` is a valid permission or an existing group (see [`permission_group`]) the author can manage
+pub async fn ensure_valid_and_manageable(
+    ctx: &serenity::client::Context,
+    msg: &serenity::model::channel::Message,
+    permission: &str,
+) -> serenity::framework::standard::CommandResult {
+    match permission_group::group_name(permission) {
+        Some(name) => {
+            let group = permission_group::get_group(ctx, msg.guild_id.unwrap().0, name).await?;
+            match group {
+                Some(group) => {
+                    for permission in &group.permissions {
+                        ensure_can_manage(ctx, msg, permission).await?;
+                    }
+                }
+                None => message_err!("This permission group doesn't exist!"),
+            }
+        }
+        None => {
+            if !is_valid_permission(permission) {
+                message_err!("This permission does't exist!");
+            }
+            ensure_can_manage(ctx, msg, permission).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Only Discord administrators can grant, deny or remove a permission that implies `permission.manage`
+pub async fn ensure_can_manage(
+    ctx: &serenity::client::Context,
+    msg: &serenity::model::channel::Message,
+    permission: &str,
+) -> serenity::framework::standard::CommandResult {
+    if permission_implies(permission, "permission.manage") {
+        let discord_permission = msg
+            .guild(&ctx.cache)
+            .await
+            .unwrap()
+            .member_permissions(ctx, msg.author.id)
+            .await;
+        if let Err(e) = &discord_permission {
+            both_err!("Internal Error", format!("Internal Error: {}", e));
+        }
+        let discord_permission = discord_permission.unwrap();
+        if !discord_permission.administrator() {
+            message_err!("This permission can only be managed by having the ADMINISTRATOR discord permission")
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct PermissionNode<'a> {
+    registered: bool,
+    children: std::collections::BTreeMap<&'a str, PermissionNode<'a>>,
+}
+
+/// Render the given permissions as a tree, every branch is shown with the wildcard that grants it
+/// ```text
+/// *
+/// ├── music.*
+/// │   └── music.manage
+/// └── points.*
+///     └── points.manage
+/// ```
+pub fn permission_tree(permissions: &[&str]) -> String {
+    let mut root = PermissionNode::default();
+    for permission in permissions {
+        let mut node = &mut root;
+        for part in permission.split('.') {
+            node = node.children.entry(part).or_default();
+        }
+        node.registered = true;
+    }
+
+    fn render(node: &PermissionNode, path: &str, prefix: &str, out: &mut String) {
+        let len = node.children.len();
+        for (i, (name, child)) in node.children.iter().enumerate() {
+            let last = i + 1 == len;
+            let full = if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", path, name)
+            };
+            let label = match (child.children.is_empty(), child.registered) {
+                (true, _) => full.clone(),
+                (false, true) => format!("{} ({}.{})", full, full, WILDCARD),
+                (false, false) => format!("{}.{}", full, WILDCARD),
+            };
+            out.push_str(prefix);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(&label);
+            out.push('\n');
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            render(child, &full, &prefix, out);
+        }
+    }
+
+    let mut out = format!("{}\n", WILDCARD);
+    render(&root, "", "", &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ancestors_from_narrowest() {
+        assert_eq!(
+            permission_ancestors("music.queue.clear"),
+            ["music.queue.clear", "music.queue.*", "music.*", "*"]
+        );
+        assert_eq!(permission_ancestors("music"), ["music", "*"]);
+        assert_eq!(permission_ancestors("*"), ["*"]);
+    }
+
+    #[test]
+    fn wildcards_imply_their_children() {
+        assert!(permission_implies("music.manage", "music.manage"));
+        assert!(permission_implies("music.*", "music.manage"));
+        assert!(permission_implies("music.*", "music.record"));
+        assert!(permission_implies("*", "music.manage"));
+        assert!(permission_implies("*", "*"));
+        assert!(permission_implies("music.*", "music.*"));
+    }
+
+    #[test]
+    fn wildcards_dont_imply_outside() {
+        assert!(!permission_implies("music.queue.*", "music.manage"));
+        assert!(!permission_implies("music.*", "music"));
+        assert!(!permission_implies("mus*", "music.manage"));
+        assert!(!permission_implies("music.manage", "music.*"));
+        assert!(!permission_implies("music.manage", "*"));
+        assert!(!permission_implies("config.*", "music.manage"));
+    }
+}
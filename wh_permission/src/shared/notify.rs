@@ -0,0 +1,78 @@
+use serenity::client::Context;
+use std::sync::atomic::{AtomicU64, Ordering};
+use wh_database::shared::{DatabaseKey, Id};
+
+/// The Postgres channel the `notify_permission_change` trigger sends the changed entries on
+pub const PERMISSION_CHANNEL: &str = "permission_change";
+
+/// Wait before listening again when the connection to the database fails
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Incremented on every invalidation, a cache filled from the database is only kept if it didn't change while reading
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+pub(crate) fn bump_generation() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Forget the cached permissions of every user and role of the guild
+pub fn invalidate_guild_caches(guildid: u64) {
+    super::role_permission::invalidate_role_cache(guildid);
+    super::user_permission::invalidate_guild_user_cache(guildid);
+}
+
+/// Forget every cached permission
+pub fn clear_caches() {
+    super::command_binding::clear_binding_cache();
+    super::role_permission::clear_role_cache();
+    super::user_permission::clear_user_cache();
+}
+
+/// Keep the caches of this process up to date with the changes made by every process, never returns
+pub async fn listen_permission_changes(ctx: Context) {
+    loop {
+        if let Err(e) = listen(&ctx).await {
+            error!("Error when listening to permission changes: {}", e);
+        }
+        // Changes may have been missed while not listening
+        clear_caches();
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+async fn listen(ctx: &Context) -> Result<(), sqlx::Error> {
+    let db = ctx.data.read().await.get::<DatabaseKey>().unwrap().clone();
+    let mut listener = sqlx::postgres::PgListener::connect_with(&db).await?;
+    listener.listen(PERMISSION_CHANNEL).await?;
+    loop {
+        match listener.try_recv().await? {
+            Some(notification) => handle_notification(notification.payload()),
+            // The connection was lost and is reopened on the next call
+            None => clear_caches(),
+        }
+    }
+}
+
+/// The payload is `user <guildid> <userid>`, `role <guildid>`, `guild <guildid>` or `command <guildid>`
+fn handle_notification(payload: &str) {
+    let mut parts = payload.split(' ');
+    let kind = parts.next();
+    let mut ids = parts.map(|id| id.parse::<i64>().ok().map(|id| Id::from(id).0));
+    match (kind, ids.next().flatten(), ids.next().flatten()) {
+        (Some("user"), Some(guildid), Some(userid)) => {
+            super::user_permission::invalidate_user_cache(guildid, userid)
+        }
+        (Some("role"), Some(guildid), None) => {
+            super::role_permission::invalidate_role_cache(guildid)
+        }
+        (Some("guild"), Some(guildid), None) => invalidate_guild_caches(guildid),
+        (Some("command"), Some(guildid), None) => {
+            super::command_binding::invalidate_binding_cache(guildid)
+        }
+        _ => warn!("Unknown permission change notification `{}`", payload),
+    }
+}
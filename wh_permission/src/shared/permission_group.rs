@@ -0,0 +1,158 @@
+use serenity::{client::Context, framework::standard::CommandResult};
+use wh_database::shared::{DatabaseKey, Id};
+
+/// A group is granted and denied like a permission, as `group:<name>`
+pub const GROUP_PREFIX: &str = "group:";
+
+/// The name of the group if `permission` refers to one
+pub fn group_name(permission: &str) -> Option<&str> {
+    permission.strip_prefix(GROUP_PREFIX)
+}
+
+pub fn is_valid_group_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+/// Replace the groups in `permissions` by what they contain, a group that doesn't exist anymore gives nothing
+pub fn expand_groups(groups: &[PermissionGroup], permissions: Vec<String>) -> Vec<String> {
+    let mut out = Vec::with_capacity(permissions.len());
+    for permission in permissions {
+        match group_name(&permission) {
+            Some(name) => {
+                if let Some(group) = groups.iter().find(|g| g.name == name) {
+                    out.extend(group.permissions.iter().cloned());
+                }
+            }
+            None => out.push(permission),
+        }
+    }
+    out
+}
+
+/// Get every group of the guild, sorted by name
+pub async fn get_groups(
+    ctx: &Context,
+    guildid: u64,
+) -> Result<Vec<PermissionGroup>, Box<dyn std::error::Error + Send + Sync>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let res = query_as!(
+        PermissionGroupRaw,
+        "SELECT * FROM permission_group WHERE guildid = $1::int8 ORDER BY name",
+        Id(guildid) as _,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res.into_iter().map(|r| r.into_processed()).collect())
+}
+
+pub async fn get_group(
+    ctx: &Context,
+    guildid: u64,
+    name: &str,
+) -> Result<Option<PermissionGroup>, Box<dyn std::error::Error + Send + Sync>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let res = query_as!(
+        PermissionGroupRaw,
+        "SELECT * FROM permission_group WHERE guildid = $1::int8 AND name = $2::text",
+        Id(guildid) as _,
+        name,
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(res.map(|r| r.into_processed()))
+}
+
+/// Create the group or replace its permissions, everyone holding it gets the new ones
+pub async fn set_group(
+    ctx: &Context,
+    guildid: u64,
+    name: &str,
+    permissions: &[String],
+) -> CommandResult {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    query!(
+        "INSERT INTO permission_group (guildid, name, permissions) VALUES ($1::int8, $2::text, $3::text[]) ON CONFLICT (guildid, name) DO UPDATE SET permissions = EXCLUDED.permissions",
+        Id(guildid) as _,
+        name,
+        permissions,
+    )
+    .execute(db)
+    .await?;
+    super::notify::invalidate_guild_caches(guildid);
+    Ok(())
+}
+
+/// Delete the group and take it back from everyone holding it, returns false if it didn't exist
+pub async fn delete_group(ctx: &Context, guildid: u64, name: &str) -> CommandResult<bool> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let reference = format!("{}{}", GROUP_PREFIX, name);
+    let mut tx = db.begin().await?;
+    let deleted = query!(
+        "DELETE FROM permission_group WHERE guildid = $1::int8 AND name = $2::text",
+        Id(guildid) as _,
+        name,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected()
+        > 0;
+    query!(
+        "UPDATE user_permission SET ids = array_diff(ids, ARRAY[$2::text]), denied = array_diff(denied, ARRAY[$2::text]) WHERE guildid = $1::int8 AND ($2::text = ANY(ids) OR $2::text = ANY(denied))",
+        Id(guildid) as _,
+        reference,
+    )
+    .execute(&mut tx)
+    .await?;
+    query!(
+        "UPDATE role_permission SET ids = array_diff(ids, ARRAY[$2::text]), denied = array_diff(denied, ARRAY[$2::text]) WHERE guildid = $1::int8 AND ($2::text = ANY(ids) OR $2::text = ANY(denied))",
+        Id(guildid) as _,
+        reference,
+    )
+    .execute(&mut tx)
+    .await?;
+    query!(
+        "DELETE FROM temporary_permission WHERE guildid = $1::int8 AND permission = $2::text",
+        Id(guildid) as _,
+        reference,
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    super::notify::invalidate_guild_caches(guildid);
+    Ok(deleted)
+}
+
+#[derive(Debug, Clone)]
+struct PermissionGroupRaw {
+    uid: i64,
+    guildid: i64,
+    name: String,
+    permissions: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PermissionGroup {
+    pub uid: i64,
+    pub guildid: Id,
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+impl PermissionGroupRaw {
+    fn into_processed(self) -> PermissionGroup {
+        PermissionGroup {
+            uid: self.uid,
+            guildid: self.guildid.into(),
+            name: self.name,
+            permissions: self.permissions,
+        }
+    }
+}
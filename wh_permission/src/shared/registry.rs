@@ -0,0 +1,98 @@
+use once_cell::sync::Lazy;
+use serenity::model::permissions::Permissions;
+use std::collections::BTreeMap;
+
+/// Who has a permission when nothing grants or denies it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultPolicy {
+    /// Only the users and roles it is granted to
+    Nobody,
+    /// Every member of the guild
+    Everyone,
+    /// The members having all of these Discord permissions
+    Discord(Permissions),
+}
+
+impl DefaultPolicy {
+    pub fn allows(self, discord_permissions: Permissions) -> bool {
+        match self {
+            DefaultPolicy::Nobody => false,
+            DefaultPolicy::Everyone => true,
+            DefaultPolicy::Discord(required) => discord_permissions.contains(required),
+        }
+    }
+}
+
+impl std::fmt::Display for DefaultPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefaultPolicy::Nobody => write!(f, "nobody"),
+            DefaultPolicy::Everyone => write!(f, "everyone"),
+            DefaultPolicy::Discord(required) => write!(
+                f,
+                "members with {}",
+                required.get_permission_names().join(", ")
+            ),
+        }
+    }
+}
+
+/// A permission a module checks, registered with [`crate::register_permission!`] or [`crate::check_permission!`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionInfo {
+    pub name: &'static str,
+    /// The crate declaring it, like `wh_music`
+    pub module: &'static str,
+    /// Should come from the fluent file
+    pub description: &'static str,
+    pub default: DefaultPolicy,
+}
+
+impl PermissionInfo {
+    /// The name of the module as shown to users, `wh_music` gives `Music`
+    pub fn module_name(&self) -> String {
+        let name = self.module.strip_prefix("wh_").unwrap_or(self.module);
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    }
+}
+
+pub use inventory;
+inventory::collect!(PermissionInfo);
+
+/// Filled once from everything submitted at link time
+static REGISTRY: Lazy<BTreeMap<&'static str, PermissionInfo>> = Lazy::new(|| {
+    let mut registry: BTreeMap<&'static str, PermissionInfo> = BTreeMap::new();
+    for permission in inventory::iter::<PermissionInfo> {
+        // The same permission can be submitted by several checks, only one of them needs the description
+        if let Some(previous) = registry.get(permission.name) {
+            if permission.description.is_empty() {
+                continue;
+            }
+            if !previous.description.is_empty() && previous != permission {
+                warn!(
+                    "The permission `{}` is registered twice with a different description or default, by {} and {}",
+                    permission.name, previous.module, permission.module
+                );
+            }
+        }
+        registry.insert(permission.name, *permission);
+    }
+    registry
+});
+
+pub fn permission_info(name: &str) -> Option<PermissionInfo> {
+    REGISTRY.get(name).copied()
+}
+
+pub fn is_registered(name: &str) -> bool {
+    REGISTRY.contains_key(name)
+}
+
+/// Every registered permission, sorted by name
+pub fn registered_permissions() -> Vec<PermissionInfo> {
+    REGISTRY.values().copied().collect()
+}
@@ -4,55 +4,126 @@ use wh_database::shared::{DatabaseKey, Id};
 
 const CACHE_SIZE: usize = 100;
 
-type RoleCache = parking_lot::Mutex<
-    lru::LruCache<
-        u64, /*guilid*/
-        std::collections::HashMap<
-            String, /*permission*/
-            std::collections::HashSet<u64 /*roleid*/>,
-        >,
+type RoleSet = std::collections::HashMap<
+    String, /*permission*/
+    std::collections::HashMap<
+        (
+            u64, /*roleid*/
+            u64, /*channelid, 0 for the whole guild*/
+        ),
+        Option<chrono::DateTime<chrono::Utc>>, /*expiration, None if permanent*/
     >,
 >;
 
-pub static ROLE_CACHE: Lazy<RoleCache> =
+/// Roles granted and denied each permission in a guild
+#[derive(Debug, Default)]
+pub struct GuildRoleCache {
+    granted: RoleSet,
+    denied: RoleSet,
+}
+
+type RoleCache = parking_lot::Mutex<lru::LruCache<u64 /*guilid*/, GuildRoleCache>>;
+
+static ROLE_CACHE: Lazy<RoleCache> =
     Lazy::new(|| parking_lot::Mutex::new(lru::LruCache::new(CACHE_SIZE)));
 
-fn check_role_permission_in_cache(guildid: u64, roleid: u64, permission: &str) -> Option<bool> {
+/// Forget the cached role permissions of the guild, they are fetched again on the next check
+pub fn invalidate_role_cache(guildid: u64) {
+    super::notify::bump_generation();
+    ROLE_CACHE.lock().pop(&guildid);
+}
+
+pub(crate) fn clear_role_cache() {
+    super::notify::bump_generation();
+    ROLE_CACHE.lock().clear();
+}
+
+fn check_role_permission_in_cache(
+    guildid: u64,
+    roleid: u64,
+    channelid: Option<u64>,
+    permission: &str,
+) -> Option<Option<bool>> {
     let mut lock = ROLE_CACHE.lock();
     let guild_cache = lock.get(&guildid);
-    if let Some(guild) = guild_cache {
-        guild.get(permission).map(|roles| roles.contains(&roleid))
-    } else {
-        None
-    }
+    let key = (roleid, channelid.unwrap_or(0));
+    guild_cache.map(|guild| {
+        let ancestors = super::permission_ancestors(permission);
+        let now = chrono::Utc::now();
+        let entries = |map: &RoleSet| {
+            ancestors
+                .iter()
+                .filter_map(|p| map.get(p.as_str()))
+                .filter_map(|roles| roles.get(&key))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        let granted = entries(&guild.granted);
+        // A temporary grant wins over the deny it was given on top of
+        if granted.iter().flatten().any(|e| *e > now) {
+            Some(true)
+        } else if !entries(&guild.denied).is_empty() {
+            Some(false)
+        } else if granted.iter().any(Option::is_none) {
+            Some(true)
+        } else {
+            None
+        }
+    })
 }
 
+/// Get the permissions of the role in the whole guild, or in a single channel if `channelid` is given
 pub async fn get_role_permission(
     ctx: &Context,
     roleid: u64,
     guildid: u64,
+    channelid: Option<u64>,
 ) -> Result<Option<RolePermission>, Box<dyn std::error::Error + Send + Sync>> {
     let lock = ctx.data.read().await;
     let db = lock.get::<DatabaseKey>().unwrap();
     let res = query_as!(
         RolePermissionRaw,
-        "SELECT * FROM role_permission WHERE roleid = $1::int8 AND guildid = $2::int8",
+        "SELECT * FROM role_permission WHERE roleid = $1::int8 AND guildid = $2::int8 AND channelid = $3::int8",
         Id(roleid) as _,
-        Id(guildid) as _
+        Id(guildid) as _,
+        Id(channelid.unwrap_or(0)) as _,
     )
     .fetch_optional(db)
     .await?;
     Ok(res.map(|r| r.into_processed()))
 }
 
+/// Get the permissions of the role in the whole guild and in every channel
+pub async fn get_role_permissions(
+    ctx: &Context,
+    roleid: u64,
+    guildid: u64,
+) -> Result<Vec<RolePermission>, Box<dyn std::error::Error + Send + Sync>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let res = query_as!(
+        RolePermissionRaw,
+        "SELECT * FROM role_permission WHERE roleid = $1::int8 AND guildid = $2::int8 ORDER BY channelid",
+        Id(roleid) as _,
+        Id(guildid) as _
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res.into_iter().map(|r| r.into_processed()).collect())
+}
+
+/// Returns `Some(false)` if the role is denied the permission, `Some(true)` if it is granted and `None` if the role has no say in it.
+/// Only the entries limited to `channelid` are looked at, or the guild wide ones if it is `None`.
+/// A deny takes precedence over a grant, except over a temporary grant which lifts it until it expires
 pub async fn check_role_permission(
     ctx: &Context,
     guildid: u64,
     roleid: u64,
+    channelid: Option<u64>,
     permission: &str,
-) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<Option<bool>, Box<dyn std::error::Error + Send + Sync>> {
     fetch_role_db(ctx, guildid).await?;
-    Ok(check_role_permission_in_cache(guildid, roleid, permission).unwrap_or(false))
+    Ok(check_role_permission_in_cache(guildid, roleid, channelid, permission).flatten())
 }
 
 async fn fetch_role_db(ctx: &Context, guildid: u64) -> CommandResult {
@@ -63,7 +134,10 @@ async fn fetch_role_db(ctx: &Context, guildid: u64) -> CommandResult {
     role_update_cache_from_db(ctx, guildid).await
 }
 async fn role_update_cache_from_db(ctx: &Context, guildid: u64) -> CommandResult {
+    use super::permission_group::expand_groups;
     use serenity::futures::StreamExt;
+    let generation = super::notify::generation();
+    let groups = super::permission_group::get_groups(ctx, guildid).await?;
     let typemap = ctx.data.read().await;
 
     let db = typemap.get::<DatabaseKey>().unwrap();
@@ -74,52 +148,94 @@ async fn role_update_cache_from_db(ctx: &Context, guildid: u64) -> CommandResult
         Id(guildid) as _
     )
     .fetch(db);
-    let mut role_hashmap =
-        std::collections::HashMap::<String, std::collections::HashSet<u64>>::with_capacity(
-            res.size_hint().1.unwrap_or(10),
-        );
+    let mut role_cache = GuildRoleCache::default();
 
     while let Some(row) = res.next().await {
         let row = row?;
         let processed = row.into_processed();
-        for perm in processed.ids {
-            if let Some(hs) = role_hashmap.get_mut(perm.as_str()) {
-                hs.insert(processed.roleid.0);
-            } else {
-                let mut hs = std::collections::HashSet::new();
-                hs.insert(processed.roleid.0);
-                role_hashmap.insert(perm, hs);
+        let key = (
+            processed.roleid.0,
+            processed.channelid.map(|c| c.0).unwrap_or(0),
+        );
+        for perm in expand_groups(&groups, processed.ids) {
+            role_cache
+                .granted
+                .entry(perm)
+                .or_default()
+                .insert(key, None);
+        }
+        for perm in expand_groups(&groups, processed.denied) {
+            role_cache.denied.entry(perm).or_default().insert(key, None);
+        }
+    }
+    drop(res);
+    drop(typemap);
+
+    for temporary in
+        super::temporary_permission::get_role_temporary_permissions(ctx, guildid).await?
+    {
+        if let super::temporary_permission::PermissionTarget::Role(roleid) = temporary.target {
+            let key = (roleid, temporary.channelid.map(|c| c.0).unwrap_or(0));
+            for perm in expand_groups(&groups, vec![temporary.permission]) {
+                let expires_at = role_cache
+                    .granted
+                    .entry(perm)
+                    .or_default()
+                    .entry(key)
+                    .or_insert(Some(temporary.expires_at));
+                // A permanent grant stays permanent, otherwise keep the latest expiration
+                if let Some(e) = expires_at {
+                    *e = (*e).max(temporary.expires_at);
+                }
             }
         }
     }
-    role_hashmap.shrink_to_fit();
+    role_cache.granted.shrink_to_fit();
+    role_cache.denied.shrink_to_fit();
 
-    ROLE_CACHE.lock().put(guildid, role_hashmap);
+    // Don't keep what was read if the permissions changed in the meantime
+    if generation == super::notify::generation() {
+        ROLE_CACHE.lock().put(guildid, role_cache);
+    }
 
     Ok(())
 }
 
+/// Create the (empty) permissions of the role in the whole guild, or in a single channel if `channelid` is given
 pub async fn create_role_permission_if_not_exist(
     ctx: &Context,
     roleid: u64,
     guildid: u64,
+    channelid: Option<u64>,
 ) -> CommandResult {
     let typemap = ctx.data.read().await;
     let db = typemap.get::<DatabaseKey>().unwrap();
-    let exist = query!(
-        "SELECT COUNT(*) FROM role_permission WHERE roleid = $1::int8 and guildid = $2::int8",
+    query!("INSERT INTO role_permission (roleid, guildid, channelid, ids) VALUES ($1::int8, $2::int8, $3::int8, $4::text[]) ON CONFLICT (guildid, roleid, channelid) DO NOTHING", Id(roleid) as _, Id(guildid) as _, Id(channelid.unwrap_or(0)) as _, &[][..] ).execute(db).await?;
+
+    Ok(())
+}
+
+/// Forget everything given to a role that was deleted from the guild
+pub async fn delete_role_permissions(ctx: &Context, guildid: u64, roleid: u64) -> CommandResult {
+    let typemap = ctx.data.read().await;
+    let db = typemap.get::<DatabaseKey>().unwrap();
+    let mut tx = db.begin().await?;
+    query!(
+        "DELETE FROM role_permission WHERE roleid = $1::int8 AND guildid = $2::int8",
         Id(roleid) as _,
-        Id(guildid) as _
+        Id(guildid) as _,
     )
-    .fetch_one(db)
-    .await?
-    .count
-        == Some(1);
-    if exist {
-        return Ok(());
-    }
-    query!("INSERT INTO role_permission (roleid, guildid, ids) VALUES ($1::int8, $2::int8, $3::text[])", Id(roleid) as _, Id(guildid) as _, &[][..] ).execute(db).await?;
-
+    .execute(&mut tx)
+    .await?;
+    query!(
+        "DELETE FROM temporary_permission WHERE roleid = $1::int8 AND guildid = $2::int8",
+        Id(roleid) as _,
+        Id(guildid) as _,
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    invalidate_role_cache(guildid);
     Ok(())
 }
 
@@ -129,6 +245,8 @@ struct RolePermissionRaw {
     guildid: i64,
     roleid: i64,
     ids: Vec<String>,
+    denied: Vec<String>,
+    channelid: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -137,6 +255,9 @@ pub struct RolePermission {
     pub guildid: Id,
     pub roleid: Id,
     pub ids: Vec<String>,
+    pub denied: Vec<String>,
+    /// `None` if the permissions apply in the whole guild
+    pub channelid: Option<Id>,
 }
 
 impl RolePermissionRaw {
@@ -144,8 +265,13 @@ impl RolePermissionRaw {
         RolePermission {
             uid: self.uid,
             ids: self.ids,
+            denied: self.denied,
             guildid: self.guildid.into(),
             roleid: self.roleid.into(),
+            channelid: match self.channelid {
+                0 => None,
+                c => Some(c.into()),
+            },
         }
     }
 }
@@ -0,0 +1,249 @@
+use serenity::{client::Context, framework::standard::CommandResult};
+use wh_database::shared::{DatabaseKey, Id};
+
+/// How often the expired grants are removed
+pub const CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionTarget {
+    User(u64),
+    Role(u64),
+}
+
+impl PermissionTarget {
+    fn columns(self) -> (Option<Id>, Option<Id>) {
+        match self {
+            PermissionTarget::User(userid) => (Some(Id(userid)), None),
+            PermissionTarget::Role(roleid) => (None, Some(Id(roleid))),
+        }
+    }
+
+    /// Forget the cached permissions the grants of the target are part of
+    fn invalidate_cache(self, guildid: u64) {
+        match self {
+            PermissionTarget::User(userid) => {
+                super::user_permission::invalidate_user_cache(guildid, userid)
+            }
+            PermissionTarget::Role(_) => super::role_permission::invalidate_role_cache(guildid),
+        }
+    }
+}
+
+/// The longest a temporary grant can last
+const MAX_DURATION: chrono::Duration = chrono::Duration::days(366);
+
+/// Parse a duration like `30m`, `12h`, `2d` or `1w2d`
+pub fn parse_duration(input: &str) -> Option<chrono::Duration> {
+    let mut total = chrono::Duration::zero();
+    let mut number = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n = number.parse::<i64>().ok()?;
+        number.clear();
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        // Checked so a huge number can't overflow the duration
+        let seconds = n.checked_mul(unit)?.checked_add(total.num_seconds())?;
+        if seconds > MAX_DURATION.num_seconds() {
+            return None;
+        }
+        total = chrono::Duration::seconds(seconds);
+    }
+    if !number.is_empty() || total <= chrono::Duration::zero() {
+        return None;
+    }
+    Some(total)
+}
+
+pub async fn add_temporary_permission(
+    ctx: &Context,
+    guildid: u64,
+    target: PermissionTarget,
+    channelid: Option<u64>,
+    permission: &str,
+    granterid: u64,
+    expires_at: chrono::DateTime<chrono::Utc>,
+) -> CommandResult {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let (userid, roleid) = target.columns();
+    query!(
+        "INSERT INTO temporary_permission (guildid, userid, roleid, channelid, permission, granterid, expires_at) VALUES ($1::int8, $2::int8, $3::int8, $4::int8, $5::text, $6::int8, $7::timestamptz)",
+        Id(guildid) as _,
+        userid as _,
+        roleid as _,
+        Id(channelid.unwrap_or(0)) as _,
+        permission,
+        Id(granterid) as _,
+        expires_at,
+    )
+    .execute(db)
+    .await?;
+    target.invalidate_cache(guildid);
+    Ok(())
+}
+
+/// Remove the temporary grants of `permission` given to `target` in the channel (or the whole guild)
+pub async fn remove_temporary_permission(
+    ctx: &Context,
+    guildid: u64,
+    target: PermissionTarget,
+    channelid: Option<u64>,
+    permission: &str,
+) -> CommandResult {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let (userid, roleid) = target.columns();
+    query!(
+        "DELETE FROM temporary_permission WHERE guildid = $1::int8 AND userid IS NOT DISTINCT FROM $2::int8 AND roleid IS NOT DISTINCT FROM $3::int8 AND channelid = $4::int8 AND permission = $5::text",
+        Id(guildid) as _,
+        userid as _,
+        roleid as _,
+        Id(channelid.unwrap_or(0)) as _,
+        permission,
+    )
+    .execute(db)
+    .await?;
+    target.invalidate_cache(guildid);
+    Ok(())
+}
+
+/// Get the grants of `target` that haven't expired yet
+pub async fn get_temporary_permissions(
+    ctx: &Context,
+    guildid: u64,
+    target: PermissionTarget,
+) -> Result<Vec<TemporaryPermission>, Box<dyn std::error::Error + Send + Sync>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let (userid, roleid) = target.columns();
+    let res = query_as!(
+        TemporaryPermissionRaw,
+        "SELECT * FROM temporary_permission WHERE guildid = $1::int8 AND userid IS NOT DISTINCT FROM $2::int8 AND roleid IS NOT DISTINCT FROM $3::int8 AND expires_at > now() ORDER BY expires_at",
+        Id(guildid) as _,
+        userid as _,
+        roleid as _,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res.into_iter().map(|r| r.into_processed()).collect())
+}
+
+/// Get the role grants of the guild that haven't expired yet
+pub async fn get_role_temporary_permissions(
+    ctx: &Context,
+    guildid: u64,
+) -> Result<Vec<TemporaryPermission>, Box<dyn std::error::Error + Send + Sync>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let res = query_as!(
+        TemporaryPermissionRaw,
+        "SELECT * FROM temporary_permission WHERE guildid = $1::int8 AND roleid IS NOT NULL AND expires_at > now()",
+        Id(guildid) as _,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(res.into_iter().map(|r| r.into_processed()).collect())
+}
+
+/// Delete the expired grants and tell their granter about it
+pub async fn expire_temporary_permissions(ctx: &Context) -> CommandResult {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let expired = query_as!(
+        TemporaryPermissionRaw,
+        "DELETE FROM temporary_permission WHERE expires_at <= now() RETURNING *"
+    )
+    .fetch_all(db)
+    .await?;
+    drop(lock);
+
+    for entry in expired.into_iter().map(|r| r.into_processed()) {
+        entry.target.invalidate_cache(entry.guildid.0);
+        let target = match entry.target {
+            PermissionTarget::User(userid) => format!("<@{}>", userid),
+            PermissionTarget::Role(roleid) => format!("<@&{}>", roleid),
+        };
+        let guild_name = serenity::model::id::GuildId(entry.guildid.0)
+            .name(&ctx.cache)
+            .await
+            .unwrap_or_else(|| entry.guildid.0.to_string());
+        let place = match entry.channelid {
+            Some(channelid) => format!(" in <#{}>", channelid.0),
+            None => String::new(),
+        };
+        let message = format!(
+            "The permission `{}` you gave to {}{} on **{}** has expired",
+            entry.permission, target, place, guild_name
+        );
+        let sent = async {
+            serenity::model::id::UserId(entry.granterid.0)
+                .create_dm_channel(ctx)
+                .await?
+                .say(ctx, message)
+                .await
+        }
+        .await;
+        if let Err(e) = sent {
+            warn!(
+                "Couldn't notify {} about an expired permission: {}",
+                entry.granterid.0, e
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct TemporaryPermissionRaw {
+    uid: i64,
+    guildid: i64,
+    userid: Option<i64>,
+    roleid: Option<i64>,
+    channelid: i64,
+    permission: String,
+    granterid: i64,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TemporaryPermission {
+    pub uid: i64,
+    pub guildid: Id,
+    pub target: PermissionTarget,
+    /// `None` if the permission applies in the whole guild
+    pub channelid: Option<Id>,
+    pub permission: String,
+    pub granterid: Id,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TemporaryPermissionRaw {
+    fn into_processed(self) -> TemporaryPermission {
+        let target = match (self.userid, self.roleid) {
+            (Some(userid), _) => PermissionTarget::User(Id::from(userid).0),
+            (None, roleid) => PermissionTarget::Role(Id::from(roleid.unwrap_or_default()).0),
+        };
+        TemporaryPermission {
+            uid: self.uid,
+            guildid: self.guildid.into(),
+            target,
+            channelid: match self.channelid {
+                0 => None,
+                c => Some(c.into()),
+            },
+            permission: self.permission,
+            granterid: self.granterid.into(),
+            expires_at: self.expires_at,
+        }
+    }
+}
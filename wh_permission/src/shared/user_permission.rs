@@ -1,104 +1,434 @@
+use super::permission_group::expand_groups;
+use super::registry::DefaultPolicy;
+use once_cell::sync::Lazy;
+use serenity::model::id::{ChannelId, GuildId, UserId};
 use serenity::{
     client::Context,
     framework::standard::{CommandResult, Reason},
 };
+use std::sync::Arc;
 use wh_database::shared::{DatabaseKey, Id};
 
-static mut PERMISSIONS: Vec<&'static str> = Vec::new();
+const CACHE_SIZE: usize = 1000;
 
-pub fn add_permission(perms: &[&'static str]) {
-    for p in perms {
-        static_set_permission(p);
+/// Everything given to a user in a guild, for every channel, with the groups replaced by their permissions
+#[derive(Debug)]
+struct CachedUserPermissions {
+    permissions: Vec<UserPermission>,
+    /// Checked against the current time when used, they may expire while cached
+    temporary: Vec<super::temporary_permission::TemporaryPermission>,
+}
+
+type UserCache = parking_lot::Mutex<
+    lru::LruCache<(u64 /*guildid*/, u64 /*userid*/), Arc<CachedUserPermissions>>,
+>;
+
+static USER_CACHE: Lazy<UserCache> =
+    Lazy::new(|| parking_lot::Mutex::new(lru::LruCache::new(CACHE_SIZE)));
+
+/// Forget the cached permissions of the user in the guild, they are fetched again on the next check
+pub fn invalidate_user_cache(guildid: u64, userid: u64) {
+    super::notify::bump_generation();
+    USER_CACHE.lock().pop(&(guildid, userid));
+}
+
+/// Forget the cached permissions of every user of the guild
+pub fn invalidate_guild_user_cache(guildid: u64) {
+    super::notify::bump_generation();
+    let mut cache = USER_CACHE.lock();
+    let keys = cache
+        .iter()
+        .map(|(key, _)| *key)
+        .filter(|(g, _)| *g == guildid)
+        .collect::<Vec<_>>();
+    for key in keys {
+        cache.pop(&key);
     }
 }
-pub fn static_set_permission(perm: &'static str) {
-    unsafe { PERMISSIONS.push(perm) };
+
+pub(crate) fn clear_user_cache() {
+    super::notify::bump_generation();
+    USER_CACHE.lock().clear();
 }
 
-pub fn static_get_permission() -> &'static Vec<&'static str> {
-    unsafe { &PERMISSIONS }
+async fn cached_user_permissions(
+    ctx: &Context,
+    guildid: u64,
+    userid: u64,
+) -> Result<Arc<CachedUserPermissions>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(cached) = USER_CACHE.lock().get(&(guildid, userid)) {
+        return Ok(cached.clone());
+    }
+    let generation = super::notify::generation();
+    let groups = super::permission_group::get_groups(ctx, guildid).await?;
+    let mut permissions = get_permissions(ctx, userid, guildid).await?;
+    for row in &mut permissions {
+        row.ids = expand_groups(&groups, std::mem::take(&mut row.ids));
+        row.denied = expand_groups(&groups, std::mem::take(&mut row.denied));
+    }
+    let mut temporary = Vec::new();
+    for grant in super::temporary_permission::get_temporary_permissions(
+        ctx,
+        guildid,
+        super::temporary_permission::PermissionTarget::User(userid),
+    )
+    .await?
+    {
+        for permission in expand_groups(&groups, vec![grant.permission.clone()]) {
+            temporary.push(super::temporary_permission::TemporaryPermission {
+                permission,
+                ..grant.clone()
+            });
+        }
+    }
+    let cached = Arc::new(CachedUserPermissions {
+        permissions,
+        temporary,
+    });
+    // Don't keep what was read if the permissions changed in the meantime
+    if generation == super::notify::generation() {
+        USER_CACHE.lock().put((guildid, userid), cached.clone());
+    }
+    Ok(cached)
 }
 
-pub async fn has_permission(
+/// Where the decision for a permission came from, see [`evaluate_permission`] for the precedence.
+/// `channel` is the channel or category the matching entry is limited to, `None` if it applies to the whole guild
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionSource {
+    /// The user has the ADMINISTRATOR Discord permission
+    Administrator,
+    UserDeny {
+        channel: Option<u64>,
+    },
+    UserGrant {
+        channel: Option<u64>,
+    },
+    RoleDeny {
+        role: u64,
+        channel: Option<u64>,
+    },
+    RoleGrant {
+        role: u64,
+        channel: Option<u64>,
+    },
+    /// Nothing grants or denies the permission, its default policy allows the user
+    Default,
+    /// Nothing grants the permission
+    NotGranted,
+}
+
+impl PermissionSource {
+    pub fn allowed(self) -> bool {
+        matches!(
+            self,
+            PermissionSource::Administrator
+                | PermissionSource::Default
+                | PermissionSource::UserGrant { .. }
+                | PermissionSource::RoleGrant { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for PermissionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let place = |channel: &Option<u64>| match channel {
+            Some(channel) => format!("in <#{}>", channel),
+            None => String::from("in the guild"),
+        };
+        match self {
+            PermissionSource::Administrator => write!(f, "Discord administrator"),
+            PermissionSource::UserDeny { channel } => {
+                write!(f, "Denied to the user {}", place(channel))
+            }
+            PermissionSource::UserGrant { channel } => {
+                write!(f, "Granted to the user {}", place(channel))
+            }
+            PermissionSource::RoleDeny { role, channel } => {
+                write!(f, "Denied to <@&{}> {}", role, place(channel))
+            }
+            PermissionSource::RoleGrant { role, channel } => {
+                write!(f, "Granted to <@&{}> {}", role, place(channel))
+            }
+            PermissionSource::Default => write!(f, "Default policy"),
+            PermissionSource::NotGranted => write!(f, "Not granted"),
+        }
+    }
+}
+
+/// The channels a permission limited to a channel can apply from when used in `channelid`:
+/// the channel itself, the voice channel the user is in, and the categories of both
+fn channel_candidates(
+    guild: &serenity::model::guild::Guild,
+    userid: UserId,
+    channelid: Option<u64>,
+) -> Vec<u64> {
+    let voice = guild
+        .voice_states
+        .get(&userid)
+        .and_then(|v| v.channel_id)
+        .map(|c| c.0);
+    let mut out = Vec::with_capacity(4);
+    for channel in channelid.into_iter().chain(voice) {
+        out.push(channel);
+        if let Some(category) = guild
+            .channels
+            .get(&ChannelId(channel))
+            .and_then(|c| c.category_id)
+        {
+            out.push(category.0);
+        }
+    }
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+/// Everything [`explain_permission`] looked at to decide a permission
+#[derive(Debug, Clone)]
+pub struct PermissionExplanation {
+    pub administrator: bool,
+    /// `None` if the permission isn't registered
+    pub default: Option<DefaultPolicy>,
+    /// If the default policy allows the user, only used when no source matched
+    pub default_allows: bool,
+    /// Every source that matched, from the highest precedence to the lowest
+    pub sources: Vec<PermissionSource>,
+    /// The first of the sources, or what the default policy gives if there is none
+    pub decision: PermissionSource,
+}
+
+/// Decide if a user has a permission when used in `channelid`. The first matching rule wins:
+///
+/// 1. Discord administrators have every permission, they can change any grant anyway
+/// 2. A deny given to the user in the channel (or its category)
+/// 3. A grant given to the user in the channel (or its category)
+/// 4. A deny given to the user in the whole guild
+/// 5. A grant given to the user in the whole guild
+/// 6. A deny given to one of the user's roles in the channel (or its category)
+/// 7. A grant given to one of the user's roles in the channel (or its category)
+/// 8. A deny given to one of the user's roles in the whole guild
+/// 9. A grant given to one of the user's roles in the whole guild
+/// 10. The default policy the permission was registered with (see [`crate::shared::registry::DefaultPolicy`])
+/// 11. Otherwise the permission is not granted
+///
+/// The voice channel the user is connected to counts as a channel the permission is used in.
+/// Temporary grants count as grants until they expire, and come before the deny of the same user or role on the same level:
+/// granting temporarily something denied lifts the deny until the grant expires.
+/// Grants and denies are hierarchical (see [`crate::shared::permission_ancestors`]): denying `music.*` denies `music.manage`.
+/// On the same level a deny always wins over a permanent grant, even if the grant is more specific
+pub async fn evaluate_permission(
     ctx: &Context,
-    msg: &Message,
     userid: u64,
     guildid: u64,
+    channelid: Option<u64>,
     permission: &str,
-) -> Result<bool, Reason> {
-    if !static_get_permission().contains(&permission) {
-        error!("You need to register the permission `{}` with the wh_permission::add_permission function", permission);
+) -> Result<PermissionSource, Reason> {
+    Ok(evaluate(ctx, userid, guildid, channelid, permission, false)
+        .await?
+        .decision)
+}
+
+/// Same as [`evaluate_permission`] but keeps looking after the first match to list every source
+pub async fn explain_permission(
+    ctx: &Context,
+    userid: u64,
+    guildid: u64,
+    channelid: Option<u64>,
+    permission: &str,
+) -> Result<PermissionExplanation, Reason> {
+    evaluate(ctx, userid, guildid, channelid, permission, true).await
+}
+
+/// Stops at the first rule that matches unless `exhaustive` is set, the decision is the same either way
+async fn evaluate(
+    ctx: &Context,
+    userid: u64,
+    guildid: u64,
+    channelid: Option<u64>,
+    permission: &str,
+    exhaustive: bool,
+) -> Result<PermissionExplanation, Reason> {
+    let info = super::registry::permission_info(permission);
+    if info.is_none() {
+        error!("You need to register the permission `{}` with the wh_permission::register_permission! macro", permission);
     }
-    let lock = ctx.data.read().await;
-    let database = lock.get::<DatabaseKey>().unwrap();
-    let r = create_permission_if_not_exists(ctx, userid, guildid).await;
-    if let Err(e) = &r {
+    let guildid = GuildId(guildid);
+    let userid = UserId(userid);
+    let guild = match guildid.to_guild_cached(&ctx.cache).await {
+        Some(g) => g,
+        None => return Err(Reason::Log(format!("Guild {} isn't in the cache", guildid))),
+    };
+    let discord_permission = guild.member_permissions(ctx, userid).await;
+    if let Err(e) = &discord_permission {
+        return Err(Reason::UserAndLog {
+            user: "❌Internal Error".into(),
+            log: format!("Internal Error: {}", e),
+        });
+    }
+    let discord_permission = discord_permission.unwrap();
+    let mut explanation = PermissionExplanation {
+        administrator: discord_permission.administrator(),
+        default: info.map(|i| i.default),
+        default_allows: info.is_some_and(|i| i.default.allows(discord_permission)),
+        sources: Vec::new(),
+        decision: PermissionSource::NotGranted,
+    };
+    let done = |explanation: &mut PermissionExplanation| {
+        explanation.decision = match explanation.sources.first() {
+            Some(source) => *source,
+            None if explanation.default_allows => PermissionSource::Default,
+            None => PermissionSource::NotGranted,
+        };
+        !exhaustive && !explanation.sources.is_empty()
+    };
+    if explanation.administrator {
+        explanation.sources.push(PermissionSource::Administrator);
+    }
+    if done(&mut explanation) {
+        return Ok(explanation);
+    }
+
+    // The entries limited to a channel are looked at before the guild wide ones
+    let scopes: [Vec<Option<u64>>; 2] = [
+        channel_candidates(&guild, userid, channelid)
+            .into_iter()
+            .map(Some)
+            .collect(),
+        vec![None],
+    ];
+    let ancestors = super::permission_ancestors(permission);
+    let matches = |ids: &[String]| ids.iter().any(|p| ancestors.contains(p));
+    let user = cached_user_permissions(ctx, guildid.0, userid.0).await;
+    if let Err(e) = &user {
         return Err(Reason::UserAndLog {
             user: "Internal Error".into(),
             log: format!("Database Error when fetching permission: {}", e),
         });
     }
-    r.unwrap();
-    let res = query!(
-        "SELECT COUNT(*) FROM user_permission WHERE userid = $1::int8 AND guildid = $2::int8 AND $3::text = ANY(ids)",
-        Id(userid) as _,
-        Id(guildid) as _,
-        permission
-    )
-    .fetch_one(database).await;
-    if let Err(e) = res.as_ref() {
-        return Err(Reason::Log(format!("Database error: {}", e)));
-    }
-    let mut role_perm = false;
-    for roleid in &msg.member(&ctx.http).await.unwrap().roles {
-        role_perm = role_perm || {
-            match super::role_permission::check_role_permission(ctx, guildid, roleid.0, permission)
+    let user = user.unwrap();
+    let now = chrono::Utc::now();
+    let temporary = user.temporary.iter().filter(|t| t.expires_at > now);
+    for scope in &scopes {
+        let rows = user
+            .permissions
+            .iter()
+            .filter(|u| scope.contains(&u.channelid.map(|c| c.0)));
+        // A temporary grant wins over the deny it was given on top of
+        for grant in temporary.clone() {
+            let channel = grant.channelid.map(|c| c.0);
+            if scope.contains(&channel) && ancestors.contains(&grant.permission) {
+                explanation
+                    .sources
+                    .push(PermissionSource::UserGrant { channel });
+            }
+        }
+        for row in rows.clone() {
+            if matches(&row.denied) {
+                explanation.sources.push(PermissionSource::UserDeny {
+                    channel: row.channelid.map(|c| c.0),
+                });
+            }
+        }
+        for row in rows {
+            if matches(&row.ids) {
+                explanation.sources.push(PermissionSource::UserGrant {
+                    channel: row.channelid.map(|c| c.0),
+                });
+            }
+        }
+        if done(&mut explanation) {
+            return Ok(explanation);
+        }
+    }
+
+    let member = guild.member(ctx, userid).await;
+    if let Err(e) = &member {
+        return Err(Reason::Log(format!("Error when fetching member: {}", e)));
+    }
+    let roles = member.unwrap().roles;
+    for scope in &scopes {
+        let mut granted_by = Vec::new();
+        for roleid in &roles {
+            for channel in scope {
+                match super::role_permission::check_role_permission(
+                    ctx, guildid.0, roleid.0, *channel, permission,
+                )
                 .await
-            {
-                Ok(b) => b,
-                Err(e) => return Err(Reason::Log(format!("Database error: {}", e))),
+                {
+                    Ok(Some(false)) => explanation.sources.push(PermissionSource::RoleDeny {
+                        role: roleid.0,
+                        channel: *channel,
+                    }),
+                    Ok(Some(true)) => granted_by.push(PermissionSource::RoleGrant {
+                        role: roleid.0,
+                        channel: *channel,
+                    }),
+                    Ok(None) => (),
+                    Err(e) => return Err(Reason::Log(format!("Database error: {}", e))),
+                }
             }
-        };
-        if role_perm {
-            break;
+        }
+        // The denies of every role come before the grants
+        explanation.sources.append(&mut granted_by);
+        if done(&mut explanation) {
+            return Ok(explanation);
         }
     }
-    Ok(res.unwrap().count.unwrap() == 1 || role_perm)
+    done(&mut explanation);
+    Ok(explanation)
+}
+
+pub async fn has_permission(
+    ctx: &Context,
+    userid: u64,
+    guildid: u64,
+    channelid: Option<u64>,
+    permission: &str,
+) -> Result<bool, Reason> {
+    Ok(
+        evaluate_permission(ctx, userid, guildid, channelid, permission)
+            .await?
+            .allowed(),
+    )
 }
 
+/// Create the (empty) permissions of the user in the whole guild, or in a single channel if `channelid` is given
 pub async fn create_permission_if_not_exists(
     ctx: &Context,
     userid: u64,
     guildid: u64,
+    channelid: Option<u64>,
 ) -> CommandResult {
     let lock = ctx.data.read().await;
     let db = lock.get::<DatabaseKey>().unwrap();
-    let user = get_permission(ctx, userid, guildid).await?;
-    if user.is_none() {
-        query!(
-            "INSERT INTO user_permission (guildid, userid, ids)VALUES ($1::int8, $2::int8, $3::text[])",
-            Id(guildid) as _,
-            Id(userid) as _,
-            &[][..]
-        )
-        .execute(db)
-        .await?;
-    }
+    query!(
+        "INSERT INTO user_permission (guildid, userid, channelid, ids)VALUES ($1::int8, $2::int8, $3::int8, $4::text[]) ON CONFLICT (guildid, userid, channelid) DO NOTHING",
+        Id(guildid) as _,
+        Id(userid) as _,
+        Id(channelid.unwrap_or(0)) as _,
+        &[][..]
+    )
+    .execute(db)
+    .await?;
     Ok(())
 }
 
+/// Get the permissions of the user in the whole guild, or in a single channel if `channelid` is given
 pub async fn get_permission(
     ctx: &Context,
     userid: u64,
     guildid: u64,
+    channelid: Option<u64>,
 ) -> Result<Option<UserPermission>, Box<dyn std::error::Error + Send + Sync>> {
     let lock = ctx.data.read().await;
     let db = lock.get::<DatabaseKey>().unwrap();
     let res = query_as!(
         UserPermissionRaw,
-        "SELECT * FROM user_permission WHERE guildid = $1::int8 AND userid= $2::int8",
+        "SELECT * FROM user_permission WHERE guildid = $1::int8 AND userid= $2::int8 AND channelid = $3::int8",
         Id(guildid) as _,
-        Id(userid) as _
+        Id(userid) as _,
+        Id(channelid.unwrap_or(0)) as _,
     )
     .fetch_optional(db)
     .await?;
@@ -106,27 +436,58 @@ pub async fn get_permission(
     Ok(res.map(|u| u.into_processed()))
 }
 
+/// Get the permissions of the user in the whole guild and in every channel
+pub async fn get_permissions(
+    ctx: &Context,
+    userid: u64,
+    guildid: u64,
+) -> Result<Vec<UserPermission>, Box<dyn std::error::Error + Send + Sync>> {
+    let lock = ctx.data.read().await;
+    let db = lock.get::<DatabaseKey>().unwrap();
+    let res = query_as!(
+        UserPermissionRaw,
+        "SELECT * FROM user_permission WHERE guildid = $1::int8 AND userid= $2::int8 ORDER BY channelid",
+        Id(guildid) as _,
+        Id(userid) as _
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(res.into_iter().map(|u| u.into_processed()).collect())
+}
+
 struct UserPermissionRaw {
     uid: i64,
     guildid: i64,
     userid: i64,
     ids: Vec<String>,
+    denied: Vec<String>,
+    channelid: i64,
 }
 impl UserPermissionRaw {
     fn into_processed(self) -> UserPermission {
         UserPermission {
             uid: self.uid,
             ids: self.ids,
+            denied: self.denied,
             guildid: self.guildid.into(),
             userid: self.userid.into(),
+            channelid: match self.channelid {
+                0 => None,
+                c => Some(c.into()),
+            },
         }
     }
 }
+#[derive(Debug)]
 pub struct UserPermission {
     pub uid: i64,
     pub guildid: wh_database::shared::Id,
     pub userid: wh_database::shared::Id,
     pub ids: Vec<String>,
+    pub denied: Vec<String>,
+    /// `None` if the permissions apply in the whole guild
+    pub channelid: Option<wh_database::shared::Id>,
 }
 use serenity::framework::standard::macros::hook;
 use serenity::model::channel::Message;
@@ -137,11 +498,17 @@ pub async fn check_permission(
     msg: &Message,
     permission: &str,
 ) -> Result<(), Reason> {
+    // The guild replaced the permission of this command, the before hook checks it instead
+    match super::command_binding::message_requirement(ctx, msg).await {
+        Ok(Some(_)) => return Ok(()),
+        Ok(None) => (),
+        Err(e) => return Err(Reason::Log(format!("Database error: {}", e))),
+    }
     let res = crate::shared::user_permission::has_permission(
         ctx,
-        msg,
         msg.author.id.0,
         msg.guild_id.unwrap().0,
+        Some(msg.channel_id.0),
         permission,
     )
     .await?;
@@ -1,5 +1,12 @@
 add_commands!(PointsManage, (points), (points_manage));
 
-check_permission!(POINTS_MANAGE_CHECK, "points.manage");
+check_permission!(
+    POINTS_MANAGE_CHECK,
+    "points.manage",
+    fluent!(POINTS_PERMISSION_manage),
+    wh_permission::shared::registry::DefaultPolicy::Discord(
+        serenity::model::permissions::Permissions::MANAGE_GUILD
+    )
+);
 
 add_commands!(Points, (top,rank), ());
\ No newline at end of file
@@ -7,13 +7,13 @@ use role_cmd::ROLE_COMMAND;
 
 #[command]
 #[only_in(guilds)]
-#[sub_commands(add, remove, set, role)]
+#[sub_commands(add, remove, set, rate, role)]
 /// The top level command used to manage users and roles points
 pub async fn points(ctx: &Context, msg: &Message) -> CommandResult {
     reply_message!(
         ctx,
         msg,
-        "This command is divided into differents subcommands: `add`, `remove`, `set` and `role`"
+        "This command is divided into differents subcommands: `add`, `remove`, `set`, `rate` and `role`"
     );
 
     Ok(())
@@ -116,6 +116,58 @@ mod points_cmd {
 
         Ok(())
     }
+
+    #[command]
+    #[only_in(guilds)]
+    #[min_args(1)]
+    #[max_args(2)]
+    #[usage("[multiplier|reset] [?#channel]")]
+    #[example("1.5 #general")]
+    /// Set the multiplier of the points earned by talking, in the given channel or in the whole guild
+    pub async fn rate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+        use wh_config::shared::Scope;
+        let rate = args.single::<String>().unwrap_or_default();
+        let guildid = msg.guild_id.unwrap().0;
+        let scope = match args.single::<String>() {
+            Ok(arg) => match serenity::utils::parse_channel(&arg) {
+                Some(channelid) => Scope::Channel { guildid, channelid },
+                None => message_err!(fluent!(POINTS_ARG_err_channel_mention)),
+            },
+            Err(_) => Scope::Guild(guildid),
+        };
+
+        let lock = ctx.data.read().await;
+        let db = lock.get::<wh_database::shared::DatabaseKey>().unwrap();
+        let author = Some(msg.author.id.0);
+
+        if rate == "reset" {
+            wh_config::shared::remove_scoped_config::<crate::shared::PointsRate>(
+                db, scope, author,
+            )
+            .await?;
+            reply_message!(ctx, msg, format!(fluent!(POINTS_rate_reset), scope));
+            return Ok(());
+        }
+        let multiplier = match rate.parse::<f64>() {
+            Ok(m) if (0.0..=crate::shared::MAX_RATE).contains(&m) => m,
+            _ => message_err!(format!(
+                fluent!(POINTS_ARG_err_invalid_rate),
+                crate::shared::MAX_RATE
+            )),
+        };
+        wh_config::shared::update_scoped_config::<crate::shared::PointsRate, _, _>(
+            db,
+            scope,
+            author,
+            |r| {
+                r.multiplier = multiplier;
+                Ok(())
+            },
+        )
+        .await?;
+        reply_message!(ctx, msg, format!(fluent!(POINTS_rate_set), scope, multiplier));
+        Ok(())
+    }
 }
 
 mod role_cmd {
@@ -32,6 +32,4 @@ fn register_intent(
     intent | I::GUILD_MESSAGES | I::GUILDS | I::GUILD_MEMBERS
 }
 
-fn register_init() {
-    wh_permission::shared::user_permission::add_permission(&["points.manage"])
-}
+fn register_init() {}
@@ -205,8 +205,14 @@ pub async fn handle_user_message(ctx: &Context, msg: &Message) -> CommandResult
             crate::shared::create_user_if_not_exist(ctx, msg.author.id.0, msg.guild_id.unwrap().0)
                 .await?;
 
-        let _ = query!("UPDATE user_points SET points = points + random_between(10,20) WHERE userid = $1::int8 and guildid = $2::int8", wh_database::shared::Id(msg.author.id.0) as _, 
-        wh_database::shared::Id(msg.guild_id.unwrap().0) as _).execute(db).await?;
+        let scopes =
+            wh_config::shared::Scope::chain(msg.guild_id.unwrap().0, Some(msg.channel_id.0), None);
+        let rate = wh_config::shared::resolve_config_or_default::<PointsRate>(db, &scopes).await?;
+        // A template may have set any value, the points must fit in an int8
+        let multiplier = rate.multiplier.clamp(0.0, MAX_RATE);
+
+        let _ = query!("UPDATE user_points SET points = points + (random_between(10,20) * $3::float8)::int8 WHERE userid = $1::int8 and guildid = $2::int8", wh_database::shared::Id(msg.author.id.0) as _, 
+        wh_database::shared::Id(msg.guild_id.unwrap().0) as _, multiplier).execute(db).await?;
 
         // drop((db, timemap));
         drop(lock);
@@ -290,6 +296,7 @@ pub struct JoinEvent {
 impl wh_config::shared::Config for JoinEvent {
     const KEY: &'static str = "points.event.join";
 }
+wh_config::register_config!(JoinEvent);
 
 pub async fn handle_join_event(
     ctx: Context,
@@ -336,3 +343,23 @@ pub async fn handle_join_event(
 
     Ok(())
 }
+
+/// The highest multiplier, the points earned by a message still fit in an int8
+pub const MAX_RATE: f64 = 100.0;
+
+/// Multiplier applied to the points earned by talking, set per channel with a guild wide fallback
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PointsRate {
+    pub multiplier: f64,
+}
+
+impl Default for PointsRate {
+    fn default() -> Self {
+        Self { multiplier: 1.0 }
+    }
+}
+
+impl wh_config::shared::Config for PointsRate {
+    const KEY: &'static str = "points.rate";
+}
+wh_config::register_config!(PointsRate);
@@ -259,6 +259,7 @@ async fn generate_queue_nowplaying(http: &CacheHttp, data: wh_music::shared::Now
             }
         },
         added_by = username,
+        queue_loop = if data.queue_loop { "on" } else { "off" },
     )
 }
 #[post("/queue/now_playing", data = "<now_playing>")]
@@ -23,6 +23,55 @@ MUSIC_error_resuming={cross} Error when resuming!
 MUSIC_loop_disable=Looping has been disabled for the current song
 MUSIC_loop_enable_inf=Looping has been enabled for the current song
 MUSIC_loop_enable_num=Looping has been enabled for the current song ({"{}"} times)
+MUSIC_channel_disabled_here={cross} The music commands are disabled in this channel!
+MUSIC_button_missing_permission={cross} You don't have the permission `{"{}"}` required to use this button!
+MUSIC_channel_set=The music commands are now {"{}"} for the {"{}"}
+MUSIC_channel_reset=The music channel setting for the {"{}"} has been reset
+MUSIC_search_pick=Pick the songs to add in the menu or answer with their numbers, like `1 3` or `2-4`, anything else cancels
+MUSIC_search_menu=Songs to add
+MUSIC_search_timeout={cross} No song was picked in time
+MUSIC_search_cancelled=The search has been cancelled
+MUSIC_shuffled=Shuffled the {"{}"} upcoming songs
+MUSIC_seeked=Moved to {"{}"}
+MUSIC_not_seekable={cross} The current song can't be seeked!
+MUSIC_loop_queue_enable=The finished songs will be added back at the end of the queue
+MUSIC_loop_queue_disable=The finished songs won't be added back to the queue anymore
+MUSIC_volume_current=The volume is at {"{}"}%
+MUSIC_volume_set=The volume is now at {"{}"}%
+MUSIC_filter_current=The current filter is `{"{}"}`, the available filters are {"{}"}
+MUSIC_filter_set=The songs are now played with the filter `{"{}"}`
+MUSIC_skip_voted=Voted to skip the song ({"{}"}/{"{}"})
+MUSIC_skip_already_voted={cross} You already voted to skip this song! ({"{}"}/{"{}"})
+MUSIC_voteskip_current={"{}"}% of the listeners need to vote to skip a song
+MUSIC_voteskip_set={"{}"}% of the listeners will now need to vote to skip a song
+MUSIC_voteskip_disabled=Anyone can now skip a song without a vote
+MUSIC_now_playing_title=Now playing
+MUSIC_now_playing_duration=Duration
+MUSIC_now_playing_added_by=Added by
+MUSIC_now_playing_loop=Loops
+MUSIC_now_playing_current=The songs are announced in <#{"{}"}> as an {"{}"}, {"{}"} buttons
+MUSIC_now_playing_set=The songs will now be announced in <#{"{}"}> as an {"{}"}, {"{}"} buttons
+MUSIC_now_playing_disabled=The songs aren't announced
+MUSIC_autoplay_off=Nothing is played once the queue is empty
+MUSIC_autoplay_related=Songs related to the last one are played once the queue is empty
+MUSIC_library_not_configured={cross} No music library is set up for the bot!
+MUSIC_library_scanned=Found {"{}"} songs in the library
+MUSIC_history_empty={cross} No song has been played in this guild yet
+MUSIC_history_page=Page {"{}"}/{"{}"}
+MUSIC_autoplay_playlist=Songs of the playlist `{"{}"}` are played once the queue is empty
+MUSIC_record_announcement=🔴 <@{"{}"}> started recording <#{"{}"}> for {"{}"} minutes at most. Only the people who agree with the button below are recorded, and they can withdraw at any time
+MUSIC_record_ended=⏹ The recording of <#{"{}"}> is over
+MUSIC_record_consent_button=Record me
+MUSIC_record_withdraw_button=Don't record me
+MUSIC_record_consented=You will be recorded, your audio is only kept while you agree
+MUSIC_record_withdrawn=You aren't recorded anymore and your audio has been removed
+MUSIC_record_status=<#{"{}"}> has been recorded for {"{}"}, {"{}"} people agreed
+MUSIC_record_not_running={cross} Nothing is being recorded!
+MUSIC_record_already={cross} This guild is already being recorded!
+MUSIC_record_empty={cross} Nobody who agreed spoke during the recording
+MUSIC_record_too_big={cross} The recording is too big to be sent and no directory is set up to store it!
+MUSIC_record_sent=Here is the recording
+MUSIC_record_stored=The recording is too big to be sent, it has been stored in `{"{}"}`
 
 
 MUSIC_ARG_invalid_number={cross} You need to provide a valid number!
@@ -30,6 +79,18 @@ MUSIC_ARG_index_oob={cross} Number given is out of bound!
 MUSIC_ARG_playlist_name={cross} You need to provide a valid playlist name!
 MUSIC_ARG_playlist_name_too_long={cross} The provided name is too long (32 characters max)
 MUSIC_ARG_query_or_url={cross} You need to provide a valid url or a query!
+MUSIC_ARG_channel_mention={cross} You need to mention a valid channel!
+MUSIC_ARG_channel_action={cross} You need to choose between `enable`, `disable` and `reset`!
+MUSIC_ARG_timestamp={cross} You need to provide a valid time, like `1:23` or `1m30s`!
+MUSIC_ARG_volume={cross} The volume needs to be a number between 0 and 200!
+MUSIC_ARG_voteskip={cross} You need to provide a percentage between 0 and 100, or `off`!
+MUSIC_ARG_now_playing={cross} You need to choose between `image` and `embed`, optionally with `buttons`!
+MUSIC_ARG_autoplay={cross} You need to choose between `off`, `related` and `playlist`!
+MUSIC_ARG_record_format={cross} You need to choose between `ogg` and `wav`!
+MUSIC_ARG_filter={cross} Unknown filter, the available filters are {"{}"}
+
+MUSIC_PERMISSION_manage=Manage the queue, skip songs of others and choose where the music commands are allowed
+MUSIC_PERMISSION_record=Record the voice channel of the bot, with the consent of the people recorded
 
 MUSIC_LOG_err_pausing=Error when pausing: {"{}"}
 MUSIC_LOG_err_leaving_channel=Error when leaving channel: {"{}"}
@@ -37,6 +98,8 @@ MUSIC_LOG_err_resuming=Error when resuming: {"{}"}
 
 # ########################################################### #
 
+POINTS_PERMISSION_manage=Give and take points, set the level roles and the points rate
+
 POINTS_LOG_err_query=Error when executing a query: {"{}"}
 
 POINTS_role_exists={cross} This role is already registered!
@@ -44,7 +107,36 @@ POINTS_role_creation=The role has been set!
 POINTS_failed_delete_role={cross} Couldn't delete the role you asked for, maybe it wasn't setup!
 POINTS_success_delete_role=The role has been removed
 POINTS_role_dont_exists={cross} The given role doesn't exists
+POINTS_rate_set=The points multiplier for the {"{}"} is now `{"{}"}`
+POINTS_rate_reset=The points multiplier for the {"{}"} has been reset
 
 POINTS_ARG_err_user_missing_mention={cross} You need to mention someone!
 POINTS_ARG_err_role_mention_missing={cross} You need to mention a role!
-POINTS_ARG_err_invalid_number={cross} You need to input a valid number!
\ No newline at end of file
+POINTS_ARG_err_invalid_number={cross} You need to input a valid number!
+POINTS_ARG_err_invalid_rate={cross} You need to input a multiplier between 0 and {"{}"} or `reset`!
+POINTS_ARG_err_channel_mention={cross} You need to mention a valid channel!
+# ########################################################### #
+
+CONFIG_subcommands=This command is divided into differents subcommands: `history`, `rollback`, `export` and `apply`
+CONFIG_no_history={cross} There is no recorded change for `{"{}"}`!
+CONFIG_history_not_found={cross} No change with this id exists in this guild!
+CONFIG_rollback_done=`{"{}"}` has been rolled back to the change `#{"{}"}`
+
+CONFIG_template_exported=Here is the guild template, attach it to `config apply` in another guild to copy this setup
+CONFIG_template_invalid={cross} The template is invalid: {"{}"}
+CONFIG_template_no_change=The guild already matches the template
+CONFIG_language_current=Your language is `{"{}"}`
+CONFIG_language_set=Your language has been set to `{"{}"}`
+CONFIG_personal_history_not_found={cross} You made no change with this id!
+
+CONFIG_PERMISSION_manage=Look at the config history, roll it back and export or apply templates
+
+CONFIG_ARG_key_missing={cross} You need to provide a config key!
+CONFIG_ARG_invalid_id={cross} You need to provide a valid change id!
+CONFIG_ARG_invalid_format={cross} The template format must be `toml` or `yaml`!
+CONFIG_ARG_template_missing={cross} You need to attach a template file!
+CONFIG_ARG_invalid_apply={cross} The only option is `dry`, to only show the changes!
+CONFIG_ARG_invalid_language={cross} You need to provide a valid language code (like `en` or `fr`)!
+# ########################################################### #
+
+PERMISSION_PERMISSION_manage=Grant, deny and remove the permissions of users and roles